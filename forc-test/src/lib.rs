@@ -616,11 +616,11 @@ impl BuiltTests {
         test_runner_count: TestRunnerCount,
         test_filter: Option<TestFilter>,
     ) -> anyhow::Result<Tested> {
+        // Named so that the logs of each runner can be told apart with `display_thread_names`.
+        let builder = rayon::ThreadPoolBuilder::new().thread_name(|i| format!("test-runner-{i}"));
         let test_runners = match test_runner_count {
-            TestRunnerCount::Manual(runner_count) => rayon::ThreadPoolBuilder::new()
-                .num_threads(runner_count)
-                .build(),
-            TestRunnerCount::Auto => rayon::ThreadPoolBuilder::new().build(),
+            TestRunnerCount::Manual(runner_count) => builder.num_threads(runner_count).build(),
+            TestRunnerCount::Auto => builder.build(),
         }?;
         run_tests(self, &test_runners, test_filter)
     }
@@ -0,0 +1,131 @@
+//! Alignment of text by the columns it takes up on a terminal, for tables and status lines.
+
+use anstream::adapter::strip_str;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The number of columns `text` takes up on a terminal: without its escape codes, with wide
+/// characters, e.g. CJK, taking up two and combining characters none.
+pub(crate) fn visible_width(text: &str) -> usize {
+    strip_str(text).to_string().width()
+}
+
+/// `text` followed by as many spaces as make it take up `width` columns on a terminal, e.g. for
+/// the first column of a table.
+///
+/// Unlike `format!("{text:<width$}")`, this ignores escape codes and counts wide characters twice,
+/// so that colored and non-ASCII text lines up too. Text that is already at least as wide is
+/// returned as it is.
+///
+/// ```
+/// use forc_tracing::pad_visible;
+///
+/// assert_eq!(pad_visible("\x1b[32mok\x1b[0m", 4), "\x1b[32mok\x1b[0m  ");
+/// ```
+pub fn pad_visible(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(visible_width(text));
+    format!("{text}{}", " ".repeat(padding))
+}
+
+/// `text` preceded by as many spaces as make it take up `width` columns on a terminal, like
+/// `pad_visible` but aligned to the right, e.g. for numbers and the verbs of status lines.
+pub(crate) fn pad_visible_start(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(visible_width(text));
+    format!("{}{text}", " ".repeat(padding))
+}
+
+/// `text` with as much of its middle replaced by `…` as makes it take up at most `max_width`
+/// columns on a terminal, keeping its start and its end, e.g. for long paths:
+///
+/// ```
+/// use forc_tracing::truncate_middle;
+///
+/// assert_eq!(truncate_middle("/very/long/path/to/my_contract", 20), "/very/long…_contract");
+/// ```
+///
+/// Wide characters, e.g. CJK, count as two columns and are never cut in two. Text that already fits
+/// is returned as it is. Unlike `pad_visible`, this doesn't skip escape codes, so it is meant for
+/// text that isn't painted yet.
+pub fn truncate_middle(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    // The ellipsis takes up one column itself.
+    let Some(budget) = max_width.checked_sub(1) else {
+        return String::new();
+    };
+    // The start gets the extra column if the budget is odd, and the end whatever the start couldn't
+    // use because of a wide character.
+    let mut head_end = 0;
+    let mut head_width = 0;
+    for (i, c) in text.char_indices() {
+        let width = c.width().unwrap_or(0);
+        if head_width + width > budget.div_ceil(2) {
+            break;
+        }
+        head_width += width;
+        head_end = i + c.len_utf8();
+    }
+    let mut tail_start = text.len();
+    let mut tail_width = 0;
+    for (i, c) in text[head_end..].char_indices().rev() {
+        let width = c.width().unwrap_or(0);
+        if head_width + tail_width + width > budget {
+            break;
+        }
+        tail_width += width;
+        tail_start = head_end + i;
+    }
+    format!("{}…{}", &text[..head_end], &text[tail_start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::paint;
+    use anstyle::AnsiColor;
+
+    #[test]
+    fn test_pad_visible() {
+        let painted = paint("Compiling", AnsiColor::Green.on_default().bold());
+        let padded = pad_visible(&painted, 12);
+        assert_eq!(padded, format!("{painted}   "));
+        assert_eq!(visible_width(&padded), 12);
+        assert_eq!(pad_visible_start(&painted, 12), format!("   {painted}"));
+
+        // `é` is one column written as two bytes, and `编译` two columns each.
+        assert_eq!(pad_visible("café", 6), "café  ");
+        assert_eq!(visible_width(&pad_visible("编译", 6)), 6);
+        assert_eq!(pad_visible("编译", 6), "编译  ");
+        // Combining characters take up no column of their own.
+        assert_eq!(pad_visible("e\u{301}", 2), "e\u{301} ");
+        assert_eq!(pad_visible("Downloading", 4), "Downloading");
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        let path = "/very/long/path/to/my_contract";
+        assert_eq!(truncate_middle(path, path.len()), path);
+        for max_width in 0..path.len() {
+            let truncated = truncate_middle(path, max_width);
+            assert_eq!(truncated.width(), max_width);
+            if max_width > 0 {
+                let (start, end) = truncated.split_once('…').unwrap();
+                assert!(path.starts_with(start) && path.ends_with(end));
+                assert!(start.len() >= end.len());
+            }
+        }
+        assert_eq!(truncate_middle(path, 11), "/very…tract");
+        assert_eq!(truncate_middle(path, 1), "…");
+
+        // Each of these characters takes up two columns, so an odd budget can't be used up.
+        let cjk = "编译合约成功";
+        assert_eq!(truncate_middle(cjk, 12), cjk);
+        assert_eq!(truncate_middle(cjk, 9), "编译…成功");
+        assert_eq!(truncate_middle(cjk, 8), "编译…功");
+        assert_eq!(truncate_middle(cjk, 2), "…");
+        for max_width in 0..12 {
+            assert!(truncate_middle(cjk, max_width).width() <= max_width);
+        }
+        assert_eq!(truncate_middle("编译 my_contract", 10), "编译 …ract");
+    }
+}
@@ -0,0 +1,119 @@
+//! Backtraces attached to ERROR events, see `TracingSubscriberOptions::error_backtraces`.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use crate::panic::PANIC_TARGET;
+use std::backtrace::Backtrace;
+use std::env;
+use std::io::Write;
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::writer::EitherWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Enables the backtraces when set to anything other than `0`, like for panics.
+const RUST_BACKTRACE: &str = "RUST_BACKTRACE";
+
+/// Adds a backtrace to the ERROR events written through the wrapped writer if `enabled`, below
+/// the event in the text formats and as a `backtrace` field in the JSON format.
+///
+/// The backtrace is captured as the event is written, so it shows where the event was logged
+/// from, below the frames of the subscriber itself. Panics already include one of their own.
+pub(crate) struct BacktraceWriter<W> {
+    inner: W,
+    enabled: bool,
+    json: bool,
+}
+
+impl<W> BacktraceWriter<W> {
+    pub(crate) fn new(inner: W, enabled: bool, json: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            json,
+        }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for BacktraceWriter<W> {
+    type Writer = EitherWriter<BoxedEvent<'a, W::Writer>, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        EitherWriter::B(self.inner.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        let inner = self.inner.make_writer_for(meta);
+        // Checked first, as capturing a backtrace is expensive.
+        let capture = self.enabled
+            && *meta.level() == Level::ERROR
+            && meta.target() != PANIC_TARGET
+            && env::var_os(RUST_BACKTRACE).is_some_and(|backtrace| backtrace != "0");
+        if !capture {
+            return EitherWriter::B(inner);
+        }
+        // Forced, as `Backtrace::capture` only reads the environment once per process.
+        let backtrace = Backtrace::force_capture().to_string();
+        let json = self.json;
+        // Written out with the backtrace, after the event or as the first member of its object.
+        EitherWriter::A(BufferedEvent::boxed(
+            inner,
+            move |buffer, inner| match buffer.strip_prefix(b"{") {
+                Some(fields) if json => {
+                    let backtrace = serde_json::to_string(&backtrace)?;
+                    write!(inner, "{{\"backtrace\":{backtrace},")?;
+                    inner.write_all(fields)
+                }
+                _ => {
+                    inner.write_all(buffer)?;
+                    writeln!(inner, "stack backtrace:\n{}", backtrace.trim_end())
+                }
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::with_captured_logs;
+    use crate::{LogFormat, TracingSubscriberOptions, ENV_LOCK};
+
+    fn capture(format: LogFormat) -> String {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        env::set_var(RUST_BACKTRACE, "1");
+        let options = TracingSubscriberOptions::builder()
+            .format(format)
+            .display_time(false)
+            .error_backtraces(true)
+            .build();
+        let output = with_captured_logs(options, || {
+            tracing::info!("Compiling");
+            tracing::error!("Internal compiler error");
+        });
+        env::remove_var(RUST_BACKTRACE);
+        output
+    }
+
+    #[test]
+    fn test_error_backtraces() {
+        let output = capture(LogFormat::Pretty);
+        let (info, error) = output.split_once('\n').unwrap();
+        assert_eq!(info, "Compiling");
+        let backtrace = error
+            .strip_prefix("Internal compiler error\nstack backtrace:\n")
+            .unwrap();
+        assert!(backtrace.contains("backtrace::tests::capture"));
+    }
+
+    #[test]
+    fn test_error_backtraces_json() {
+        let output = capture(LogFormat::Json);
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(events[0].get("backtrace").is_none());
+        assert_eq!(events[1]["message"], "Internal compiler error");
+        let backtrace = events[1]["backtrace"].as_str().unwrap();
+        assert!(backtrace.contains("backtrace::tests::capture"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Collapsing runs of blank lines, see `TracingSubscriberOptions::collapse_blank_lines`.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use anstream::adapter::strip_str;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::Metadata;
+use tracing_subscriber::fmt::writer::EitherWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Drops the output of each event written through the wrapped writer that is blank, i.e. nothing
+/// but whitespace and escape codes, if the event before it was blank too, if `enabled`.
+pub(crate) struct BlankLineWriter<W> {
+    inner: W,
+    enabled: bool,
+    // Whether the output of the last event written was blank.
+    previous_blank: AtomicBool,
+}
+
+impl<W> BlankLineWriter<W> {
+    pub(crate) fn new(inner: W, enabled: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            previous_blank: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for BlankLineWriter<W> {
+    type Writer = EitherWriter<BoxedEvent<'a, W::Writer>, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.wrap(self.inner.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.wrap(self.inner.make_writer_for(meta))
+    }
+}
+
+impl<W> BlankLineWriter<W> {
+    /// Write the output of the event written to `inner` unless it repeats a blank line.
+    fn wrap<'a, E: io::Write>(&'a self, inner: E) -> EitherWriter<BoxedEvent<'a, E>, E> {
+        if !self.enabled {
+            return EitherWriter::B(inner);
+        }
+        let previous_blank = &self.previous_blank;
+        EitherWriter::A(BufferedEvent::boxed(inner, move |buffer, inner| {
+            let blank = strip_str(&String::from_utf8_lossy(buffer))
+                .to_string()
+                .trim()
+                .is_empty();
+            // Swapped, so that of concurrent blank events only one is written.
+            let repeated = match blank {
+                true => previous_blank.swap(true, Ordering::Relaxed),
+                false => {
+                    previous_blank.store(false, Ordering::Relaxed);
+                    false
+                }
+            };
+            match repeated {
+                true => Ok(()),
+                false => inner.write_all(buffer),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, TracingSubscriberOptions};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn capture(options: TracingSubscriberOptions, ansi: bool, f: impl FnOnce()) -> String {
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&options, ansi, None, capture.clone()).unwrap();
+        let subscriber = Registry::default().with(layer);
+        tracing::subscriber::with_default(subscriber, f);
+        capture.contents()
+    }
+
+    fn log() {
+        tracing::info!("Compiling");
+        tracing::info!("");
+        tracing::info!("");
+        tracing::info!("   ");
+        tracing::info!("Finished");
+        tracing::info!("");
+        tracing::info!("\t");
+        tracing::info!("  indented");
+        tracing::info!("");
+    }
+
+    #[test]
+    fn test_collapse_blank_lines() {
+        let options = TracingSubscriberOptions::builder()
+            .collapse_blank_lines(true)
+            .build();
+        assert_eq!(
+            capture(options.clone(), false, log),
+            "Compiling\n\nFinished\n\n  indented\n\n"
+        );
+        // Escape codes alone don't make a line visible.
+        assert_eq!(
+            capture(options, true, || {
+                tracing::info!("\x1b[1m\x1b[0m");
+                tracing::info!("");
+                tracing::info!("\x1b[1mFinished\x1b[0m");
+            }),
+            "\x1b[1m\x1b[0m\n\x1b[1mFinished\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_blank_lines_kept_by_default() {
+        let output = capture(TracingSubscriberOptions::default(), false, log);
+        assert_eq!(output, "Compiling\n\n\n   \nFinished\n\n\t\n  indented\n\n");
+    }
+}
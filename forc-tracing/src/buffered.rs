@@ -0,0 +1,90 @@
+//! The writer for one event that the writers transforming whole events are built on.
+
+use std::io;
+
+/// Buffers the output of one event, and passes it to `transform` with the wrapped writer to write
+/// out, e.g. with a field inserted into its JSON object, when flushed or dropped.
+///
+/// The output is only passed on if something was written, but the wrapped writer is flushed either
+/// way.
+pub(crate) struct BufferedEvent<W: io::Write, F: FnMut(&[u8], &mut W) -> io::Result<()>> {
+    inner: W,
+    transform: F,
+    buffer: Vec<u8>,
+}
+
+/// The transform of a `BufferedEvent` whose type is named, e.g. as a `MakeWriter::Writer`.
+pub(crate) type Transform<'a, W> = Box<dyn FnMut(&[u8], &mut W) -> io::Result<()> + 'a>;
+
+/// A `BufferedEvent` with a boxed transform.
+pub(crate) type BoxedEvent<'a, W> = BufferedEvent<W, Transform<'a, W>>;
+
+impl<W: io::Write, F: FnMut(&[u8], &mut W) -> io::Result<()>> BufferedEvent<W, F> {
+    pub(crate) fn new(inner: W, transform: F) -> Self {
+        Self {
+            inner,
+            transform,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<'a, W: io::Write> BufferedEvent<W, Transform<'a, W>> {
+    pub(crate) fn boxed(
+        inner: W,
+        transform: impl FnMut(&[u8], &mut W) -> io::Result<()> + 'a,
+    ) -> Self {
+        Self::new(inner, Box::new(transform))
+    }
+}
+
+impl<W: io::Write, F: FnMut(&[u8], &mut W) -> io::Result<()>> io::Write for BufferedEvent<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            (self.transform)(&buffer, &mut self.inner)?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: io::Write, F: FnMut(&[u8], &mut W) -> io::Result<()>> Drop for BufferedEvent<W, F> {
+    fn drop(&mut self) {
+        // There's nowhere left to report a failure to write the log output itself.
+        let _ = io::Write::flush(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use std::io::Write;
+
+    #[test]
+    fn test_buffered_event() {
+        let capture = CapturingWriter::new();
+        let mut event = BufferedEvent::new(
+            capture.clone(),
+            |buffer: &[u8], inner: &mut CapturingWriter| {
+                inner.write_all(&buffer.to_ascii_uppercase())
+            },
+        );
+        event.write_all(b"comp").unwrap();
+        event.write_all(b"iling\n").unwrap();
+        assert_eq!(capture.contents(), "");
+        drop(event);
+        assert_eq!(capture.contents(), "COMPILING\n");
+
+        // Nothing is passed on if nothing was written.
+        let event = BufferedEvent::new(capture.clone(), |_: &[u8], _: &mut CapturingWriter| {
+            panic!("nothing to transform")
+        });
+        drop(event);
+    }
+}
@@ -0,0 +1,147 @@
+//! A machine-readable summary of a whole build, e.g. for a CI system to keep as an artifact.
+
+use crate::counts::log_counts;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The rollup of a build, accumulated while it runs and written with `write_summary_json` at its
+/// end.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildSummary {
+    /// Whether the build succeeded, i.e. no errors were logged, see `record_log_counts`.
+    pub success: bool,
+    pub warnings: usize,
+    pub errors: usize,
+    /// How long the whole build took.
+    pub duration_ms: u64,
+    /// The phases of the build in the order they ran, e.g. parsing or type-checking a package.
+    pub phases: Vec<PhaseSummary>,
+    /// The packages or other targets built.
+    pub targets: Vec<String>,
+    /// The files the build produced, e.g. the bytecode and ABI of a contract.
+    pub artifacts: Vec<PathBuf>,
+}
+
+/// How long one phase of a build took.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseSummary {
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+impl BuildSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_phase(&mut self, name: impl Into<String>, duration: Duration) {
+        self.phases.push(PhaseSummary {
+            name: name.into(),
+            duration_ms: millis(duration),
+        });
+    }
+
+    pub fn add_target(&mut self, target: impl Into<String>) {
+        self.targets.push(target.into());
+    }
+
+    pub fn add_artifact(&mut self, path: impl Into<PathBuf>) {
+        self.artifacts.push(path.into());
+    }
+
+    /// Set how long the whole build took, e.g. to the `CommandTimer::elapsed` of the command.
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration_ms = millis(duration);
+    }
+
+    /// Take the number of warnings and errors from those logged so far, see `log_counts`, so
+    /// that the summary agrees with the output, and whether the build succeeded from there being
+    /// no errors.
+    pub fn record_log_counts(&mut self) {
+        let counts = log_counts();
+        self.warnings = counts.warnings;
+        self.errors = counts.errors;
+        self.success = counts.errors == 0;
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Write `summary` to `path` as JSON, creating its parent directories if missing and replacing the
+/// file if it exists.
+pub fn write_summary_json(path: &Path, summary: &BuildSummary) -> io::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let mut json = serde_json::to_vec_pretty(summary)?;
+    json.push(b'\n');
+    fs::write(path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_subscriber, TracingSubscriberOptions, TracingWriterMode, ENV_LOCK};
+
+    #[test]
+    fn test_write_summary_json() {
+        let mut summary = BuildSummary::new();
+        summary.add_phase("parse", Duration::from_millis(42));
+        summary.add_phase("typecheck", Duration::from_micros(120_900));
+        summary.add_target("my_contract");
+        summary.add_artifact("out/debug/my_contract.bin");
+        summary.set_duration(Duration::from_secs(3));
+        summary.warnings = 2;
+        summary.success = true;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("target").join("summary.json");
+        write_summary_json(&path, &summary).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let parsed: BuildSummary = serde_json::from_str(&contents).unwrap();
+        assert_eq!(parsed, summary);
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "success": true,
+                "warnings": 2,
+                "errors": 0,
+                "duration_ms": 3000,
+                "phases": [
+                    {"name": "parse", "duration_ms": 42},
+                    {"name": "typecheck", "duration_ms": 120},
+                ],
+                "targets": ["my_contract"],
+                "artifacts": ["out/debug/my_contract.bin"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_record_log_counts() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Null)
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        let mut summary = BuildSummary::new();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "forc", "Unused");
+            tracing::error!(target: "forc", "Failed");
+        });
+        summary.record_log_counts();
+        // Other tests may log meanwhile, as the counters are shared.
+        assert!(summary.warnings >= 1);
+        assert!(summary.errors >= 1);
+        assert!(!summary.success);
+    }
+}
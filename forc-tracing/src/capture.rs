@@ -0,0 +1,161 @@
+//! Scoped redirection of the log output of a thread, e.g. of an operation that a command runs on
+//! behalf of another.
+
+use crate::color::StripAnsi;
+use crate::testing::CapturingWriter;
+use std::cell::RefCell;
+use std::io;
+
+/// The buffer that `with_writer_override` redirects the log output to.
+///
+/// ANSI color is stripped from the captured output unless `with_ansi(true)` is set.
+#[derive(Clone, Debug, Default)]
+pub struct WriterOverride {
+    buffer: CapturingWriter,
+    ansi: bool,
+}
+
+impl WriterOverride {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the color that the terminal would have been shown in the captured output.
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    fn writer(&self) -> Box<dyn io::Write> {
+        match self.ansi {
+            true => Box::new(self.buffer.clone()),
+            false => Box::new(StripAnsi::new(self.buffer.clone())),
+        }
+    }
+}
+
+/// Capture into the given writer, which keeps what was written to it before.
+impl From<CapturingWriter> for WriterOverride {
+    fn from(buffer: CapturingWriter) -> Self {
+        Self {
+            buffer,
+            ansi: false,
+        }
+    }
+}
+
+thread_local! {
+    // The overrides in effect on this thread, innermost last.
+    static OVERRIDES: RefCell<Vec<WriterOverride>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Run `f` with the log output of the current thread redirected to `writer`, returning its result
+/// and the output it captured.
+///
+/// The events that would have been written to stdout, stderr or a `TracingWriterMode::Custom`
+/// writer are written to `writer` instead, while log files are still appended to. Other threads
+/// are unaffected, including the background writers of `non_blocking`, as each event is
+/// formatted on the thread that logs it. Overrides nest: the innermost one captures the output of
+/// `f` until it returns, after which the enclosing one is in effect again, even if `f` panics.
+///
+/// ```
+/// use forc_tracing::{init_tracing_subscriber, with_writer_override, WriterOverride};
+///
+/// init_tracing_subscriber(Default::default());
+/// let (result, output) = with_writer_override(WriterOverride::new(), || {
+///     tracing::info!("Compiling");
+///     42
+/// });
+/// assert_eq!((result, output.as_str()), (42, "Compiling\n"));
+/// ```
+pub fn with_writer_override<R>(
+    writer: impl Into<WriterOverride>,
+    f: impl FnOnce() -> R,
+) -> (R, String) {
+    let writer = writer.into();
+    let start = writer.buffer.contents().len();
+    OVERRIDES.with(|overrides| overrides.borrow_mut().push(writer.clone()));
+    let restore = Restore;
+    let result = f();
+    drop(restore);
+    let captured = writer.buffer.contents()[start..].to_string();
+    (result, captured)
+}
+
+/// Removes the innermost override of the thread when dropped.
+struct Restore;
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        OVERRIDES.with(|overrides| overrides.borrow_mut().pop());
+    }
+}
+
+/// A writer for one event to the innermost override of the current thread, if any.
+pub(crate) fn override_writer() -> Option<Box<dyn io::Write>> {
+    OVERRIDES.with(|overrides| overrides.borrow().last().map(WriterOverride::writer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_subscriber, CustomWriter, TracingSubscriberOptions, TracingWriterMode};
+    use tracing::Dispatch;
+
+    /// A subscriber writing to `capture` when its output isn't overridden.
+    fn dispatch(capture: &CapturingWriter) -> Dispatch {
+        let capture = capture.clone();
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Custom(CustomWriter::new(move || {
+                Box::new(capture.clone())
+            })))
+            .build();
+        Dispatch::new(build_subscriber(options).unwrap())
+    }
+
+    #[test]
+    fn test_nested_overrides() {
+        let _guard = crate::ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let terminal = CapturingWriter::new();
+        let outer = tracing::dispatcher::with_default(&dispatch(&terminal), || {
+            tracing::info!(target: "forc", "Resolving");
+            let (inner, outer) = with_writer_override(WriterOverride::new(), || {
+                tracing::info!(target: "forc", "Fetching std");
+                let (result, inner) = with_writer_override(WriterOverride::new(), || {
+                    tracing::warn!(target: "forc", "Building std");
+                    7
+                });
+                tracing::info!(target: "forc", "Fetched std");
+                (result, inner)
+            });
+            tracing::info!(target: "forc", "Resolved");
+            assert_eq!(inner, (7, "Building std\n".to_string()));
+            outer
+        });
+        assert_eq!(outer, "Fetching std\nFetched std\n");
+        assert_eq!(terminal.contents(), "Resolving\nResolved\n");
+    }
+
+    #[test]
+    fn test_override_is_thread_local() {
+        let _guard = crate::ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let terminal = CapturingWriter::new();
+        let dispatch = dispatch(&terminal);
+        let captured = CapturingWriter::new();
+        let ((), output) = with_writer_override(captured.clone(), || {
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    tracing::dispatcher::with_default(&dispatch, || {
+                        tracing::info!(target: "forc", "Elsewhere");
+                    })
+                });
+            });
+            tracing::dispatcher::with_default(&dispatch, || {
+                tracing::info!(target: "forc", "Here");
+            });
+        });
+        assert_eq!(output, "Here\n");
+        assert_eq!(captured.contents(), "Here\n");
+        assert_eq!(terminal.contents(), "Elsewhere\n");
+    }
+}
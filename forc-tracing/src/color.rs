@@ -0,0 +1,550 @@
+//! Resolution of whether ANSI escape codes should be emitted on each output stream.
+
+use anstream::adapter::StripBytes;
+use anstyle::Style;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::Level;
+
+/// A standard stream that log lines can be routed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub(crate) fn is_terminal(self) -> bool {
+        match self {
+            Stream::Stdout => io::stdout().is_terminal(),
+            Stream::Stderr => io::stderr().is_terminal(),
+        }
+    }
+}
+
+/// Disables color when set to any value, see <https://no-color.org>.
+const NO_COLOR: &str = "NO_COLOR";
+/// Disables color when set to `0`, see <https://bixense.com/clicolors>.
+const CLICOLOR: &str = "CLICOLOR";
+/// Forces color on when set to anything other than `0`, even if the stream isn't a terminal.
+const CLICOLOR_FORCE: &str = "CLICOLOR_FORCE";
+/// `always`, `never` or `auto`, for compatibility with `env_logger`.
+const RUST_LOG_STYLE: &str = "RUST_LOG_STYLE";
+/// The type of the terminal, `dumb` for those that don't interpret escape codes, e.g. the shells
+/// of Emacs.
+const TERM: &str = "TERM";
+/// Set to `truecolor` or `24bit` by terminals that show RGB colors.
+pub(crate) const COLORTERM: &str = "COLORTERM";
+
+/// Decide whether ANSI color should be written to `stream`.
+///
+/// In order of precedence:
+///
+/// 1. `CLICOLOR_FORCE` set to anything other than `0` enables color.
+/// 2. An explicit `ansi` choice.
+/// 3. `RUST_LOG_STYLE=always` enables color and `RUST_LOG_STYLE=never` disables it. Like with
+///    `env_logger`, `auto` and any other value defer to the rest.
+/// 4. `NO_COLOR` set to any value, or `CLICOLOR=0`, disables color, and so does `TERM=dumb`, or
+///    `TERM` unset on Unix, even if the stream is a terminal.
+/// 5. Color is enabled only if the stream is a terminal.
+pub(crate) fn resolve_ansi(ansi: Option<bool>, stream: Stream) -> bool {
+    if env::var_os(CLICOLOR_FORCE).is_some_and(|force| force != "0") {
+        return true;
+    }
+    if let Some(ansi) = ansi {
+        return ansi;
+    }
+    match env::var_os(RUST_LOG_STYLE) {
+        Some(style) if style == "always" => return true,
+        Some(style) if style == "never" => return false,
+        _ => {}
+    }
+    if env::var_os(NO_COLOR).is_some() || env::var_os(CLICOLOR).is_some_and(|c| c == "0") {
+        return false;
+    }
+    term_shows_color() && stream.is_terminal()
+}
+
+/// Whether `TERM` names a terminal that interprets escape codes, as far as can be told. Windows
+/// consoles don't set it.
+fn term_shows_color() -> bool {
+    match env::var_os(TERM) {
+        Some(term) => term != "dumb",
+        None => !cfg!(unix),
+    }
+}
+
+/// Whether `COLORTERM` says that the terminal shows RGB colors.
+pub(crate) fn truecolor() -> bool {
+    env::var(COLORTERM).is_ok_and(|term| term == "truecolor" || term == "24bit")
+}
+
+/// What `detect_capabilities` found out about the terminal and the color environment variables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputCapabilities {
+    pub stdout_is_tty: bool,
+    pub stderr_is_tty: bool,
+    /// Whether the subscriber would show color on either stream without an explicit `ansi`
+    /// choice, as decided by the variables below and the streams being terminals.
+    pub color_enabled: bool,
+    /// Whether RGB colors are shown as they are rather than as the closest basic colors, as
+    /// `COLORTERM` says.
+    pub truecolor: bool,
+    /// The value of `TERM`, if set.
+    pub term: Option<String>,
+    /// Whether `NO_COLOR` is set.
+    pub no_color_set: bool,
+    /// The value of `CLICOLOR`, if set to a number.
+    pub clicolor: Option<i32>,
+}
+
+/// Detect what the output can show the way the subscriber does, e.g. for a forc subcommand to
+/// print to answer why its output isn't colored:
+///
+/// ```
+/// let capabilities = forc_tracing::detect_capabilities();
+/// println!("{capabilities:#?}");
+/// ```
+///
+/// The environment variables are read at the time of the call, so this reflects the decision of a
+/// subscriber initialized with them, without taking the `ansi` option into account.
+pub fn detect_capabilities() -> OutputCapabilities {
+    OutputCapabilities {
+        stdout_is_tty: Stream::Stdout.is_terminal(),
+        stderr_is_tty: Stream::Stderr.is_terminal(),
+        color_enabled: resolve_ansi(None, Stream::Stdout) || resolve_ansi(None, Stream::Stderr),
+        truecolor: truecolor(),
+        term: env::var(TERM).ok(),
+        no_color_set: env::var_os(NO_COLOR).is_some(),
+        clicolor: env::var(CLICOLOR).ok().and_then(|value| value.parse().ok()),
+    }
+}
+
+// The color decision for events of each level, indexed by `level_index`. These are set as the
+// subscriber is installed by `init_tracing_subscriber`, but not by `build_subscriber`, so that the
+// `println_*` helpers paint their text only when the stream their level is routed to would show it.
+static LEVEL_COLOR: [AtomicBool; 5] = [
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+];
+
+fn level_index(level: &Level) -> usize {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+/// Record whether events at `level` are written to a stream that shows color.
+pub(crate) fn set_level_color(level: &Level, enabled: bool) {
+    LEVEL_COLOR[level_index(level)].store(enabled, Ordering::Relaxed);
+}
+
+/// Whether text logged at `level` should be painted with ANSI color.
+pub(crate) fn level_color(level: &Level) -> bool {
+    global_color_enabled() && LEVEL_COLOR[level_index(level)].load(Ordering::Relaxed)
+}
+
+// The switch of `set_global_color`, set as the subscriber is installed by `init_tracing_subscriber`
+// to whether any stream shows color.
+static GLOBAL_COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Turn ANSI color on or off for all of the output at runtime, e.g. when an interactive tool finds
+/// its output redirected or the user changes a setting.
+///
+/// Both the subscriber and the `println_*` helpers check the switch as each line is written. It
+/// only gates the color decision made when the subscriber was installed by one of the
+/// `init_tracing_subscriber` functions, which seeds it, while `build_subscriber` leaves it: turning
+/// it back on shows color again on the streams that showed it before, but never adds escape codes
+/// to a stream that doesn't show color, e.g. one that is redirected.
+pub fn set_global_color(enabled: bool) {
+    GLOBAL_COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether ANSI color is currently turned on, see `set_global_color`.
+pub fn global_color_enabled() -> bool {
+    GLOBAL_COLOR.load(Ordering::Relaxed)
+}
+
+/// Enable the processing of ANSI escape codes by the Windows console that `handle` refers to.
+///
+/// Consoles don't always have it enabled by default and would print the escape codes literally
+/// instead. Succeeds without doing anything if `handle` isn't a console, e.g. a pipe, a file or a
+/// terminal emulator like mintty that processes escape codes itself.
+#[cfg(windows)]
+pub(crate) fn enable_virtual_terminal(
+    handle: &impl std::os::windows::io::AsRawHandle,
+) -> io::Result<()> {
+    use windows_sys::Win32::System::Console::{
+        GetConsoleMode, SetConsoleMode, CONSOLE_MODE, ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+    };
+
+    let handle = handle.as_raw_handle() as isize;
+    let mut mode: CONSOLE_MODE = 0;
+    // SAFETY: `handle` is a valid handle for as long as it is borrowed, and `mode` outlives the call.
+    unsafe {
+        if GetConsoleMode(handle, &mut mode) == 0 {
+            return Ok(());
+        }
+        if mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING == 0
+            && SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Wrap `txt` in the escape codes for `style`, one of the crate's or of `anstyle`.
+pub(crate) fn paint(txt: &str, style: impl Into<Style>) -> String {
+    let style = style.into();
+    format!("{}{txt}{}", style.render(), style.render_reset())
+}
+
+/// An `io::Write` adapter that removes ANSI escape sequences from everything written through it.
+///
+/// Used for streams that are not shown color, so that escape codes emitted by the formatter for
+/// other streams don't end up as noise in redirected output.
+///
+/// Unlike `anstream::StripStream`, this works for any writer rather than only the standard streams
+/// and files, e.g. the background writers of `non_blocking`.
+///
+/// The formatter only ever writes valid UTF-8, but raw bytes may be written past it, e.g. the
+/// output of an external tool. Invalid sequences are replaced by `U+FFFD` first, like
+/// `String::from_utf8_lossy` does, as the stripping would drop or garble them.
+pub(crate) struct StripAnsi<W> {
+    inner: W,
+    state: StripBytes,
+    // The start of a sequence cut off at the end of the last write, completed by the next one.
+    pending: Vec<u8>,
+}
+
+impl<W> StripAnsi<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self {
+            inner,
+            state: StripBytes::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for StripAnsi<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(buf);
+        let text = decode_lossy(&bytes, &mut self.pending);
+        // Escape sequences split across writes are still recognized, as the state is kept.
+        for printable in self.state.strip_next(text.as_bytes()) {
+            self.inner.write_all(printable)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // The events are flushed whole, so a sequence still cut off is invalid.
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.inner
+                .write_all(char::REPLACEMENT_CHARACTER.to_string().as_bytes())?;
+        }
+        self.inner.flush()
+    }
+}
+
+/// `bytes` as UTF-8 with each invalid sequence replaced by `U+FFFD`, except for a sequence cut off
+/// at the end, which is left in `pending` instead.
+fn decode_lossy(mut bytes: &[u8], pending: &mut Vec<u8>) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(valid) => {
+                text.push_str(valid);
+                return text;
+            }
+            Err(err) => {
+                let (valid, invalid) = bytes.split_at(err.valid_up_to());
+                // Checked by `from_utf8` already.
+                text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                match err.error_len() {
+                    Some(len) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        bytes = &invalid[len..];
+                    }
+                    None => {
+                        *pending = invalid.to_vec();
+                        return text;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::LEVELS;
+    use crate::{theme, with_writer_override, TracingSubscriberOptions, WriterOverride, ENV_LOCK};
+    use anstyle::AnsiColor;
+    use std::io::Write;
+
+    /// Run `f` with the color environment variables set to `vars`, and all others unset but for
+    /// `TERM`, which is that of a terminal that shows color unless given.
+    fn with_color_env(vars: &[(&str, &str)], f: impl FnOnce()) {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let names = [
+            TERM,
+            NO_COLOR,
+            CLICOLOR,
+            CLICOLOR_FORCE,
+            RUST_LOG_STYLE,
+            COLORTERM,
+        ];
+        let names = names.into_iter().chain(vars.iter().map(|(var, _)| *var));
+        // Dropped before the lock, even if `f` panics, so that no other test sees the variables.
+        let _restore = RestoreEnv(
+            names
+                .map(|var| (var.to_string(), env::var_os(var)))
+                .collect(),
+        );
+        for var in [
+            NO_COLOR,
+            CLICOLOR,
+            CLICOLOR_FORCE,
+            RUST_LOG_STYLE,
+            COLORTERM,
+        ] {
+            env::remove_var(var);
+        }
+        env::set_var(TERM, "xterm-256color");
+        for (var, value) in vars {
+            env::set_var(var, value);
+        }
+        f();
+    }
+
+    /// Sets the environment variables back to the values they had, or unsets them, when dropped.
+    struct RestoreEnv(Vec<(String, Option<std::ffi::OsString>)>);
+
+    impl Drop for RestoreEnv {
+        fn drop(&mut self) {
+            for (var, value) in &self.0 {
+                match value {
+                    Some(value) => env::set_var(var, value),
+                    None => env::remove_var(var),
+                }
+            }
+        }
+    }
+
+    /// Assert that `println_red` and `println_red_err` print in color to stdout and stderr as
+    /// `stdout` and `stderr` say, once the subscriber built with `ansi` under the current
+    /// environment has seeded their color like installing it does.
+    fn assert_printed(ansi: Option<bool>, stdout: bool, stderr: bool) {
+        let options = TracingSubscriberOptions {
+            ansi,
+            ..Default::default()
+        };
+        let (subscriber, _guard, globals) = crate::build(&options, Vec::new()).unwrap();
+        let dispatch = tracing::Dispatch::new(subscriber);
+        for (level, enabled) in LEVELS.iter().zip(globals.level_colors) {
+            set_level_color(level, enabled);
+        }
+        set_global_color(globals.any_color);
+        let print = |print: fn(&str)| {
+            let (_, printed) = tracing::dispatcher::with_default(&dispatch, || {
+                with_writer_override(WriterOverride::new().with_ansi(true), || print("Failed"))
+            });
+            printed
+        };
+        let (out, err) = (print(crate::println_red), print(crate::println_red_err));
+        for level in &LEVELS {
+            set_level_color(level, false);
+        }
+        set_global_color(true);
+        let red = |ansi| crate::paint("Failed", theme::theme().error.on_default(), ansi);
+        assert_eq!(out, format!("{}\n", red(stdout)));
+        assert_eq!(err, format!("{}\n", red(stderr)));
+    }
+
+    fn strip(text: &str) -> String {
+        let mut writer = StripAnsi::new(Vec::new());
+        writer.write_all(text.as_bytes()).unwrap();
+        String::from_utf8(writer.inner).unwrap()
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let painted = format!(
+            "{} and {}",
+            paint("red", AnsiColor::Red.on_default()),
+            paint("green", AnsiColor::Green.on_default().bold())
+        );
+        assert_eq!(strip(&painted), "red and green");
+        assert_eq!(strip("\x1b]8;;file:///a\x1b\\a\x1b]8;;\x1b\\"), "a");
+    }
+
+    #[test]
+    fn test_strip_ansi_writer_across_writes() {
+        let mut writer = StripAnsi::new(Vec::new());
+        writer.write_all(b"\x1b[3").unwrap();
+        writer.write_all(b"1mred\x1b[0m").unwrap();
+        assert_eq!(writer.inner, b"red");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_enable_virtual_terminal_on_non_console() {
+        let file = tempfile::tempfile().unwrap();
+        enable_virtual_terminal(&file).unwrap();
+    }
+
+    #[test]
+    fn test_explicit_ansi_overrides_detection() {
+        with_color_env(&[], || {
+            assert!(resolve_ansi(Some(true), Stream::Stdout));
+            assert!(!resolve_ansi(Some(false), Stream::Stderr));
+            assert_printed(Some(true), true, true);
+            assert_printed(Some(false), false, false);
+        });
+    }
+
+    #[test]
+    fn test_no_color_disables_ansi() {
+        with_color_env(&[(NO_COLOR, "1")], || {
+            assert!(!resolve_ansi(None, Stream::Stdout));
+            // An explicit choice still takes precedence over `NO_COLOR`.
+            assert!(resolve_ansi(Some(true), Stream::Stdout));
+            assert_printed(None, false, false);
+            assert_printed(Some(true), true, true);
+        });
+        with_color_env(&[(CLICOLOR, "0")], || {
+            assert!(!resolve_ansi(None, Stream::Stderr));
+            assert_printed(None, false, false);
+        });
+    }
+
+    #[test]
+    fn test_clicolor_force_enables_ansi() {
+        with_color_env(&[(CLICOLOR_FORCE, "1"), (NO_COLOR, "1")], || {
+            assert!(resolve_ansi(None, Stream::Stdout));
+            assert!(resolve_ansi(Some(false), Stream::Stderr));
+            assert_printed(None, true, true);
+            assert_printed(Some(false), true, true);
+        });
+        with_color_env(&[(CLICOLOR_FORCE, "0"), (NO_COLOR, "1")], || {
+            assert!(!resolve_ansi(None, Stream::Stdout));
+            assert_printed(None, false, false);
+        });
+    }
+
+    #[test]
+    fn test_dumb_terminal_disables_ansi() {
+        with_color_env(&[(TERM, "dumb")], || {
+            assert!(!term_shows_color());
+            assert!(!resolve_ansi(None, Stream::Stdout));
+            assert!(!resolve_ansi(None, Stream::Stderr));
+            // The explicit choices still take precedence.
+            assert!(resolve_ansi(Some(true), Stream::Stdout));
+            assert_printed(None, false, false);
+            assert_printed(Some(true), true, true);
+        });
+        with_color_env(&[(TERM, "dumb"), (CLICOLOR_FORCE, "1")], || {
+            assert!(resolve_ansi(None, Stream::Stdout));
+            assert_printed(None, true, true);
+        });
+        with_color_env(&[(TERM, "dumb"), (RUST_LOG_STYLE, "always")], || {
+            assert!(resolve_ansi(None, Stream::Stdout));
+            assert_printed(None, true, true);
+        });
+        with_color_env(&[], || {
+            env::remove_var(TERM);
+            assert_eq!(term_shows_color(), !cfg!(unix));
+        });
+    }
+
+    #[test]
+    fn test_color_terminal_allows_ansi() {
+        with_color_env(&[(TERM, "xterm-256color")], || {
+            assert!(term_shows_color());
+            // Whether color is shown is then up to whether the stream is a terminal.
+            assert_eq!(
+                resolve_ansi(None, Stream::Stdout),
+                Stream::Stdout.is_terminal()
+            );
+            assert_printed(
+                None,
+                Stream::Stdout.is_terminal(),
+                Stream::Stderr.is_terminal(),
+            );
+        });
+    }
+
+    #[test]
+    fn test_detect_capabilities() {
+        let vars = [(NO_COLOR, "1"), (CLICOLOR, "0"), (COLORTERM, "truecolor")];
+        with_color_env(&vars, || {
+            let capabilities = detect_capabilities();
+            assert_eq!(capabilities.stdout_is_tty, Stream::Stdout.is_terminal());
+            assert_eq!(capabilities.stderr_is_tty, Stream::Stderr.is_terminal());
+            assert!(!capabilities.color_enabled);
+            assert!(capabilities.truecolor);
+            assert_eq!(capabilities.term.as_deref(), Some("xterm-256color"));
+            assert!(capabilities.no_color_set);
+            assert_eq!(capabilities.clicolor, Some(0));
+        });
+        with_color_env(&[(TERM, "dumb"), (CLICOLOR_FORCE, "1")], || {
+            let capabilities = detect_capabilities();
+            // As the subscriber decides, forcing color takes precedence over the terminal.
+            assert!(capabilities.color_enabled);
+            assert!(!capabilities.truecolor);
+            assert_eq!(capabilities.term.as_deref(), Some("dumb"));
+            assert!(!capabilities.no_color_set);
+            assert_eq!(capabilities.clicolor, None);
+        });
+    }
+
+    #[test]
+    fn test_rust_log_style() {
+        with_color_env(&[(RUST_LOG_STYLE, "always"), (NO_COLOR, "1")], || {
+            assert!(resolve_ansi(None, Stream::Stdout));
+            assert!(!resolve_ansi(Some(false), Stream::Stdout));
+            assert_printed(None, true, true);
+            assert_printed(Some(false), false, false);
+        });
+        with_color_env(&[(RUST_LOG_STYLE, "never"), (CLICOLOR_FORCE, "1")], || {
+            // `CLICOLOR_FORCE` still takes precedence.
+            assert!(resolve_ansi(None, Stream::Stdout));
+            assert_printed(None, true, true);
+        });
+        with_color_env(&[(RUST_LOG_STYLE, "never")], || {
+            assert!(!resolve_ansi(None, Stream::Stderr));
+            assert!(resolve_ansi(Some(true), Stream::Stderr));
+            assert_printed(None, false, false);
+            assert_printed(Some(true), true, true);
+        });
+        with_color_env(&[(RUST_LOG_STYLE, "auto"), (NO_COLOR, "1")], || {
+            assert!(!resolve_ansi(None, Stream::Stdout));
+            assert_printed(None, false, false);
+        });
+        with_color_env(&[(RUST_LOG_STYLE, "auto")], || {
+            assert_eq!(
+                resolve_ansi(None, Stream::Stdout),
+                Stream::Stdout.is_terminal()
+            );
+            assert_printed(
+                None,
+                Stream::Stdout.is_terminal(),
+                Stream::Stderr.is_terminal(),
+            );
+        });
+    }
+}
@@ -0,0 +1,274 @@
+//! Default options read from the environment, so that a team can share log settings without
+//! passing the same flags to every command.
+
+use crate::{LogFormat, TimeFormat, TracingSubscriberOptions, TracingWriterMode};
+use std::env;
+
+/// The format events are written in: `pretty`, `compact`, `json`, `json-array` or `logfmt`.
+const LOG_FORMAT: &str = "FORC_LOG_FORMAT";
+/// A file that everything is appended to, besides being written to the terminal.
+const LOG_FILE: &str = "FORC_LOG_FILE";
+/// Whether to prefix events with a timestamp, or the format of the timestamp.
+const LOG_TIME: &str = "FORC_LOG_TIME";
+/// Whether to emit ANSI color codes: `always`, `never` or `auto`.
+const LOG_COLOR: &str = "FORC_LOG_COLOR";
+/// A file that every event is appended to, whatever the level shown on the terminal.
+const TRANSCRIPT: &str = "FORC_TRANSCRIPT";
+
+impl TracingSubscriberOptions {
+    /// The options set by the `FORC_LOG_*` environment variables, with all others unset:
+    ///
+    /// - `FORC_LOG_FORMAT`: the `format`, one of `pretty`, `compact`, `json`, `json-array` or
+    ///   `logfmt`.
+    /// - `FORC_LOG_FILE`: a file to append everything to besides the terminal, i.e.
+    ///   `TracingWriterMode::Tee`.
+    /// - `FORC_LOG_TIME`: `1`/`true` or `0`/`false` for `display_time`, or one of `rfc3339`,
+    ///   `local`, `uptime` and `delta`, or a strftime-style pattern, for the `time_format`.
+    /// - `FORC_LOG_COLOR`: `always` or `never` for `ansi`, or `auto` to detect it.
+    /// - `FORC_TRANSCRIPT`: the `transcript_file`.
+    ///
+    /// Invalid values are ignored with a warning on stderr. Use `merge` to let the options given on
+    /// the command line take precedence.
+    pub fn from_env() -> Self {
+        let mut options = Self::default();
+        if let Some(format) = var(LOG_FORMAT) {
+            options.format = match format.to_ascii_lowercase().as_str() {
+                "pretty" => Some(LogFormat::Pretty),
+                "compact" => Some(LogFormat::Compact),
+                "json" => Some(LogFormat::Json),
+                "json-array" => Some(LogFormat::JsonArray),
+                "logfmt" => Some(LogFormat::Logfmt),
+                _ => invalid(
+                    LOG_FORMAT,
+                    &format,
+                    "`pretty`, `compact`, `json`, `json-array` or `logfmt`",
+                ),
+            };
+        }
+        if let Some(file) = env::var_os(LOG_FILE).filter(|file| !file.is_empty()) {
+            options.writer_mode = Some(TracingWriterMode::Tee { file: file.into() });
+        }
+        if let Some(time) = var(LOG_TIME) {
+            match time.to_ascii_lowercase().as_str() {
+                "1" | "true" => options.display_time = Some(true),
+                "0" | "false" => options.display_time = Some(false),
+                "rfc3339" => options.time_format = Some(TimeFormat::Rfc3339),
+                "local" => options.time_format = Some(TimeFormat::LocalRfc3339),
+                "uptime" => options.time_format = Some(TimeFormat::Uptime),
+                "delta" => options.time_format = Some(TimeFormat::Delta),
+                _ => options.time_format = Some(TimeFormat::Custom(time)),
+            }
+        }
+        if let Some(color) = var(LOG_COLOR) {
+            options.ansi = match color.to_ascii_lowercase().as_str() {
+                "always" => Some(true),
+                "never" => Some(false),
+                "auto" => None,
+                _ => invalid(LOG_COLOR, &color, "`always`, `never` or `auto`"),
+            };
+        }
+        if let Some(file) = env::var_os(TRANSCRIPT).filter(|file| !file.is_empty()) {
+            options.transcript_file = Some(file.into());
+        }
+        options
+    }
+
+    /// Fill in the options that aren't set from `defaults`, e.g. the options given on the command
+    /// line from those of `from_env`. The targets of both are kept, with those of `self` last so
+    /// that its `target_levels` win.
+    pub fn merge(self, defaults: Self) -> Self {
+        let TracingSubscriberOptions {
+            verbosity,
+            silent,
+            quiet,
+            log_level,
+            writer_mode,
+            split_files,
+            transcript_file,
+            rotation,
+            max_log_files,
+            compress_rotated,
+            file_line_ending,
+            ansi,
+            plain,
+            format,
+            message_format,
+            display_level,
+            level_style,
+            display_target,
+            display_file,
+            display_line_number,
+            display_thread_ids,
+            display_thread_names,
+            display_pid,
+            display_span_context,
+            display_time,
+            time_format,
+            non_blocking,
+            flush_policy,
+            dedupe_window,
+            defer_warnings,
+            capture_panics,
+            error_backtraces,
+            ring_buffer_capacity,
+            report_timings,
+            otlp_endpoint,
+            syslog,
+            wrap_width,
+            indent_continuation,
+            collapse_blank_lines,
+            max_message_len,
+            never_truncate_errors,
+            extra_targets,
+            target_levels,
+            suppress_targets,
+            level_symbols,
+            no_unicode,
+        } = self;
+        let mut merged_extra_targets = defaults.extra_targets;
+        merged_extra_targets.extend(extra_targets);
+        let mut merged_target_levels = defaults.target_levels;
+        merged_target_levels.extend(target_levels);
+        let mut merged_suppress_targets = defaults.suppress_targets;
+        merged_suppress_targets.extend(suppress_targets);
+        TracingSubscriberOptions {
+            verbosity: verbosity.or(defaults.verbosity),
+            silent: silent.or(defaults.silent),
+            quiet: quiet.or(defaults.quiet),
+            log_level: log_level.or(defaults.log_level),
+            writer_mode: writer_mode.or(defaults.writer_mode),
+            split_files: split_files.or(defaults.split_files),
+            transcript_file: transcript_file.or(defaults.transcript_file),
+            rotation: rotation.or(defaults.rotation),
+            max_log_files: max_log_files.or(defaults.max_log_files),
+            compress_rotated: compress_rotated.or(defaults.compress_rotated),
+            file_line_ending: file_line_ending.or(defaults.file_line_ending),
+            ansi: ansi.or(defaults.ansi),
+            plain: plain.or(defaults.plain),
+            format: format.or(defaults.format),
+            message_format: message_format.or(defaults.message_format),
+            display_level: display_level.or(defaults.display_level),
+            level_style: level_style.or(defaults.level_style),
+            display_target: display_target.or(defaults.display_target),
+            display_file: display_file.or(defaults.display_file),
+            display_line_number: display_line_number.or(defaults.display_line_number),
+            display_thread_ids: display_thread_ids.or(defaults.display_thread_ids),
+            display_thread_names: display_thread_names.or(defaults.display_thread_names),
+            display_pid: display_pid.or(defaults.display_pid),
+            display_span_context: display_span_context.or(defaults.display_span_context),
+            display_time: display_time.or(defaults.display_time),
+            time_format: time_format.or(defaults.time_format),
+            non_blocking: non_blocking.or(defaults.non_blocking),
+            flush_policy: flush_policy.or(defaults.flush_policy),
+            dedupe_window: dedupe_window.or(defaults.dedupe_window),
+            defer_warnings: defer_warnings.or(defaults.defer_warnings),
+            capture_panics: capture_panics.or(defaults.capture_panics),
+            error_backtraces: error_backtraces.or(defaults.error_backtraces),
+            ring_buffer_capacity: ring_buffer_capacity.or(defaults.ring_buffer_capacity),
+            report_timings: report_timings.or(defaults.report_timings),
+            otlp_endpoint: otlp_endpoint.or(defaults.otlp_endpoint),
+            syslog: syslog.or(defaults.syslog),
+            wrap_width: wrap_width.or(defaults.wrap_width),
+            indent_continuation: indent_continuation.or(defaults.indent_continuation),
+            collapse_blank_lines: collapse_blank_lines.or(defaults.collapse_blank_lines),
+            max_message_len: max_message_len.or(defaults.max_message_len),
+            never_truncate_errors: never_truncate_errors.or(defaults.never_truncate_errors),
+            extra_targets: merged_extra_targets,
+            target_levels: merged_target_levels,
+            suppress_targets: merged_suppress_targets,
+            level_symbols: level_symbols.or(defaults.level_symbols),
+            no_unicode: no_unicode.or(defaults.no_unicode),
+        }
+    }
+}
+
+/// The value of the environment variable `name`, unless it is unset or empty.
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn invalid<T>(name: &str, value: &str, expected: &str) -> Option<T> {
+    eprintln!("warning: ignoring invalid `{name}` value `{value}`, expected {expected}");
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ENV_LOCK;
+    use tracing_subscriber::filter::LevelFilter;
+
+    /// The options read with the `FORC_LOG_*` variables set to `vars`, and all others unset.
+    fn from_vars(vars: &[(&str, &str)]) -> TracingSubscriberOptions {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        for var in [LOG_FORMAT, LOG_FILE, LOG_TIME, LOG_COLOR, TRANSCRIPT] {
+            env::remove_var(var);
+        }
+        for (var, value) in vars {
+            env::set_var(var, value);
+        }
+        let options = TracingSubscriberOptions::from_env();
+        for (var, _) in vars {
+            env::remove_var(var);
+        }
+        options
+    }
+
+    #[test]
+    fn test_from_env() {
+        assert_eq!(from_vars(&[]), TracingSubscriberOptions::default());
+        assert_eq!(
+            from_vars(&[(LOG_FORMAT, "JSON")]).format,
+            Some(LogFormat::Json)
+        );
+        assert_eq!(from_vars(&[(LOG_FORMAT, "yaml")]).format, None);
+        assert_eq!(
+            from_vars(&[(LOG_FILE, "out/forc.log")]).writer_mode,
+            Some(TracingWriterMode::Tee {
+                file: "out/forc.log".into()
+            })
+        );
+        assert_eq!(from_vars(&[(LOG_TIME, "1")]).display_time, Some(true));
+        assert_eq!(from_vars(&[(LOG_TIME, "false")]).display_time, Some(false));
+        assert_eq!(
+            from_vars(&[(LOG_TIME, "uptime")]).time_format,
+            Some(TimeFormat::Uptime)
+        );
+        assert_eq!(
+            from_vars(&[(LOG_TIME, "%H:%M")]).time_format,
+            Some(TimeFormat::Custom("%H:%M".to_string()))
+        );
+        assert_eq!(from_vars(&[(LOG_COLOR, "always")]).ansi, Some(true));
+        assert_eq!(from_vars(&[(LOG_COLOR, "never")]).ansi, Some(false));
+        assert_eq!(from_vars(&[(LOG_COLOR, "auto")]).ansi, None);
+        assert_eq!(
+            from_vars(&[(TRANSCRIPT, "session.log")]).transcript_file,
+            Some("session.log".into())
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let env = from_vars(&[(LOG_FORMAT, "json"), (LOG_COLOR, "never")]);
+        let cli = TracingSubscriberOptions::builder()
+            .format(LogFormat::Compact)
+            .verbosity(1)
+            .target_level("forc_pkg", LevelFilter::WARN)
+            .build();
+        let defaults = TracingSubscriberOptions {
+            target_levels: vec![("forc_pkg".to_string(), LevelFilter::TRACE)],
+            ..env
+        };
+        let merged = cli.merge(defaults);
+        assert_eq!(merged.format, Some(LogFormat::Compact));
+        assert_eq!(merged.ansi, Some(false));
+        assert_eq!(merged.verbosity, Some(1));
+        assert_eq!(
+            merged.target_levels,
+            [
+                ("forc_pkg".to_string(), LevelFilter::TRACE),
+                ("forc_pkg".to_string(), LevelFilter::WARN)
+            ]
+        );
+    }
+}
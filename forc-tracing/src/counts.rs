@@ -0,0 +1,181 @@
+//! Counting of the warnings and errors logged, for a cargo-style summary at the end of a command.
+
+use crate::color;
+use anstyle::AnsiColor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// The number of WARN and ERROR events logged since the subscriber was initialized, or since the
+/// last `reset_log_counts`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogCounts {
+    pub warnings: usize,
+    pub errors: usize,
+}
+
+/// The target of the summary events logged by this crate, by `print_summary` and for the
+/// repetitions dropped by `dedupe_window`, which aren't counted themselves.
+pub(crate) const SUMMARY_TARGET: &str = "forc_tracing::summary";
+
+pub(crate) struct Counters {
+    warnings: AtomicUsize,
+    errors: AtomicUsize,
+}
+
+impl Counters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            warnings: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+        }
+    }
+
+    fn get(&self) -> LogCounts {
+        LogCounts {
+            warnings: self.warnings.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        self.warnings.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+    }
+}
+
+/// The counters of the global subscriber.
+pub(crate) static COUNTERS: Counters = Counters::new();
+
+/// Counts the WARN and ERROR events that pass the level filter of the layer it is combined with,
+/// including the repetitions dropped by `dedupe_window`.
+pub(crate) struct CountingLayer {
+    counters: &'static Counters,
+}
+
+impl CountingLayer {
+    pub(crate) fn new(counters: &'static Counters) -> Self {
+        Self { counters }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for CountingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target() != SUMMARY_TARGET {
+            match *metadata.level() {
+                Level::ERROR => self.counters.errors.fetch_add(1, Ordering::Relaxed),
+                Level::WARN => self.counters.warnings.fetch_add(1, Ordering::Relaxed),
+                _ => 0,
+            };
+        }
+    }
+}
+
+/// The number of warnings and errors logged so far.
+pub fn log_counts() -> LogCounts {
+    COUNTERS.get()
+}
+
+/// Start counting warnings and errors from zero again, e.g. between the requests of a server.
+pub fn reset_log_counts() {
+    COUNTERS.reset()
+}
+
+/// Print how many warnings and errors were logged, if any, e.g.
+/// `warning: 3 warnings emitted` and `error: could not compile due to 2 previous errors`.
+pub fn print_summary() {
+    for line in summary(log_counts()) {
+        match line.level {
+            Level::ERROR => tracing::error!(target: SUMMARY_TARGET, "{}", line.text),
+            _ => tracing::warn!(target: SUMMARY_TARGET, "{}", line.text),
+        }
+    }
+}
+
+struct SummaryLine {
+    level: Level,
+    text: String,
+}
+
+fn summary(counts: LogCounts) -> Vec<SummaryLine> {
+    let line = |level, color: AnsiColor, label, message| {
+        let label = match color::level_color(&level) {
+            true => color::paint(label, color.on_default().bold()),
+            false => label.to_string(),
+        };
+        SummaryLine {
+            level,
+            text: format!("{label}: {message}"),
+        }
+    };
+    let mut lines = Vec::new();
+    if counts.warnings > 0 {
+        let message = format!("{} emitted", plural(counts.warnings, "warning"));
+        lines.push(line(Level::WARN, AnsiColor::Yellow, "warning", message));
+    }
+    if counts.errors > 0 {
+        let message = format!(
+            "could not compile due to {}",
+            plural(counts.errors, "previous error")
+        );
+        lines.push(line(Level::ERROR, AnsiColor::Red, "error", message));
+    }
+    lines
+}
+
+pub(crate) fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("{count} {noun}"),
+        _ => format!("{count} {noun}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    #[test]
+    fn test_log_counts() {
+        static TEST_COUNTERS: Counters = Counters::new();
+        let subscriber = Registry::default().with(CountingLayer::new(&TEST_COUNTERS));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!("error");
+            tracing::warn!("warning");
+            tracing::warn!("warning");
+            tracing::info!("info");
+            tracing::debug!("debug");
+            tracing::error!(target: SUMMARY_TARGET, "summary");
+        });
+        let counts = TEST_COUNTERS.get();
+        assert_eq!(
+            counts,
+            LogCounts {
+                warnings: 2,
+                errors: 1
+            }
+        );
+        TEST_COUNTERS.reset();
+        assert_eq!(TEST_COUNTERS.get(), LogCounts::default());
+    }
+
+    #[test]
+    fn test_summary() {
+        let texts = |warnings, errors| {
+            summary(LogCounts { warnings, errors })
+                .into_iter()
+                .map(|line| line.text)
+                .collect::<Vec<_>>()
+        };
+        assert!(texts(0, 0).is_empty());
+        assert_eq!(texts(1, 0), ["warning: 1 warning emitted"]);
+        assert_eq!(
+            texts(3, 2),
+            [
+                "warning: 3 warnings emitted",
+                "error: could not compile due to 2 previous errors"
+            ]
+        );
+    }
+}
@@ -0,0 +1,398 @@
+//! Suppression of repeated identical events, see `TracingSubscriberOptions::dedupe_window`.
+
+use crate::writer::lock;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::callsite::{Callsite, Identifier};
+use tracing::dispatcher::WeakDispatch;
+use tracing::field::{Field, FieldSet, Visit};
+use tracing::metadata::{Kind, LevelFilter};
+use tracing::span;
+use tracing::subscriber::Interest;
+use tracing::{Dispatch, Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Wraps the layer that `inner` events are passed on to, dropping the events that repeat the level
+/// and message of an event seen less than `window` ago.
+///
+/// Once the window of a message closes, an event noting how many times it was repeated is passed on
+/// in place of the repetitions. ERROR events are never dropped.
+///
+/// This wraps the formatting layer rather than filtering the events of the whole subscriber, so
+/// that it only sees the events that pass the level filter of that layer, and can pass it the
+/// repetition events, which can't be logged with the `tracing` macros from within the subscriber.
+pub(crate) struct DedupeLayer<L> {
+    inner: L,
+    window: Duration,
+    state: Arc<Mutex<HashMap<Key, Seen>>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    level: Level,
+    message: String,
+}
+
+struct Seen {
+    first: Instant,
+    repeated: usize,
+}
+
+/// Wrap `layer` in a `DedupeLayer` if a `window` is given.
+pub(crate) fn dedupe<S>(
+    layer: Box<dyn Layer<S> + Send + Sync>,
+    window: Option<Duration>,
+) -> (Box<dyn Layer<S> + Send + Sync>, Option<DedupeGuard>)
+where
+    S: Subscriber,
+{
+    match window {
+        Some(window) => {
+            let (layer, guard) = DedupeLayer::new(layer, window);
+            (Box::new(layer), Some(guard))
+        }
+        None => (layer, None),
+    }
+}
+
+/// Logs the repetitions of the messages whose window hasn't closed yet when dropped, so that they
+/// aren't lost at exit.
+pub(crate) struct DedupeGuard {
+    state: Arc<Mutex<HashMap<Key, Seen>>>,
+}
+
+// The guards given up on by `DedupeGuard::forget`, with the dispatcher to log their repetitions to,
+// for `flush` to log them instead.
+static FORGOTTEN: Mutex<Vec<(DedupeGuard, WeakDispatch)>> = Mutex::new(Vec::new());
+
+impl DedupeGuard {
+    /// Leave the repetitions to `log_forgotten`, as the guard won't be dropped, e.g. as the
+    /// subscriber was installed by `init_tracing_subscriber`. They are logged to the current
+    /// dispatcher.
+    pub(crate) fn forget(self) {
+        let dispatch = tracing::dispatcher::get_default(Dispatch::downgrade);
+        lock(&FORGOTTEN).push((self, dispatch));
+    }
+}
+
+/// Log the pending repetitions of the guards given up on by `DedupeGuard::forget`.
+pub(crate) fn log_forgotten() {
+    // Collected first, so that the lock isn't held while the events are logged.
+    let forgotten: Vec<_> = lock(&FORGOTTEN)
+        .iter()
+        .filter_map(|(guard, dispatch)| Some((guard.state.clone(), dispatch.upgrade()?)))
+        .collect();
+    for (state, dispatch) in forgotten {
+        log_pending(&state, |event| dispatch.event(event));
+    }
+}
+
+/// Log the repetitions of the messages whose window hasn't closed yet with `dispatch`.
+fn log_pending(state: &Mutex<HashMap<Key, Seen>>, dispatch: impl Fn(&Event<'_>)) {
+    let state = std::mem::take(&mut *lock(state));
+    for (key, seen) in state {
+        if seen.repeated > 0 {
+            log_repeated(&key, seen.repeated, &dispatch);
+        }
+    }
+}
+
+impl<L> DedupeLayer<L> {
+    pub(crate) fn new(inner: L, window: Duration) -> (Self, DedupeGuard) {
+        for callsite in [&WARN, &INFO, &DEBUG, &TRACE] {
+            tracing::callsite::register(callsite);
+        }
+        let state = Arc::<Mutex<_>>::default();
+        let guard = DedupeGuard {
+            state: state.clone(),
+        };
+        let layer = Self {
+            inner,
+            window,
+            state,
+        };
+        (layer, guard)
+    }
+
+    /// Take the keys whose window has closed and that were repeated meanwhile.
+    fn take_closed(&self, now: Instant) -> Vec<(Key, usize)> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut closed = Vec::new();
+        state.retain(|key, seen| {
+            let open = now.duration_since(seen.first) < self.window;
+            if !open && seen.repeated > 0 {
+                closed.push((key.clone(), seen.repeated));
+            }
+            open
+        });
+        closed
+    }
+
+    /// Whether `event` is the first with its level and message in the current window.
+    fn is_first(&self, event: &Event<'_>, now: Instant) -> bool {
+        let level = *event.metadata().level();
+        if level == Level::ERROR {
+            return true;
+        }
+        let key = Key {
+            level,
+            message: render(event),
+        };
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.get_mut(&key) {
+            Some(seen) => {
+                seen.repeated += 1;
+                false
+            }
+            None => {
+                state.insert(
+                    key,
+                    Seen {
+                        first: now,
+                        repeated: 0,
+                    },
+                );
+                true
+            }
+        }
+    }
+}
+
+impl<S: Subscriber, L: Layer<S>> Layer<S> for DedupeLayer<L> {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let now = Instant::now();
+        for (key, repeated) in self.take_closed(now) {
+            log_repeated(&key, repeated, |event| {
+                self.inner.on_event(event, ctx.clone())
+            });
+        }
+        if self.is_first(event, now) {
+            self.inner.on_event(event, ctx);
+        }
+    }
+
+    // Everything else is passed on to the wrapped layer as is.
+
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.inner.on_register_dispatch(subscriber)
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber)
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.inner.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.enabled(metadata, ctx)
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, ctx)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(span, values, ctx)
+    }
+
+    fn on_follows_from(&self, span: &span::Id, follows: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, ctx)
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.event_enabled(event, ctx)
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx)
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx)
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx)
+    }
+
+    fn on_id_change(&self, old: &span::Id, new: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_id_change(old, new, ctx)
+    }
+
+    unsafe fn downcast_raw(&self, id: std::any::TypeId) -> Option<*const ()> {
+        if id == std::any::TypeId::of::<Self>() {
+            Some(self as *const _ as *const ())
+        } else {
+            self.inner.downcast_raw(id)
+        }
+    }
+}
+
+impl Drop for DedupeGuard {
+    fn drop(&mut self) {
+        log_pending(&self.state, |event| {
+            tracing::dispatcher::get_default(|dispatch| dispatch.event(event))
+        });
+    }
+}
+
+/// The message of `event` followed by its other fields, as the text formats show them.
+fn render(event: &Event<'_>) -> String {
+    struct Render(String);
+
+    impl Visit for Render {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if !self.0.is_empty() {
+                self.0.push(' ');
+            }
+            let _ = match field.name() {
+                "message" => write!(self.0, "{value:?}"),
+                name => write!(self.0, "{name}={value:?}"),
+            };
+        }
+    }
+
+    let mut render = Render(String::new());
+    event.record(&mut render);
+    render.0
+}
+
+/// Build the event noting that the message of `key` was repeated, and pass it to `dispatch`.
+fn log_repeated(key: &Key, repeated: usize, dispatch: impl FnOnce(&Event<'_>)) {
+    let metadata = match key.level {
+        Level::WARN => WARN.metadata(),
+        Level::INFO => INFO.metadata(),
+        Level::DEBUG => DEBUG.metadata(),
+        _ => TRACE.metadata(),
+    };
+    let fields = metadata.fields();
+    let message = fields
+        .field("message")
+        .expect("the message field is declared");
+    let text = format_args!(
+        "{} (repeated {})",
+        key.message,
+        crate::counts::plural(repeated, "time")
+    );
+    dispatch(&Event::new(
+        metadata,
+        &fields.value_set(&[(&message, Some(&text as &dyn tracing::Value))]),
+    ));
+}
+
+// The callsites of the repetition events, one per level.
+struct RepeatedCallsite(&'static Metadata<'static>);
+
+impl Callsite for RepeatedCallsite {
+    fn set_interest(&self, _: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        self.0
+    }
+}
+
+macro_rules! repeated_callsite {
+    ($callsite:ident, $metadata:ident, $level:expr) => {
+        static $callsite: RepeatedCallsite = RepeatedCallsite(&$metadata);
+        static $metadata: Metadata<'static> = Metadata::new(
+            "repeated",
+            crate::counts::SUMMARY_TARGET,
+            $level,
+            Some(file!()),
+            Some(line!()),
+            Some(module_path!()),
+            FieldSet::new(&["message"], Identifier(&$callsite)),
+            Kind::EVENT,
+        );
+    };
+}
+
+repeated_callsite!(WARN, WARN_METADATA, Level::WARN);
+repeated_callsite!(INFO, INFO_METADATA, Level::INFO);
+repeated_callsite!(DEBUG, DEBUG_METADATA, Level::DEBUG);
+repeated_callsite!(TRACE, TRACE_METADATA, Level::TRACE);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fmt_layer;
+    use crate::testing::CapturingWriter;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    /// Log the events of `f` with duplicates within `window` dropped, returning the output.
+    fn capture_deduped(window: Duration, f: impl FnOnce()) -> String {
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&Default::default(), false, None, capture.clone()).unwrap();
+        let (dedupe, guard) = DedupeLayer::new(layer, window);
+        let subscriber = Registry::default().with(dedupe);
+        tracing::subscriber::with_default(subscriber, || {
+            f();
+            drop(guard);
+        });
+        capture.contents()
+    }
+
+    #[test]
+    fn test_dedupe_repeated_warnings() {
+        let output = capture_deduped(Duration::from_secs(3600), || {
+            for _ in 0..3 {
+                tracing::warn!("Unused import");
+            }
+            tracing::info!("Unused import");
+        });
+        assert_eq!(
+            output,
+            "Unused import\nUnused import\nUnused import (repeated 2 times)\n"
+        );
+    }
+
+    #[test]
+    fn test_dedupe_never_drops_errors() {
+        let output = capture_deduped(Duration::from_secs(3600), || {
+            tracing::error!("Failed");
+            tracing::error!("Failed");
+        });
+        assert_eq!(output, "Failed\nFailed\n");
+    }
+
+    #[test]
+    fn test_dedupe_window_closes() {
+        let output = capture_deduped(Duration::from_millis(10), || {
+            tracing::warn!(count = 1, "Unused");
+            tracing::warn!(count = 1, "Unused");
+            std::thread::sleep(Duration::from_millis(20));
+            tracing::warn!(count = 1, "Unused");
+        });
+        assert_eq!(
+            output,
+            "Unused count=1\nUnused count=1 (repeated 1 time)\nUnused count=1\n"
+        );
+    }
+
+    #[test]
+    fn test_flush_logs_repetitions_of_forgotten_guard() {
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&Default::default(), false, None, capture.clone()).unwrap();
+        let (dedupe, guard) = DedupeLayer::new(layer, Duration::from_secs(3600));
+        // Kept alive past the scope, like a global default.
+        let dispatch = Dispatch::new(Registry::default().with(dedupe));
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::warn!("Unused import");
+            tracing::warn!("Unused import");
+            guard.forget();
+        });
+        crate::flush();
+        assert_eq!(
+            capture.contents(),
+            "Unused import\nUnused import (repeated 1 time)\n"
+        );
+    }
+}
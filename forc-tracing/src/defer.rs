@@ -0,0 +1,169 @@
+//! Warnings held back until the end of a command, see `TracingSubscriberOptions::defer_warnings`.
+
+use crate::counts::{plural, SUMMARY_TARGET};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// The warnings held back, by group and then by message, with the number of times each was
+/// logged.
+pub(crate) type Deferred = Mutex<BTreeMap<String, BTreeMap<String, usize>>>;
+
+/// The warnings held back by the global subscriber.
+pub(crate) static DEFERRED: Deferred = Mutex::new(BTreeMap::new());
+
+fn lock(deferred: &Deferred) -> MutexGuard<'_, BTreeMap<String, BTreeMap<String, usize>>> {
+    deferred.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether the output holds `metadata` back, i.e. whether it is a WARN event that isn't one of
+/// this crate's summaries.
+pub(crate) fn is_deferred(metadata: &Metadata<'_>) -> bool {
+    *metadata.level() == Level::WARN && metadata.target() != SUMMARY_TARGET
+}
+
+/// Holds back the WARN events that pass the level filter of the layer it is combined with, which
+/// the output skips meanwhile.
+pub(crate) struct DeferLayer {
+    deferred: &'static Deferred,
+}
+
+impl DeferLayer {
+    pub(crate) fn new(deferred: &'static Deferred) -> Self {
+        Self { deferred }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for DeferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if !is_deferred(event.metadata()) {
+            return;
+        }
+        let mut warning = Warning::default();
+        event.record(&mut warning);
+        let group = warning
+            .code
+            .unwrap_or_else(|| event.metadata().target().to_string());
+        *lock(self.deferred)
+            .entry(group)
+            .or_default()
+            .entry(warning.text)
+            .or_default() += 1;
+    }
+}
+
+/// The message of a warning followed by its other fields, as the text formats show them, and its
+/// `code` field if it has one, which it is grouped by.
+#[derive(Default)]
+struct Warning {
+    code: Option<String>,
+    text: String,
+}
+
+impl Visit for Warning {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "code" => self.code = Some(value.to_string()),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "code" {
+            self.code = Some(format!("{value:?}"));
+            return;
+        }
+        if !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        let _ = match field.name() {
+            "message" => write!(self.text, "{value:?}"),
+            name => write!(self.text, "{name}={value:?}"),
+        };
+    }
+}
+
+/// Log the warnings held back by `defer_warnings` so far, if any, and forget them.
+///
+/// They are grouped by their `code` field, or by their target if they have none, each group led by
+/// the number of warnings in it, e.g. `3 warnings from forc_pkg:`. The groups and the messages in
+/// each are sorted, and messages logged more than once are shown once with the number of times,
+/// e.g. `unused import (2 times)`.
+///
+/// They are logged as WARN events again, which aren't counted a second time by `log_counts`.
+pub fn flush_deferred_warnings() {
+    for line in take_report(&DEFERRED) {
+        tracing::warn!(target: SUMMARY_TARGET, "{line}");
+    }
+}
+
+/// The lines of the warnings held back in `deferred`, which are forgotten.
+fn take_report(deferred: &Deferred) -> Vec<String> {
+    let groups = std::mem::take(&mut *lock(deferred));
+    let mut lines = Vec::new();
+    for (group, warnings) in groups {
+        let count = warnings.values().sum();
+        lines.push(format!("{} from {group}:", plural(count, "warning")));
+        for (text, count) in warnings {
+            match count {
+                1 => lines.push(format!("  {text}")),
+                _ => lines.push(format!("  {text} ({count} times)")),
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::{
+        build_subscriber, log_counts, CustomWriter, TracingSubscriberOptions, TracingWriterMode,
+        ENV_LOCK,
+    };
+
+    #[test]
+    fn test_defer_warnings() {
+        // The warnings held back are global too.
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var("RUST_LOG");
+        let terminal = CapturingWriter::new();
+        let custom = {
+            let terminal = terminal.clone();
+            CustomWriter::new(move || Box::new(terminal.clone()))
+        };
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Custom(custom))
+            .defer_warnings(true)
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        let before = log_counts();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "forc_pkg", "unused dependency `std`");
+            tracing::info!(target: "forc", "Compiling");
+            tracing::warn!(target: "sway_core", code = "W0001", "unused import");
+            tracing::warn!(target: "forc_pkg", "lock file is out of date");
+            tracing::warn!(target: "sway_core", code = "W0001", "unused import");
+            tracing::error!(target: "forc", "Failed");
+            tracing::warn!(target: "sway_core", code = "W0002", "dead code");
+            assert_eq!(terminal.contents(), "Compiling\nFailed\n");
+
+            flush_deferred_warnings();
+            // There is nothing left to flush.
+            flush_deferred_warnings();
+        });
+        let after = log_counts();
+        assert!(after.warnings >= before.warnings + 5);
+        assert_eq!(
+            terminal.contents(),
+            "Compiling\nFailed\n\
+             2 warnings from W0001:\n  unused import (2 times)\n\
+             1 warning from W0002:\n  dead code\n\
+             2 warnings from forc_pkg:\n  lock file is out of date\n  unused dependency `std`\n"
+        );
+    }
+}
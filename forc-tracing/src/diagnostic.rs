@@ -0,0 +1,337 @@
+//! Compiler diagnostics with a stable JSON schema, for IDEs and CI dashboards reading the JSON format.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use crate::{color, theme, Color, MessageFormat, Style};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::PathBuf;
+use tracing::Metadata;
+use tracing_subscriber::fmt::writer::EitherWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// The target of the events logged by `emit_diagnostic`.
+pub const DIAGNOSTICS_TARGET: &str = "forc::diagnostics";
+
+/// The version of the schema of the `diagnostic` objects of the JSON format, the
+/// `schema_version` key of each object.
+///
+/// It is incremented whenever a key is removed or changes meaning. Keys may be added without
+/// changing it, so consumers should ignore those they don't know.
+pub const DIAGNOSTIC_SCHEMA_VERSION: u32 = 1;
+
+/// How severe a diagnostic is, which decides the level it is logged at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Logged at ERROR.
+    Error,
+    /// Logged at WARN.
+    Warning,
+    /// Logged at INFO.
+    Note,
+}
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+
+    fn color(self) -> Color {
+        let theme = theme::theme();
+        match self {
+            Severity::Error => theme.error,
+            Severity::Warning => theme.warn,
+            Severity::Note => theme.info,
+        }
+    }
+}
+
+/// A position in a source file, with the line and column counted from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// The range of a source file that a diagnostic or label refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct SourceSpan {
+    pub start: LineCol,
+    pub end: LineCol,
+}
+
+/// A message attached to another part of the source than the diagnostic itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Label {
+    pub span: SourceSpan,
+    pub message: String,
+}
+
+/// A diagnostic about the source being compiled, see `emit_diagnostic`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// The code identifying the kind of diagnostic, e.g. `E0308`.
+    pub code: Option<String>,
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub span: Option<SourceSpan>,
+    pub labels: Vec<Label>,
+}
+
+impl Diagnostic {
+    /// The diagnostic with the schema version, as it's written by the JSON format, with the
+    /// `rendered` key if the `message_format` asks for one.
+    fn to_json(&self, message_format: Option<MessageFormat>) -> String {
+        #[derive(Serialize)]
+        struct Versioned<'a> {
+            schema_version: u32,
+            #[serde(flatten)]
+            diagnostic: &'a Diagnostic,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            rendered: Option<String>,
+        }
+        let rendered = match message_format {
+            Some(MessageFormat::Json) => Some(self.render(false)),
+            Some(MessageFormat::JsonDiagnosticShort) => Some(self.render_short()),
+            Some(MessageFormat::JsonDiagnosticRenderedAnsi) => Some(self.render(true)),
+            Some(MessageFormat::Human) | None => None,
+        };
+        serde_json::to_string(&Versioned {
+            schema_version: DIAGNOSTIC_SCHEMA_VERSION,
+            diagnostic: self,
+            rendered,
+        })
+        .expect("diagnostics serialize to JSON")
+    }
+
+    /// The severity and code, e.g. `error[E0308]`.
+    fn header(&self) -> String {
+        match &self.code {
+            Some(code) => format!("{}[{code}]", self.severity.as_str()),
+            None => self.severity.as_str().to_string(),
+        }
+    }
+
+    /// The diagnostic as the text formats show it, e.g.
+    ///
+    /// ```text
+    /// error[E0308]: mismatched types
+    ///   --> src/main.sw:3:5
+    ///    = 3:9: expected `u64`
+    /// ```
+    ///
+    /// With `ansi`, the header is painted in the color of the severity and the markers in the
+    /// color of info, like cargo does.
+    fn render(&self, ansi: bool) -> String {
+        let paint = |txt: &str, style: Style| match ansi {
+            true => color::paint(txt, style),
+            false => txt.to_string(),
+        };
+        let marker = theme::theme().info.on_default().bold();
+        let mut text = paint(&self.header(), self.severity.color().on_default().bold());
+        text.push_str(&paint(&format!(": {}", self.message), Style::new().bold()));
+        if let Some(file) = &self.file {
+            let _ = write!(text, "\n  {} {}", paint("-->", marker), file.display());
+            if let Some(span) = &self.span {
+                let _ = write!(text, ":{}:{}", span.start.line, span.start.col);
+            }
+        }
+        for label in &self.labels {
+            let start = label.span.start;
+            let _ = write!(
+                text,
+                "\n   {} {}:{}: {}",
+                paint("=", marker),
+                start.line,
+                start.col,
+                label.message
+            );
+        }
+        text
+    }
+
+    /// The diagnostic on one line, without its labels, e.g.
+    /// `src/main.sw:3:5: error[E0308]: mismatched types`.
+    fn render_short(&self) -> String {
+        let mut text = String::new();
+        if let Some(file) = &self.file {
+            let _ = write!(text, "{}", file.display());
+            if let Some(span) = &self.span {
+                let _ = write!(text, ":{}:{}", span.start.line, span.start.col);
+            }
+            text.push_str(": ");
+        }
+        let _ = write!(text, "{}: {}", self.header(), self.message);
+        text
+    }
+}
+
+thread_local! {
+    // The diagnostic being logged on this thread, for the JSON writers to add to the event, as the
+    // fields of events can't hold objects.
+    static PENDING: RefCell<Option<Diagnostic>> = const { RefCell::new(None) };
+}
+
+/// Log `diagnostic` at the level of its severity, with the `forc::diagnostics` target.
+///
+/// The text formats show it like the compiler does. The JSON format writes it as the object under
+/// the `diagnostic` key of the event, with the `schema_version` key set to
+/// `DIAGNOSTIC_SCHEMA_VERSION`:
+///
+/// ```json
+/// {"diagnostic":{"schema_version":1,"code":"E0308","severity":"error",...},"level":"ERROR",...}
+/// ```
+///
+/// The object also has a `rendered` key with the text of the diagnostic if
+/// `TracingSubscriberOptions::message_format` is one of the JSON formats, like cargo writes.
+pub fn emit_diagnostic(diagnostic: Diagnostic) {
+    let text = diagnostic.render(false);
+    let severity = diagnostic.severity;
+    PENDING.with(|pending| *pending.borrow_mut() = Some(diagnostic));
+    match severity {
+        Severity::Error => tracing::error!(target: DIAGNOSTICS_TARGET, "{text}"),
+        Severity::Warning => tracing::warn!(target: DIAGNOSTICS_TARGET, "{text}"),
+        Severity::Note => tracing::info!(target: DIAGNOSTICS_TARGET, "{text}"),
+    }
+    PENDING.with(|pending| *pending.borrow_mut() = None);
+}
+
+/// Adds the `diagnostic` object to the JSON of the events logged by `emit_diagnostic` that are
+/// written through the wrapped writer, rendered as `message_format` says.
+pub(crate) struct JsonDiagnosticWriter<W> {
+    inner: W,
+    message_format: Option<MessageFormat>,
+}
+
+impl<W> JsonDiagnosticWriter<W> {
+    pub(crate) fn new(inner: W, message_format: Option<MessageFormat>) -> Self {
+        Self {
+            inner,
+            message_format,
+        }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for JsonDiagnosticWriter<W> {
+    type Writer = EitherWriter<BoxedEvent<'a, W::Writer>, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        EitherWriter::B(self.inner.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        let inner = self.inner.make_writer_for(meta);
+        if meta.target() != DIAGNOSTICS_TARGET {
+            return EitherWriter::B(inner);
+        }
+        let json = PENDING.with(|pending| {
+            let pending = pending.borrow();
+            pending
+                .as_ref()
+                .map(|diagnostic| diagnostic.to_json(self.message_format))
+        });
+        let Some(diagnostic) = json else {
+            return EitherWriter::B(inner);
+        };
+        EitherWriter::A(BufferedEvent::boxed(
+            inner,
+            move |buffer, inner| match buffer.strip_prefix(b"{") {
+                Some(fields) => {
+                    write!(inner, "{{\"diagnostic\":{diagnostic},")?;
+                    inner.write_all(fields)
+                }
+                None => inner.write_all(buffer),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::with_captured_logs;
+    use crate::{LogFormat, TracingSubscriberOptions};
+
+    fn mismatched_types() -> Diagnostic {
+        Diagnostic {
+            code: Some("E0308".to_string()),
+            severity: Severity::Error,
+            message: "mismatched types".to_string(),
+            file: Some("src/main.sw".into()),
+            span: Some(SourceSpan {
+                start: LineCol { line: 3, col: 5 },
+                end: LineCol { line: 3, col: 12 },
+            }),
+            labels: vec![Label {
+                span: SourceSpan {
+                    start: LineCol { line: 3, col: 9 },
+                    end: LineCol { line: 3, col: 12 },
+                },
+                message: "expected `u64`".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_diagnostic_json_schema() {
+        let json: serde_json::Value =
+            serde_json::from_str(&mismatched_types().to_json(None)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schema_version": DIAGNOSTIC_SCHEMA_VERSION,
+                "code": "E0308",
+                "severity": "error",
+                "message": "mismatched types",
+                "file": "src/main.sw",
+                "span": {"start": {"line": 3, "col": 5}, "end": {"line": 3, "col": 12}},
+                "labels": [{
+                    "span": {"start": {"line": 3, "col": 9}, "end": {"line": 3, "col": 12}},
+                    "message": "expected `u64`",
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_emit_diagnostic_json() {
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .build();
+        let output = with_captured_logs(options, || {
+            tracing::info!("Compiling");
+            emit_diagnostic(mismatched_types());
+        });
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].get("diagnostic").is_none());
+        assert_eq!(events[1]["target"], DIAGNOSTICS_TARGET);
+        assert_eq!(events[1]["level"], "ERROR");
+        let diagnostic = &events[1]["diagnostic"];
+        assert_eq!(diagnostic["schema_version"], DIAGNOSTIC_SCHEMA_VERSION);
+        assert_eq!(diagnostic["code"], "E0308");
+        assert_eq!(diagnostic["labels"][0]["span"]["start"]["col"], 9);
+    }
+
+    #[test]
+    fn test_emit_diagnostic_text() {
+        let output = with_captured_logs(TracingSubscriberOptions::default(), || {
+            emit_diagnostic(mismatched_types());
+        });
+        assert_eq!(
+            output,
+            "error[E0308]: mismatched types\n  --> src/main.sw:3:5\n   = 3:9: expected `u64`\n"
+        );
+    }
+}
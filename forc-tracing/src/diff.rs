@@ -0,0 +1,87 @@
+//! Colored line diffs, e.g. of the changes `forc fmt` would make.
+
+use crate::{color, paint};
+use anstyle::AnsiColor;
+use similar::{ChangeTag, TextDiff};
+use tracing::Level;
+
+/// The number of unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// Print a unified diff of the lines of `old` and `new` at INFO, one event per line, with removed
+/// lines prefixed with `-` in red and added lines with `+` in green.
+///
+/// The `-` and `+` prefixes are kept when color is omitted, so the diff stays readable.
+///
+/// ```no_run
+/// forc_tracing::print_diff("let x=1;\n", "let x = 1;\n");
+/// ```
+pub fn print_diff(old: &str, new: &str) {
+    for line in diff_lines(old, new, color::level_color(&Level::INFO)) {
+        tracing::info!("{line}");
+    }
+}
+
+fn diff_lines(old: &str, new: &str, ansi: bool) -> Vec<String> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut lines = Vec::new();
+    for hunk in diff
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .iter_hunks()
+    {
+        let header = hunk.header().to_string();
+        lines.push(paint(&header, AnsiColor::Cyan.on_default(), ansi));
+        for change in hunk.iter_changes() {
+            let (prefix, color) = match change.tag() {
+                ChangeTag::Delete => ("-", Some(AnsiColor::Red)),
+                ChangeTag::Insert => ("+", Some(AnsiColor::Green)),
+                ChangeTag::Equal => (" ", None),
+            };
+            let line = format!("{prefix}{}", change.value().trim_end_matches(['\r', '\n']));
+            lines.push(match color {
+                Some(color) => paint(&line, color.on_default(), ansi),
+                None => line,
+            });
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "script;\nfn main() {\n    let x=1;\n}\n";
+    const NEW: &str = "script;\nfn main() {\n    let x = 1;\n}\n";
+
+    #[test]
+    fn test_diff_prefixes() {
+        assert_eq!(
+            diff_lines(OLD, NEW, false),
+            [
+                "@@ -1,4 +1,4 @@",
+                " script;",
+                " fn main() {",
+                "-    let x=1;",
+                "+    let x = 1;",
+                " }",
+            ]
+        );
+        assert!(diff_lines(OLD, OLD, false).is_empty());
+    }
+
+    #[test]
+    fn test_diff_colors() {
+        let lines = diff_lines(OLD, NEW, true);
+        assert_eq!(lines[1], " script;");
+        assert_eq!(
+            lines[3],
+            color::paint("-    let x=1;", AnsiColor::Red.on_default())
+        );
+        assert_eq!(
+            lines[4],
+            color::paint("+    let x = 1;", AnsiColor::Green.on_default())
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! The final error of a command, reported before exiting, see `report_fatal`.
+
+use std::error::Error;
+use std::fmt::Write;
+
+/// The exit code of `report_fatal`.
+const DEFAULT_EXIT_CODE: i32 = 1;
+
+/// Print `err` and the chain of errors that caused it in red, flush the log output and exit with
+/// code 1, e.g. at the end of the `main` of a forc subcommand:
+///
+/// ```text
+/// failed to build my_contract
+///
+/// Caused by:
+///     failed to fetch std
+///     connection refused
+/// ```
+///
+/// The error is logged at ERROR like `println_red_err`. Use `report_fatal_with_code` to exit with
+/// another code.
+///
+/// ```no_run
+/// use forc_tracing::report_fatal;
+///
+/// let err = std::fs::read("Forc.toml").unwrap_err();
+/// report_fatal(&err);
+/// ```
+pub fn report_fatal(err: &dyn Error) -> ! {
+    report_fatal_with_code(err, DEFAULT_EXIT_CODE)
+}
+
+/// Like `report_fatal`, but exits with `code`.
+///
+/// The background writers of `TracingSubscriberOptions::non_blocking` aren't waited for, drop the
+/// `TracingGuard` before calling this for that.
+pub fn report_fatal_with_code(err: &dyn Error, code: i32) -> ! {
+    crate::println_red_err(&format_error_chain(err));
+    crate::flush();
+    std::process::exit(code)
+}
+
+/// `err` followed by the errors that caused it, each indented on a line of its own below
+/// `Caused by:`, as `report_fatal` prints them. The continuation lines of each are indented too.
+pub fn format_error_chain(err: &dyn Error) -> String {
+    let mut text = err.to_string();
+    let mut source = err.source();
+    if source.is_some() {
+        text.push_str("\n\nCaused by:");
+    }
+    while let Some(err) = source {
+        for line in err.to_string().lines() {
+            let _ = write!(text, "\n    {line}");
+        }
+        source = err.source();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct Failure {
+        message: &'static str,
+        source: Option<Box<Failure>>,
+    }
+
+    impl Failure {
+        fn new(message: &'static str, source: Option<Failure>) -> Self {
+            Self {
+                message,
+                source: source.map(Box::new),
+            }
+        }
+    }
+
+    impl fmt::Display for Failure {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl Error for Failure {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            self.source.as_deref().map(|source| source as _)
+        }
+    }
+
+    #[test]
+    fn test_format_error_chain() {
+        let err = Failure::new(
+            "failed to build my_contract",
+            Some(Failure::new(
+                "failed to fetch std",
+                Some(Failure::new("connection refused\nretried 3 times", None)),
+            )),
+        );
+        assert_eq!(
+            format_error_chain(&err),
+            "failed to build my_contract\n\
+             \n\
+             Caused by:\n    \
+             failed to fetch std\n    \
+             connection refused\n    \
+             retried 3 times"
+        );
+        let err = Failure::new("failed to build my_contract", None);
+        assert_eq!(format_error_chain(&err), "failed to build my_contract");
+    }
+}
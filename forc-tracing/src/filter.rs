@@ -0,0 +1,748 @@
+//! Resolution of which events are logged.
+
+use crate::{TracingInitError, TracingSubscriberOptions};
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex, RwLock};
+use std::{cmp, env};
+use tracing::callsite::{Callsite, Identifier};
+use tracing::field::FieldSet;
+use tracing::metadata::Kind;
+use tracing::subscriber::Interest;
+use tracing::{Level, Metadata, Subscriber};
+use tracing_subscriber::{
+    filter::{EnvFilter, LevelFilter, ParseError},
+    layer::{Layered, SubscriberExt},
+    reload, Registry,
+};
+
+const LOG_FILTER: &str = "RUST_LOG";
+
+/// The targets of forc's own crates, that the level, verbosity and silent options apply to. These
+/// are prefixes, e.g. `forc` matches the `forc_pkg` crate too.
+const DEFAULT_TARGETS: &[&str] = &["forc", "sway", "test"];
+
+/// The filter that `init_tracing_subscriber` applies to the log output for `options`, built from
+/// `resolve_filter_directives`.
+///
+/// Panics if `RUST_LOG`, `target_levels` or `suppress_targets` contain invalid directives.
+pub fn resolve_env_filter(options: &TracingSubscriberOptions) -> EnvFilter {
+    parse_directives(&resolve_filter_directives(options)).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// The filter of the `directives` resolved from the options.
+///
+/// Returns an error if they are invalid, because of `RUST_LOG`, `target_levels` or
+/// `suppress_targets`.
+fn parse_directives(directives: &str) -> Result<EnvFilter, TracingInitError> {
+    EnvFilter::try_new(directives).map_err(|err| TracingInitError::InvalidFilter(err.to_string()))
+}
+
+/// The filter directives, in the syntax of `RUST_LOG`, that `init_tracing_subscriber` resolves
+/// `options` to, e.g. to show why an event isn't logged.
+///
+/// In order of precedence, these are `off` in silent mode, `RUST_LOG` or `error` directives in
+/// quiet mode, the directives for the `log_level` or `verbosity` options, `RUST_LOG`, or `info`.
+/// The `target_levels` are appended to all but the first, followed by `off` directives for the
+/// `suppress_targets`.
+pub fn resolve_filter_directives(options: &TracingSubscriberOptions) -> String {
+    // Silent mode suppresses everything, whatever else is set.
+    if options.silent == Some(true) {
+        return LevelFilter::OFF.to_string();
+    }
+
+    let from_env =
+        || env::var_os(LOG_FILTER).map(|directives| directives.to_string_lossy().into_owned());
+    let mut directives = if options.quiet == Some(true) {
+        // Quiet mode wins over the log level and verbosity, but not over an explicit RUST_LOG, so
+        // that a quiet command can still be debugged.
+        from_env()
+            .unwrap_or_else(|| level_directives(LevelFilter::ERROR, false, &options.extra_targets))
+    } else {
+        // If log level or verbosity is set, it overrides the RUST_LOG setting
+        level_override(options)
+            .or_else(from_env)
+            .unwrap_or_else(|| "info".to_string())
+    };
+    push_target_levels(&mut directives, &options.target_levels);
+    push_suppressed(&mut directives, &options.suppress_targets);
+    directives
+}
+
+/// Append a directive for each of `target_levels` to `directives`.
+///
+/// A directive for a longer target takes precedence over one for its prefix, e.g. `sway_core` over
+/// `sway`, and one for the same target replaces it, so these win over the broad directives.
+fn push_target_levels(directives: &mut String, target_levels: &[(String, LevelFilter)]) {
+    for (target, level) in target_levels {
+        if !directives.is_empty() {
+            directives.push(',');
+        }
+        let _ = write!(directives, "{target}={level}");
+    }
+}
+
+/// Append an `off` directive for each of `suppress_targets` to `directives`, after all others so
+/// that these replace any for the same target.
+fn push_suppressed(directives: &mut String, suppress_targets: &[String]) {
+    for target in suppress_targets {
+        if !directives.is_empty() {
+            directives.push(',');
+        }
+        let _ = write!(directives, "{target}={}", LevelFilter::OFF);
+    }
+}
+
+/// The level of forc's own targets for a `verbosity` count, e.g. of a clap flag with
+/// `ArgAction::Count`: none for 0 to leave the level as is, DEBUG for 1 (`-v`) and TRACE for 2
+/// (`-vv`) and beyond.
+///
+/// ```
+/// use forc_tracing::level_filter_from_verbosity;
+/// use tracing_subscriber::filter::LevelFilter;
+///
+/// assert_eq!(level_filter_from_verbosity(1), Some(LevelFilter::DEBUG));
+/// ```
+pub fn level_filter_from_verbosity(verbosity: u8) -> Option<LevelFilter> {
+    match verbosity {
+        0 => None,
+        1 => Some(LevelFilter::DEBUG), // matches --verbose or -v
+        _ => Some(LevelFilter::TRACE), // matches -vv and beyond
+    }
+}
+
+/// The filter directives for the `log_level` or `verbosity` options, if either is set.
+fn level_override(options: &TracingSubscriberOptions) -> Option<String> {
+    if let Some(level) = options.log_level {
+        return Some(level_directives(level, false, &options.extra_targets));
+    }
+    let verbosity = options.verbosity?;
+    let level = level_filter_from_verbosity(verbosity)?;
+    // -vvv and beyond log everything, including dependencies.
+    Some(level_directives(
+        level,
+        verbosity >= 3,
+        &options.extra_targets,
+    ))
+}
+
+/// The filter directives applying `level` to the default targets and `extra_targets`, and to all
+/// other targets too if `all_targets` is set.
+fn level_directives(level: LevelFilter, all_targets: bool, extra_targets: &[String]) -> String {
+    // Other crates are capped at INFO by default, so that raising the verbosity doesn't drown
+    // forc's own logs in dependency noise.
+    let default_level = match all_targets {
+        true => level,
+        false => cmp::min(level, LevelFilter::INFO),
+    };
+    let mut directives = vec![default_level.to_string()];
+    let targets = DEFAULT_TARGETS
+        .iter()
+        .copied()
+        .chain(extra_targets.iter().map(String::as_str));
+    directives.extend(targets.map(|target| format!("{target}={level}")));
+    directives.join(",")
+}
+
+/// The filter of the ring buffer of `ring_buffer_capacity` and of the spans exported to
+/// `otlp_endpoint`, which includes everything of forc's own targets and `extra_targets`, and that
+/// of other targets at INFO at most.
+pub(crate) fn own_targets_filter(options: &TracingSubscriberOptions) -> EnvFilter {
+    EnvFilter::new(level_directives(
+        LevelFilter::TRACE,
+        false,
+        &options.extra_targets,
+    ))
+}
+
+/// Wrap the filter that `options` resolve to in a layer that can be swapped out at runtime through
+/// the returned handle.
+pub(crate) fn reloadable_env_filter<S: Subscriber + 'static>(
+    options: &TracingSubscriberOptions,
+) -> Result<(reload::Layer<EnvFilter, S>, ReloadHandle), TracingInitError> {
+    let directives = resolve_filter_directives(options);
+    let (layer, handle) = reload::Layer::new(parse_directives(&directives)?);
+    let handle = ReloadHandle {
+        reload: Arc::new(move |filter| handle.reload(filter)),
+        probe: Arc::new(RwLock::new(FilterProbe::new(parse_directives(
+            &directives,
+        )?))),
+        directives: Arc::new(RwLock::new(directives)),
+        extra_targets: options.extra_targets.clone().into(),
+        target_levels: options.target_levels.clone().into(),
+        suppress_targets: options.suppress_targets.clone().into(),
+    };
+    Ok((layer, handle))
+}
+
+/// A handle for changing which events are logged while the program is running, e.g. to raise the
+/// verbosity of a long-running server on request.
+///
+/// Returned by `init_reloadable_tracing_subscriber`. The handle is cheap to clone and can be shared
+/// between threads.
+#[derive(Clone)]
+pub struct ReloadHandle {
+    // Replaces the filter, whatever the subscriber that it belongs to, e.g. with the layers of
+    // `init_tracing_subscriber_with_layers` below it.
+    reload: Arc<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>,
+    probe: Arc<RwLock<FilterProbe>>,
+    // The directives of the current filter, for `with_min_level` to restore.
+    directives: Arc<RwLock<String>>,
+    extra_targets: Arc<[String]>,
+    target_levels: Arc<[(String, LevelFilter)]>,
+    suppress_targets: Arc<[String]>,
+}
+
+impl fmt::Debug for ReloadHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadHandle")
+            .field("extra_targets", &self.extra_targets)
+            .field("target_levels", &self.target_levels)
+            .field("suppress_targets", &self.suppress_targets)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The error returned when the filter of a `ReloadHandle` could not be changed.
+#[derive(Debug, thiserror::Error)]
+pub enum ReloadError {
+    /// The filter directives passed to `ReloadHandle::set_filter` are invalid.
+    #[error("invalid filter directives: {0}")]
+    InvalidFilter(#[from] ParseError),
+    /// The subscriber the handle belongs to no longer exists.
+    #[error("failed to reload the filter: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+impl ReloadHandle {
+    /// Log forc's own targets, and the `extra_targets` the subscriber was initialized with, at
+    /// `level`, the same way as the `log_level` option. The `target_levels` and `suppress_targets`
+    /// the subscriber was initialized with still take precedence.
+    pub fn set_level(&self, level: LevelFilter) -> Result<(), ReloadError> {
+        let mut directives = level_directives(level, false, &self.extra_targets);
+        push_target_levels(&mut directives, &self.target_levels);
+        push_suppressed(&mut directives, &self.suppress_targets);
+        self.set_filter(&directives)
+    }
+
+    /// Replace the filter with the given directives, in the syntax of `RUST_LOG`.
+    pub fn set_filter(&self, directives: &str) -> Result<(), ReloadError> {
+        let filter = EnvFilter::try_new(directives)?;
+        (self.reload)(filter)?;
+        *self.probe.write().unwrap_or_else(|e| e.into_inner()) =
+            FilterProbe::new(EnvFilter::try_new(directives)?);
+        *self.directives.write().unwrap_or_else(|e| e.into_inner()) = directives.to_string();
+        Ok(())
+    }
+
+    /// Run `f` with every target logged at `level` at least, and the filter restored afterwards,
+    /// also if `f` panics, see `with_min_level`.
+    pub fn with_min_level<R>(&self, level: LevelFilter, f: impl FnOnce() -> R) -> R {
+        let prior = self
+            .directives
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        // The floor only ever adds events, so it's no reason not to run `f` if it can't be set.
+        let _ = self.set_filter(&raise_floor(&prior, level));
+        let _restore = Restore {
+            handle: self,
+            directives: prior,
+        };
+        f()
+    }
+
+    /// Whether the current filter lets events at `level` of `target` through, see `is_enabled`.
+    pub fn is_enabled(&self, level: Level, target: &str) -> bool {
+        self.probe
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .enabled(level, target)
+    }
+}
+
+/// Sets the filter of `handle` back to `directives` when dropped.
+struct Restore<'a> {
+    handle: &'a ReloadHandle,
+    directives: String,
+}
+
+impl Drop for Restore<'_> {
+    fn drop(&mut self) {
+        // The directives were in use before, so only a subscriber that is gone can refuse them.
+        let _ = self.handle.set_filter(&self.directives);
+    }
+}
+
+/// `directives` with the level of each raised to `floor` if it is less verbose, and `floor` as the
+/// default level if they have none, so that every target is logged at `floor` at least.
+fn raise_floor(directives: &str, floor: LevelFilter) -> String {
+    let mut has_default = false;
+    let mut raised: Vec<String> = directives
+        .split(',')
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| {
+            if let Ok(level) = directive.parse::<LevelFilter>() {
+                has_default = true;
+                return cmp::max(level, floor).to_string();
+            }
+            match directive.rsplit_once('=') {
+                Some((selector, level)) => match level.parse::<LevelFilter>() {
+                    Ok(level) => format!("{selector}={}", cmp::max(level, floor)),
+                    Err(_) => directive.to_string(),
+                },
+                // A target alone is logged at TRACE already.
+                None => directive.to_string(),
+            }
+        })
+        .collect();
+    if !has_default {
+        raised.insert(0, floor.to_string());
+    }
+    raised.join(",")
+}
+
+/// The `ReloadHandle` of the global subscriber, once forc's is installed.
+static INSTALLED: Mutex<Option<ReloadHandle>> = Mutex::new(None);
+
+/// Remember `handle` as that of the global subscriber, for `is_enabled`.
+pub(crate) fn set_installed(handle: &ReloadHandle) {
+    *INSTALLED.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle.clone());
+}
+
+/// Whether an event at `level` of `target` would pass the filter of the subscriber installed by
+/// `init_tracing_subscriber`, e.g. to skip building an expensive DEBUG payload that would be
+/// dropped anyway:
+///
+/// ```
+/// use forc_tracing::is_enabled;
+/// use tracing::Level;
+///
+/// if is_enabled(Level::DEBUG, "forc_pkg") {
+///     tracing::debug!(target: "forc_pkg", "{:?}", std::env::vars().collect::<Vec<_>>());
+/// }
+/// ```
+///
+/// Unlike `tracing::enabled!`, `target` doesn't need to be known at compile time. The filter is
+/// the one set by the options or `RUST_LOG`, or last by a `ReloadHandle`, without directives that
+/// depend on the spans entered. If forc's subscriber isn't installed, only the most verbose level
+/// enabled by any subscriber is checked.
+pub fn is_enabled(level: Level, target: &str) -> bool {
+    let installed = INSTALLED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match installed {
+        Some(handle) => handle.is_enabled(level, target),
+        None => level <= LevelFilter::current(),
+    }
+}
+
+/// Run `f` with every target logged at `level` at least by the subscriber installed by
+/// `init_tracing_subscriber`, e.g. to trace a tricky operation without restarting, and the filter
+/// restored afterwards, also if `f` panics. Targets already logged at a more verbose level stay that
+/// way.
+///
+/// ```no_run
+/// use forc_tracing::with_min_level;
+/// use tracing::level_filters::LevelFilter;
+///
+/// let lock = with_min_level(LevelFilter::TRACE, || std::fs::read_to_string("Forc.lock"));
+/// ```
+///
+/// The filter is global, so the events that other threads log meanwhile are let through too, and
+/// changing it meanwhile, e.g. with a `ReloadHandle` or another `with_min_level` on another thread,
+/// is undone when `f` returns. If forc's subscriber isn't installed, `f` is just run.
+pub fn with_min_level<R>(level: LevelFilter, f: impl FnOnce() -> R) -> R {
+    let installed = INSTALLED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match installed {
+        Some(handle) => handle.with_min_level(level, f),
+        None => f(),
+    }
+}
+
+/// A subscriber of its own around a copy of a filter, that tells whether the filter lets an event
+/// through without dispatching one. Asking the installed subscriber instead would disturb the
+/// state the layers of the events being logged keep between the filters and the layers.
+#[derive(Debug)]
+struct FilterProbe(Layered<EnvFilter, Registry>);
+
+impl FilterProbe {
+    fn new(filter: EnvFilter) -> Self {
+        Self(Registry::default().with(filter))
+    }
+
+    fn enabled(&self, level: Level, target: &str) -> bool {
+        let fields = FieldSet::new(&[], Identifier(&PROBE_CALLSITE));
+        let metadata = Metadata::new(
+            "is_enabled",
+            target,
+            level,
+            None,
+            None,
+            None,
+            fields,
+            Kind::EVENT,
+        );
+        self.0.enabled(&metadata)
+    }
+}
+
+// The callsite that the events of `FilterProbe` claim to come from.
+struct ProbeCallsite;
+
+static PROBE_CALLSITE: ProbeCallsite = ProbeCallsite;
+
+static PROBE_METADATA: Metadata<'static> = Metadata::new(
+    "is_enabled",
+    "forc_tracing",
+    Level::TRACE,
+    None,
+    None,
+    None,
+    FieldSet::new(&[], Identifier(&PROBE_CALLSITE)),
+    Kind::EVENT,
+);
+
+impl Callsite for ProbeCallsite {
+    fn set_interest(&self, _: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        &PROBE_METADATA
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, TracingSubscriberOptionsBuilder, ENV_LOCK};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    /// Log the events of `f` through the filter that `options` resolve to, returning the output.
+    fn capture_filtered(options: &TracingSubscriberOptions, f: impl FnOnce()) -> String {
+        let capture = CapturingWriter::new();
+        let subscriber = Registry::default()
+            .with(resolve_env_filter(options))
+            .with(fmt_layer(&Default::default(), false, None, capture.clone()).unwrap());
+        tracing::subscriber::with_default(subscriber, f);
+        capture.contents()
+    }
+
+    #[test]
+    fn test_reload_level() {
+        let capture = CapturingWriter::new();
+        let (filter, handle) = reloadable_env_filter(&TracingSubscriberOptions::default()).unwrap();
+        let subscriber = Registry::default()
+            .with(filter)
+            .with(fmt_layer(&Default::default(), false, None, capture.clone()).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            handle.set_level(LevelFilter::OFF).unwrap();
+            tracing::error!(target: "forc", "dropped");
+            handle.set_level(LevelFilter::TRACE).unwrap();
+            tracing::trace!(target: "forc", "passed");
+            handle.set_filter("off,sway=debug").unwrap();
+            tracing::debug!(target: "forc", "dropped");
+            tracing::debug!(target: "sway_core", "filtered");
+        });
+        assert_eq!(capture.contents(), "passed\nfiltered\n");
+        assert!(matches!(
+            handle.set_filter("forc=loud"),
+            Err(ReloadError::InvalidFilter(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_enabled() {
+        let options = TracingSubscriberOptions::builder()
+            .log_level(LevelFilter::DEBUG)
+            .target_level("hyper", LevelFilter::WARN)
+            .build();
+        let (_filter, handle) = reloadable_env_filter::<Registry>(&options).unwrap();
+        assert!(handle.is_enabled(Level::ERROR, "forc_pkg"));
+        assert!(handle.is_enabled(Level::DEBUG, "forc_pkg"));
+        assert!(!handle.is_enabled(Level::TRACE, "forc_pkg"));
+        // Other crates are logged at INFO at most, and below the levels given for them.
+        assert!(handle.is_enabled(Level::INFO, "serde"));
+        assert!(!handle.is_enabled(Level::DEBUG, "serde"));
+        assert!(handle.is_enabled(Level::WARN, "hyper::client"));
+        assert!(!handle.is_enabled(Level::INFO, "hyper::client"));
+
+        // Reloading the filter is taken into account, and a failed reload leaves it as it was.
+        handle.set_level(LevelFilter::TRACE).unwrap();
+        assert!(handle.is_enabled(Level::TRACE, "sway_core"));
+        assert!(!handle.is_enabled(Level::INFO, "hyper"));
+        handle.set_filter("error").unwrap();
+        assert!(!handle.is_enabled(Level::WARN, "forc"));
+        assert!(handle.set_filter("forc=loud").is_err());
+        assert!(handle.is_enabled(Level::ERROR, "forc"));
+        assert!(!handle.is_enabled(Level::WARN, "forc"));
+    }
+
+    #[test]
+    fn test_with_min_level() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let capture = CapturingWriter::new();
+        let options = TracingSubscriberOptions::builder()
+            .log_level(LevelFilter::INFO)
+            .target_level("hyper", LevelFilter::WARN)
+            .build();
+        let (filter, handle) = reloadable_env_filter(&options).unwrap();
+        let subscriber = Registry::default()
+            .with(filter)
+            .with(fmt_layer(&Default::default(), false, None, capture.clone()).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::trace!(target: "forc_pkg", "dropped");
+            let answer = handle.with_min_level(LevelFilter::TRACE, || {
+                assert!(handle.is_enabled(Level::TRACE, "hyper"));
+                tracing::trace!(target: "forc_pkg", "traced");
+                tracing::trace!(target: "hyper", "traced dependency");
+                42
+            });
+            assert_eq!(answer, 42);
+            tracing::trace!(target: "forc_pkg", "dropped again");
+            tracing::info!(target: "hyper", "dropped again");
+
+            // Restored also if the closure panics.
+            let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                handle.with_min_level(LevelFilter::DEBUG, || panic!("failed"))
+            }));
+            assert!(panicked.is_err());
+            tracing::debug!(target: "forc_pkg", "dropped after the panic");
+        });
+        assert_eq!(capture.contents(), "traced\ntraced dependency\n");
+        assert!(!handle.is_enabled(Level::TRACE, "forc_pkg"));
+    }
+
+    #[test]
+    fn test_raise_floor() {
+        assert_eq!(
+            raise_floor("info,forc=trace,hyper=warn,sway", LevelFilter::DEBUG),
+            "debug,forc=trace,hyper=debug,sway"
+        );
+        assert_eq!(
+            raise_floor("forc[build{name=std}]=info", LevelFilter::TRACE),
+            "trace,forc[build{name=std}]=trace"
+        );
+    }
+
+    #[test]
+    fn test_extra_targets() {
+        let options = TracingSubscriberOptions {
+            verbosity: Some(2),
+            extra_targets: vec!["my_plugin".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            level_override(&options).unwrap(),
+            "info,forc=trace,sway=trace,test=trace,my_plugin=trace"
+        );
+        let output = capture_filtered(&options, || {
+            tracing::trace!(target: "my_plugin::resolve", "plugin");
+            tracing::trace!(target: "forc_pkg", "forc");
+            tracing::trace!(target: "hyper", "dependency");
+            tracing::info!(target: "hyper", "dependency info");
+        });
+        assert_eq!(output, "plugin\nforc\ndependency info\n");
+    }
+
+    #[test]
+    fn test_target_levels() {
+        let options = TracingSubscriberOptions {
+            verbosity: Some(1),
+            target_levels: vec![
+                ("sway_core".to_string(), LevelFilter::TRACE),
+                ("forc_pkg".to_string(), LevelFilter::WARN),
+            ],
+            ..Default::default()
+        };
+        let output = capture_filtered(&options, || {
+            tracing::trace!(target: "sway_core::semantic_analysis", "sway_core trace");
+            tracing::trace!(target: "sway_ir", "sway_ir trace");
+            tracing::debug!(target: "sway_ir", "sway_ir debug");
+            tracing::info!(target: "forc_pkg", "forc_pkg info");
+            tracing::warn!(target: "forc_pkg", "forc_pkg warn");
+            tracing::debug!(target: "forc_util", "forc_util debug");
+        });
+        assert_eq!(
+            output,
+            "sway_core trace\nsway_ir debug\nforc_pkg warn\nforc_util debug\n"
+        );
+
+        // A later level for the same target replaces an earlier one.
+        let options = TracingSubscriberOptions {
+            log_level: Some(LevelFilter::INFO),
+            target_levels: vec![
+                ("forc_pkg".to_string(), LevelFilter::ERROR),
+                ("forc_pkg".to_string(), LevelFilter::DEBUG),
+            ],
+            ..Default::default()
+        };
+        let output = capture_filtered(&options, || tracing::debug!(target: "forc_pkg", "debug"));
+        assert_eq!(output, "debug\n");
+    }
+
+    #[test]
+    fn test_suppress_targets() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        env::set_var(LOG_FILTER, "trace,salsa=trace");
+        let options = TracingSubscriberOptions::builder()
+            .target_level("salsa", LevelFilter::DEBUG)
+            .suppress_target("salsa")
+            .build();
+        assert_eq!(
+            resolve_filter_directives(&options),
+            "trace,salsa=trace,salsa=debug,salsa=off"
+        );
+        let output = capture_filtered(&options, || {
+            tracing::error!(target: "salsa::runtime", "suppressed");
+            tracing::trace!(target: "forc_pkg", "allowed");
+        });
+        env::remove_var(LOG_FILTER);
+        assert_eq!(output, "allowed\n");
+    }
+
+    #[test]
+    fn test_invalid_suppress_targets() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        env::remove_var(LOG_FILTER);
+        for target in ["a=b=c", "x["] {
+            let options = TracingSubscriberOptions::builder()
+                .suppress_target(target)
+                .build();
+            assert!(
+                matches!(
+                    reloadable_env_filter::<Registry>(&options),
+                    Err(TracingInitError::InvalidFilter(_))
+                ),
+                "{target}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_filter_directives() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        env::remove_var(LOG_FILTER);
+        let resolve =
+            |options: TracingSubscriberOptionsBuilder| resolve_filter_directives(&options.build());
+        let options = TracingSubscriberOptions::builder;
+        assert_eq!(resolve(options()), "info");
+        assert_eq!(resolve(options().silent(true).verbosity(2)), "off");
+        assert_eq!(
+            resolve(options().verbosity(1)),
+            "info,forc=debug,sway=debug,test=debug"
+        );
+        assert_eq!(
+            resolve(options().verbosity(2)),
+            "info,forc=trace,sway=trace,test=trace"
+        );
+        assert_eq!(
+            resolve(options().log_level(LevelFilter::WARN).verbosity(2)),
+            "warn,forc=warn,sway=warn,test=warn"
+        );
+        assert_eq!(
+            resolve(options().target_level("sway_core", LevelFilter::TRACE)),
+            "info,sway_core=trace"
+        );
+
+        env::set_var(LOG_FILTER, "forc_pkg=debug");
+        let from_env = resolve(options());
+        let overridden = resolve(options().verbosity(1));
+        env::remove_var(LOG_FILTER);
+        assert_eq!(from_env, "forc_pkg=debug");
+        assert_eq!(overridden, "info,forc=debug,sway=debug,test=debug");
+    }
+
+    #[test]
+    fn test_quiet() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        env::remove_var(LOG_FILTER);
+        let log = || {
+            tracing::error!(target: "forc", "Failed");
+            tracing::warn!(target: "sway_core", "Unused");
+            tracing::info!(target: "forc_pkg", "Compiling");
+        };
+        let options = TracingSubscriberOptions::builder;
+        let quiet = options().quiet(true).verbosity(2).build();
+        assert_eq!(capture_filtered(&quiet, log), "Failed\n");
+        let silent = options().quiet(true).silent(true).build();
+        assert_eq!(capture_filtered(&silent, log), "");
+
+        env::set_var(LOG_FILTER, "forc_pkg=info");
+        let from_env = resolve_filter_directives(&quiet);
+        env::remove_var(LOG_FILTER);
+        assert_eq!(from_env, "forc_pkg=info");
+    }
+
+    #[test]
+    fn test_level_filter_from_verbosity() {
+        assert_eq!(level_filter_from_verbosity(0), None);
+        assert_eq!(level_filter_from_verbosity(1), Some(LevelFilter::DEBUG));
+        assert_eq!(level_filter_from_verbosity(2), Some(LevelFilter::TRACE));
+        assert_eq!(level_filter_from_verbosity(3), Some(LevelFilter::TRACE));
+        assert_eq!(
+            level_filter_from_verbosity(u8::MAX),
+            Some(LevelFilter::TRACE)
+        );
+    }
+
+    #[test]
+    fn test_verbosity_levels() {
+        let directives = |verbosity| {
+            level_override(&TracingSubscriberOptions {
+                verbosity: Some(verbosity),
+                ..Default::default()
+            })
+        };
+        assert_eq!(directives(0), None);
+        assert_eq!(
+            directives(1).unwrap(),
+            "info,forc=debug,sway=debug,test=debug"
+        );
+        assert_eq!(
+            directives(2).unwrap(),
+            "info,forc=trace,sway=trace,test=trace"
+        );
+        assert_eq!(
+            directives(3).unwrap(),
+            "trace,forc=trace,sway=trace,test=trace"
+        );
+        assert_eq!(directives(4), directives(3));
+    }
+
+    #[test]
+    fn test_silent_with_verbosity() {
+        let options = TracingSubscriberOptions {
+            silent: Some(true),
+            verbosity: Some(2),
+            log_level: Some(LevelFilter::TRACE),
+            ..Default::default()
+        };
+        let output = capture_filtered(&options, || {
+            tracing::error!(target: "forc", "error");
+            tracing::trace!(target: "forc", "trace");
+        });
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_silent_without_verbosity() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let options = TracingSubscriberOptions {
+            silent: Some(true),
+            ..Default::default()
+        };
+        let output = capture_filtered(&options, || tracing::error!(target: "forc", "error"));
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_silent_with_rust_log() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        env::set_var(LOG_FILTER, "trace");
+        let options = TracingSubscriberOptions {
+            silent: Some(true),
+            ..Default::default()
+        };
+        let output = capture_filtered(&options, || tracing::error!(target: "forc", "error"));
+        env::remove_var(LOG_FILTER);
+        assert_eq!(output, "");
+    }
+}
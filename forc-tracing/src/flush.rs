@@ -0,0 +1,207 @@
+//! Buffering of the output written to stdout and stderr, see `TracingSubscriberOptions::flush_policy`.
+
+use crate::buffered::BufferedEvent;
+use crate::color::StripAnsi;
+use crate::writer::lock;
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// When the output for stdout and stderr is written out to them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Write each event to its stream as it is logged.
+    Immediate,
+    /// Collect the output in a buffer, and write out and flush only complete lines, so that the
+    /// stream never receives part of a line.
+    LineBuffered,
+    /// Collect the output in a buffer that a background thread writes out and flushes once per
+    /// interval, so that logging doesn't wait for a slow terminal, at the cost of the output
+    /// showing up that much later. `flush` and dropping the `TracingGuard` write it out at once.
+    Interval(Duration),
+}
+
+type Destination = Box<dyn Fn() -> Box<dyn io::Write> + Send + Sync>;
+
+// The buffers of all subscribers built, for `flush` to write out.
+static BUFFERS: Mutex<Vec<Weak<FlushBuffer>>> = Mutex::new(Vec::new());
+
+/// The output for one stream that hasn't been written out to it yet.
+pub(crate) struct FlushBuffer {
+    pending: Mutex<Vec<u8>>,
+    line_buffered: bool,
+    destination: Destination,
+}
+
+impl FlushBuffer {
+    /// A buffer writing out to the writers returned by `destination`, either whenever a line is
+    /// complete if `line_buffered` is set, or only when drained.
+    pub(crate) fn new(
+        line_buffered: bool,
+        destination: impl Fn() -> Box<dyn io::Write> + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        let buffer = Arc::new(Self {
+            pending: Mutex::default(),
+            line_buffered,
+            destination: Box::new(destination),
+        });
+        let mut buffers = lock(&BUFFERS);
+        buffers.retain(|buffer| buffer.strong_count() > 0);
+        buffers.push(Arc::downgrade(&buffer));
+        buffer
+    }
+
+    /// A writer for one event, that passes color through only if `ansi` is set.
+    pub(crate) fn writer(&self, ansi: bool) -> Box<dyn io::Write + '_> {
+        // The output of the event is added to the buffer whole.
+        let event = BufferedEvent::new(io::sink(), move |bytes: &[u8], _: &mut io::Sink| {
+            self.push(bytes);
+            Ok(())
+        });
+        match ansi {
+            true => Box::new(event),
+            false => Box::new(StripAnsi::new(event)),
+        }
+    }
+
+    fn push(&self, bytes: &[u8]) {
+        let mut pending = lock(&self.pending);
+        pending.extend_from_slice(bytes);
+        if self.line_buffered {
+            if let Some(newline) = pending.iter().rposition(|&byte| byte == b'\n') {
+                let lines: Vec<u8> = pending.drain(..=newline).collect();
+                self.write_out(&lines);
+            }
+        }
+    }
+
+    /// Write out everything that is pending.
+    pub(crate) fn drain(&self) {
+        // The lock is held while writing, so that the output of a concurrent drain or push
+        // can't overtake it.
+        let mut pending = lock(&self.pending);
+        if !pending.is_empty() {
+            self.write_out(&std::mem::take(&mut *pending));
+        }
+    }
+
+    fn write_out(&self, bytes: &[u8]) {
+        let mut destination = (self.destination)();
+        // The events were already accepted into the buffer, so no caller is left to return an
+        // error to.
+        let _ = destination.write_all(bytes);
+        let _ = destination.flush();
+    }
+}
+
+/// Write out the pending output of every subscriber.
+pub(crate) fn drain_all() {
+    let buffers: Vec<_> = lock(&BUFFERS).iter().filter_map(Weak::upgrade).collect();
+    for buffer in buffers {
+        buffer.drain();
+    }
+}
+
+/// Drains `buffers` once per interval on a background thread, and one last time when dropped.
+pub(crate) struct Flusher {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Flusher {
+    pub(crate) fn spawn(buffers: Vec<Arc<FlushBuffer>>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            let drain = || buffers.iter().for_each(|buffer| buffer.drain());
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                drain();
+            }
+            drain();
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Flusher {
+    fn drop(&mut self) {
+        // Disconnecting the channel stops the thread.
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for Flusher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Flusher").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fmt_layer;
+    use crate::testing::CapturingWriter;
+    use std::io::Write;
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn buffer(line_buffered: bool, capture: &CapturingWriter) -> Arc<FlushBuffer> {
+        let capture = capture.clone();
+        FlushBuffer::new(line_buffered, move || Box::new(capture.clone()))
+    }
+
+    struct Buffered(Arc<FlushBuffer>);
+
+    impl<'a> MakeWriter<'a> for Buffered {
+        type Writer = Box<dyn io::Write + 'a>;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.0.writer(false)
+        }
+    }
+
+    #[test]
+    fn test_interval_flush_policy() {
+        let capture = CapturingWriter::new();
+        let buffer = buffer(false, &capture);
+        let layer = fmt_layer(&Default::default(), false, None, Buffered(buffer.clone())).unwrap();
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            tracing::info!("Compiling");
+            tracing::info!("Finished");
+        });
+        assert_eq!(capture.contents(), "");
+        crate::flush();
+        assert_eq!(capture.contents(), "Compiling\nFinished\n");
+
+        // The background thread drains the buffer on its own, and one last time when stopped.
+        let flusher = Flusher::spawn(vec![buffer.clone()], Duration::from_millis(10));
+        buffer.writer(false).write_all(b"Testing\n").unwrap();
+        let start = std::time::Instant::now();
+        while !capture.contents().ends_with("Testing\n") {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+        drop(flusher);
+        let flusher = Flusher::spawn(vec![buffer.clone()], Duration::from_secs(3600));
+        buffer.writer(false).write_all(b"Done\n").unwrap();
+        drop(flusher);
+        assert_eq!(capture.contents(), "Compiling\nFinished\nTesting\nDone\n");
+    }
+
+    #[test]
+    fn test_line_buffered_flush_policy() {
+        let capture = CapturingWriter::new();
+        let buffer = buffer(true, &capture);
+        buffer.writer(false).write_all(b"Compiling\nFin").unwrap();
+        assert_eq!(capture.contents(), "Compiling\n");
+        buffer.writer(true).write_all(b"ished\n").unwrap();
+        assert_eq!(capture.contents(), "Compiling\nFinished\n");
+    }
+}
@@ -0,0 +1,208 @@
+//! Collapsible sections of the log output on CI, see `log_group`.
+
+use crate::PRINT_TARGET;
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The CI services whose logs can be folded, by the variable they set to `true`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Ci {
+    /// `GITHUB_ACTIONS`, folded with `::group::` lines. Groups can't be nested.
+    GitHubActions,
+    /// `GITLAB_CI`, folded with `section_start` and `section_end` lines. Sections can be nested.
+    GitLab,
+    /// `BUILDKITE`, folded with `---` headers, which each close the group before. Groups can't be
+    /// nested, nor closed other than by the next header.
+    Buildkite,
+}
+
+impl Ci {
+    fn detect() -> Option<Self> {
+        let set = |name| env::var(name).is_ok_and(|value| value == "true");
+        if set("GITHUB_ACTIONS") {
+            Some(Self::GitHubActions)
+        } else if set("GITLAB_CI") {
+            Some(Self::GitLab)
+        } else if set("BUILDKITE") {
+            Some(Self::Buildkite)
+        } else {
+            None
+        }
+    }
+
+    fn nests(self) -> bool {
+        self == Self::GitLab
+    }
+}
+
+/// The number of groups open, across threads.
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of GitLab sections opened so far, to give each a name of its own.
+static SECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Run `f` inside a group titled `title` that the CI service folds into one line, e.g. for the
+/// output of each step of a build.
+///
+/// GitHub Actions, GitLab CI and Buildkite are detected by the variables they set. Elsewhere, and
+/// for groups nested in others where the service can't nest them, only the title is printed. The
+/// markers are logged at INFO like the `println_*` helpers, and are only recognized if the lines
+/// aren't decorated, e.g. with a timestamp.
+///
+/// ```
+/// let compiled = forc_tracing::log_group("Compiling my_contract", || {
+///     forc_tracing::println_green("Finished");
+///     true
+/// });
+/// ```
+pub fn log_group<R>(title: &str, f: impl FnOnce() -> R) -> R {
+    let _group = Group::open(title, Ci::detect());
+    f()
+}
+
+/// An open group, closed when dropped, also when `f` panics.
+struct Group {
+    end: Option<End>,
+}
+
+/// How a group is closed.
+enum End {
+    /// `::endgroup::`.
+    EndGroup,
+    /// The `section_end` of the GitLab section of this name.
+    SectionEnd(String),
+}
+
+impl Group {
+    fn open(title: &str, ci: Option<Ci>) -> Self {
+        let depth = DEPTH.fetch_add(1, Ordering::Relaxed);
+        let (start, end) = match ci.filter(|ci| depth == 0 || ci.nests()) {
+            Some(Ci::GitHubActions) => (format!("::group::{title}"), Some(End::EndGroup)),
+            Some(Ci::GitLab) => {
+                let name = format!("forc_{}", SECTIONS.fetch_add(1, Ordering::Relaxed));
+                let start = format!(
+                    "\x1b[0Ksection_start:{}:{name}[collapsed=true]\r\x1b[0K{title}",
+                    unix_time()
+                );
+                (start, Some(End::SectionEnd(name)))
+            }
+            Some(Ci::Buildkite) => (format!("--- {title}"), None),
+            None => (title.to_string(), None),
+        };
+        tracing::info!(target: PRINT_TARGET, "{start}");
+        Self { end }
+    }
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        match &self.end {
+            Some(End::EndGroup) => tracing::info!(target: PRINT_TARGET, "::endgroup::"),
+            Some(End::SectionEnd(name)) => tracing::info!(
+                target: PRINT_TARGET,
+                "\x1b[0Ksection_end:{}:{name}\r\x1b[0K",
+                unix_time()
+            ),
+            None => {}
+        }
+        DEPTH.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::with_captured_logs;
+    use crate::{TracingSubscriberOptions, ENV_LOCK};
+
+    const CI_VARS: [&str; 3] = ["GITHUB_ACTIONS", "GITLAB_CI", "BUILDKITE"];
+
+    /// The output of `f` with only `ci` of the variables detected set, if any.
+    fn captured_with_ci(ci: Option<&str>, f: impl FnOnce()) -> String {
+        // The groups of other tests would count as nesting.
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var("RUST_LOG");
+        for name in CI_VARS {
+            std::env::remove_var(name);
+        }
+        if let Some(name) = ci {
+            std::env::set_var(name, "true");
+        }
+        let output = with_captured_logs(TracingSubscriberOptions::default(), f);
+        for name in CI_VARS {
+            std::env::remove_var(name);
+        }
+        output
+    }
+
+    fn build() {
+        log_group("Compiling my_contract", || {
+            tracing::info!(target: "forc", "Compiling");
+            log_group(
+                "Type-checking",
+                || tracing::info!(target: "forc", "Checked"),
+            );
+        });
+    }
+
+    #[test]
+    fn test_github_actions_group() {
+        let output = captured_with_ci(Some("GITHUB_ACTIONS"), build);
+        // Groups can't be nested on GitHub Actions, so the inner one is only a title.
+        assert_eq!(
+            output,
+            "::group::Compiling my_contract\nCompiling\nType-checking\nChecked\n::endgroup::\n"
+        );
+        let output = captured_with_ci(Some("GITHUB_ACTIONS"), || {
+            log_group("Testing", || 42);
+        });
+        assert_eq!(output, "::group::Testing\n::endgroup::\n");
+    }
+
+    #[test]
+    fn test_gitlab_group() {
+        let output = captured_with_ci(Some("GITLAB_CI"), build);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("\x1b[0Ksection_start:"));
+        assert!(lines[0].ends_with("[collapsed=true]\r\x1b[0KCompiling my_contract"));
+        assert_eq!(lines[1], "Compiling");
+        assert!(lines[2].ends_with("[collapsed=true]\r\x1b[0KType-checking"));
+        assert_eq!(lines[3], "Checked");
+        // The sections are closed by name, the inner one first.
+        let name = |line: &str| {
+            line.split(':')
+                .nth(2)
+                .unwrap()
+                .split(['[', '\r'])
+                .next()
+                .unwrap()
+                .to_string()
+        };
+        assert!(lines[4].starts_with("\x1b[0Ksection_end:"));
+        assert_eq!(name(lines[4]), name(lines[2]));
+        assert_eq!(name(lines[5]), name(lines[0]));
+        assert_ne!(name(lines[0]), name(lines[2]));
+    }
+
+    #[test]
+    fn test_group_outside_ci() {
+        let output = captured_with_ci(None, build);
+        assert_eq!(
+            output,
+            "Compiling my_contract\nCompiling\nType-checking\nChecked\n"
+        );
+        let output = captured_with_ci(Some("BUILDKITE"), build);
+        assert_eq!(
+            output,
+            "--- Compiling my_contract\nCompiling\nType-checking\nChecked\n"
+        );
+    }
+}
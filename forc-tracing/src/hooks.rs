@@ -0,0 +1,156 @@
+//! Callbacks for the events logged, for custom sinks that don't implement a `Layer`.
+
+use std::fmt::{self, Write};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// An event as passed to the callbacks of `on_event`, owned and with its fields formatted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    /// The message, empty if the event has none.
+    pub message: String,
+    /// The other fields in the order they were given, with their values formatted as the text
+    /// formats show them, e.g. `("name", "my_contract")`.
+    pub fields: Vec<(String, String)>,
+    /// When the event was logged.
+    pub timestamp: SystemTime,
+}
+
+impl LogRecord {
+    fn new(event: &Event<'_>) -> Self {
+        let metadata = event.metadata();
+        let mut record = Self {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: String::new(),
+            fields: Vec::new(),
+            timestamp: SystemTime::now(),
+        };
+        event.record(&mut record);
+        record
+    }
+}
+
+impl Visit for LogRecord {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => self.fields.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let mut text = String::new();
+        let _ = write!(text, "{value:?}");
+        match field.name() {
+            "message" => self.message = text,
+            name => self.fields.push((name.to_string(), text)),
+        }
+    }
+}
+
+type Callback = Arc<dyn Fn(&LogRecord) + Send + Sync>;
+
+pub(crate) type Callbacks = RwLock<Vec<Callback>>;
+
+/// The callbacks of the global subscriber.
+pub(crate) static CALLBACKS: Callbacks = RwLock::new(Vec::new());
+
+fn register(callbacks: &Callbacks, callback: Callback) {
+    callbacks
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(callback);
+}
+
+/// Call `callback` with every event that passes the level filter of the output from now on, e.g.
+/// to forward the errors to an error reporting service. Any number of callbacks can be registered,
+/// before or after the subscriber is initialized, and they are called in the order they were
+/// registered, on the thread that logged the event.
+///
+/// ```
+/// forc_tracing::on_event(|record| {
+///     if record.level == tracing::Level::ERROR {
+///         eprintln!("reporting: {}", record.message);
+///     }
+/// });
+/// ```
+pub fn on_event(callback: impl Fn(&LogRecord) + Send + Sync + 'static) {
+    register(&CALLBACKS, Arc::new(callback));
+}
+
+/// Passes the events of the layer it is combined with to `callbacks`.
+pub(crate) struct HookLayer {
+    callbacks: &'static Callbacks,
+}
+
+impl HookLayer {
+    pub(crate) fn new(callbacks: &'static Callbacks) -> Self {
+        Self { callbacks }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for HookLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Not called with the lock held, so that the callbacks may log or register callbacks
+        // themselves.
+        let callbacks = self
+            .callbacks
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if callbacks.is_empty() {
+            return;
+        }
+        let record = LogRecord::new(event);
+        for callback in callbacks {
+            callback(&record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    #[test]
+    fn test_on_event() {
+        static TEST_CALLBACKS: Callbacks = RwLock::new(Vec::new());
+        let records = Arc::new(Mutex::new(Vec::new()));
+        for _ in 0..2 {
+            let records = records.clone();
+            register(
+                &TEST_CALLBACKS,
+                Arc::new(move |record: &LogRecord| records.lock().unwrap().push(record.clone())),
+            );
+        }
+        let subscriber = Registry::default().with(HookLayer::new(&TEST_CALLBACKS));
+        let before = SystemTime::now();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!(target: "forc_pkg", name = "my_contract", attempts = 3, "fetch failed");
+        });
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        let record = &records[0];
+        assert_eq!(record.level, Level::ERROR);
+        assert_eq!(record.target, "forc_pkg");
+        assert_eq!(record.message, "fetch failed");
+        assert_eq!(
+            record.fields,
+            [
+                ("name".to_string(), "my_contract".to_string()),
+                ("attempts".to_string(), "3".to_string()),
+            ]
+        );
+        assert!(record.timestamp >= before);
+        assert_eq!(records[1], *record);
+    }
+}
@@ -0,0 +1,137 @@
+//! OSC 8 hyperlinks, which terminals that support them render as clickable links.
+
+use crate::color;
+use std::env;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use tracing::Level;
+
+/// Forces hyperlinks on when set to anything other than `0`, or off when set to `0`.
+const FORCE_HYPERLINK: &str = "FORCE_HYPERLINK";
+
+/// Wrap `text` in the OSC 8 escape sequence that links it to `url`.
+///
+/// This doesn't check whether the terminal supports hyperlinks, see `print_file_link` for that.
+pub fn hyperlink(text: &str, url: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
+/// Print `path:line:col`, linked to the file at `path` if the stream it is routed to shows color
+/// and the terminal is known to support hyperlinks.
+///
+/// Detection can be overridden by setting `FORCE_HYPERLINK` to `1` or `0`.
+pub fn print_file_link(path: &Path, line: u32, col: u32) {
+    let enabled = color::level_color(&Level::INFO) && supports_hyperlinks(|var| env::var(var).ok());
+    tracing::info!("{}", file_link(path, line, col, enabled));
+}
+
+/// `path:line:col`, linked to the file at `path` if `enabled`.
+fn file_link(path: &Path, line: u32, col: u32, enabled: bool) -> String {
+    let text = format!("{}:{line}:{col}", path.display());
+    if enabled {
+        hyperlink(&text, &file_url(path))
+    } else {
+        text
+    }
+}
+
+/// The `file://` URL of `path`, made absolute against the current directory.
+fn file_url(path: &Path) -> String {
+    let path = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| PathBuf::from(path)),
+    };
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from("file://");
+    if !path.starts_with('/') {
+        // A Windows path such as `C:/src/main.sw`.
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' | b':' => {
+                url.push(byte as char)
+            }
+            _ => {
+                let _ = write!(url, "%{byte:02X}");
+            }
+        }
+    }
+    url
+}
+
+/// Whether the terminal is known to render OSC 8 hyperlinks, judging by the environment variables
+/// returned by `var`. Terminals that don't support them may print the link target as garbage, so
+/// unknown terminals are assumed not to.
+fn supports_hyperlinks(var: impl Fn(&str) -> Option<String>) -> bool {
+    if let Some(force) = var(FORCE_HYPERLINK) {
+        return force != "0";
+    }
+    if var("WT_SESSION").is_some() || var("KONSOLE_VERSION").is_some() {
+        return true;
+    }
+    if var("VTE_VERSION")
+        .and_then(|version| version.parse::<u32>().ok())
+        .is_some_and(|version| version >= 5000)
+    {
+        return true;
+    }
+    if var("TERM_PROGRAM").is_some_and(|program| {
+        matches!(
+            program.as_str(),
+            "iTerm.app" | "WezTerm" | "vscode" | "Hyper" | "ghostty"
+        )
+    }) {
+        return true;
+    }
+    var("TERM").is_some_and(|term| matches!(term.as_str(), "xterm-kitty" | "alacritty"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |var| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_hyperlink() {
+        assert_eq!(
+            hyperlink("main.sw", "file:///src/main.sw"),
+            "\x1b]8;;file:///src/main.sw\x1b\\main.sw\x1b]8;;\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_file_link() {
+        let path = Path::new("/project/src/my main.sw");
+        assert_eq!(
+            file_link(path, 12, 3, true),
+            "\x1b]8;;file:///project/src/my%20main.sw\x1b\\/project/src/my main.sw:12:3\x1b]8;;\x1b\\"
+        );
+        let plain = file_link(path, 12, 3, false);
+        assert_eq!(plain, "/project/src/my main.sw:12:3");
+        assert!(!plain.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_supports_hyperlinks() {
+        assert!(!supports_hyperlinks(env(&[])));
+        assert!(!supports_hyperlinks(env(&[("TERM", "xterm-256color")])));
+        assert!(supports_hyperlinks(env(&[("TERM_PROGRAM", "WezTerm")])));
+        assert!(supports_hyperlinks(env(&[("VTE_VERSION", "6003")])));
+        assert!(!supports_hyperlinks(env(&[("VTE_VERSION", "4205")])));
+        assert!(supports_hyperlinks(env(&[("FORCE_HYPERLINK", "1")])));
+        assert!(!supports_hyperlinks(env(&[
+            ("FORCE_HYPERLINK", "0"),
+            ("WT_SESSION", "1")
+        ])));
+    }
+}
@@ -0,0 +1,160 @@
+//! The JSON objects of the events framed as one array, see `LogFormat::JsonArray`.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::Metadata;
+use tracing_subscriber::fmt::writer::EitherWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// How far the array has been written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Not even the `[`, as no event has been written yet.
+    Empty,
+    /// The `[` and at least one event.
+    Open,
+    /// The `]` too, after which events are written as they are.
+    Closed,
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Writes the `]` that closes the array, see `JsonArrayWriter::closer`.
+pub(crate) type JsonArrayCloser = Box<dyn FnOnce() + Send + Sync>;
+
+/// Writes the JSON objects of the events written through the wrapped writer as the elements of one
+/// array, if `enabled`: `[` before the first, `,` before each other, and `]` when the closer of
+/// `closer` is called.
+///
+/// All events go to the destination of INFO events, as an array can't be split across streams.
+pub(crate) struct JsonArrayWriter<W> {
+    inner: Arc<W>,
+    // Held for as long as an event is written, so that the separators are in the order of the
+    // events they precede.
+    state: Option<Arc<Mutex<State>>>,
+}
+
+impl<W> JsonArrayWriter<W> {
+    pub(crate) fn new(inner: W, enabled: bool) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            state: enabled.then(|| Arc::new(Mutex::new(State::Empty))),
+        }
+    }
+}
+
+impl<W> JsonArrayWriter<W>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    /// The function that closes the array, a `[]` if no event was written, if `enabled`.
+    pub(crate) fn closer(&self) -> Option<JsonArrayCloser> {
+        let state = self.state.clone()?;
+        let inner = self.inner.clone();
+        Some(Box::new(move || {
+            let mut state = lock(&state);
+            let end: &[u8] = match *state {
+                State::Empty => b"[]\n",
+                State::Open => b"]\n",
+                State::Closed => return,
+            };
+            *state = State::Closed;
+            let mut writer = inner.make_writer();
+            // The closer runs when the guard is dropped, which has no caller to return an error to.
+            let _ = writer.write_all(end).and_then(|()| writer.flush());
+        }))
+    }
+}
+
+impl<'a, W: MakeWriter<'a> + 'a> MakeWriter<'a> for JsonArrayWriter<W> {
+    type Writer = EitherWriter<BoxedEvent<'a, io::Sink>, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        match &self.state {
+            Some(state) => EitherWriter::A(array_event(&*self.inner, state)),
+            None => EitherWriter::B(self.inner.make_writer()),
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        match &self.state {
+            Some(state) => EitherWriter::A(array_event(&*self.inner, state)),
+            None => EitherWriter::B(self.inner.make_writer_for(meta)),
+        }
+    }
+}
+
+/// A writer for one event that writes its JSON object out after its separator.
+///
+/// The writer of the destination is only made then, after the state is locked, so that the locks
+/// are always taken in the same order as by the closer.
+fn array_event<'a, W: MakeWriter<'a>>(
+    inner: &'a W,
+    state: &'a Mutex<State>,
+) -> BoxedEvent<'a, io::Sink> {
+    BufferedEvent::boxed(io::sink(), move |buffer, _| {
+        let mut state = lock(state);
+        let separator: &[u8] = match *state {
+            State::Empty => b"[",
+            State::Open => b",",
+            State::Closed => b"",
+        };
+        if *state == State::Empty {
+            *state = State::Open;
+        }
+        let mut writer = inner.make_writer();
+        writer.write_all(separator)?;
+        writer.write_all(buffer)?;
+        writer.flush()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::CapturingWriter;
+    use crate::{
+        build_subscriber, CustomWriter, LogFormat, TracingSubscriberOptions, TracingWriterMode,
+        ENV_LOCK,
+    };
+
+    fn capture(f: impl FnOnce()) -> String {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var("RUST_LOG");
+        let capture = CapturingWriter::new();
+        let custom = {
+            let capture = capture.clone();
+            CustomWriter::new(move || Box::new(capture.clone()))
+        };
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Custom(custom))
+            .format(LogFormat::JsonArray)
+            .display_time(false)
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        // The array is closed as the subscriber, and with it its guard, is dropped.
+        tracing::subscriber::with_default(subscriber, f);
+        capture.contents()
+    }
+
+    #[test]
+    fn test_json_array() {
+        let output = capture(|| {
+            tracing::info!(target: "forc", "Compiling");
+            tracing::error!(target: "forc", name = "my_contract", "Failed");
+            tracing::warn!(target: "forc", "Unused");
+        });
+        let events: Vec<serde_json::Value> = serde_json::from_str(&output).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["message"], "Compiling");
+        assert_eq!(events[0]["level"], "INFO");
+        assert_eq!(events[1]["message"], "Failed");
+        assert_eq!(events[1]["name"], "my_contract");
+        assert_eq!(events[2]["message"], "Unused");
+
+        let events: Vec<serde_json::Value> = serde_json::from_str(&capture(|| {})).unwrap();
+        assert!(events.is_empty());
+    }
+}
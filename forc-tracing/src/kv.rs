@@ -0,0 +1,189 @@
+//! Key-value fields for the messages of the print helpers, see `print_kv`.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use crate::{color, PRINT_TARGET};
+use anstyle::Style;
+use std::cell::RefCell;
+use std::io::Write;
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::writer::EitherWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+thread_local! {
+    // The fields of the event being logged by `print_kv` on this thread, for the writers to add to
+    // it, as the names of the fields of events must be known at compile time.
+    static PENDING: RefCell<Option<Vec<(String, String)>>> = const { RefCell::new(None) };
+}
+
+/// Log `message` at `level` with the key-value `fields`, like the `println_*` helpers but as a
+/// structured event.
+///
+/// The text formats show the fields as dimmed `key=value` pairs after the message, quoting the
+/// values that are empty or contain whitespace, and the JSON format writes them as string members
+/// of the object of the event. Fields shouldn't be named like the keys the JSON format writes
+/// itself, e.g. `message` or `level`.
+///
+/// ```
+/// use forc_tracing::print_kv;
+/// use tracing::Level;
+///
+/// print_kv(Level::INFO, "Deployed", &[("contract", "0x5fbd"), ("gas", "1201")]);
+/// // Deployed contract=0x5fbd gas=1201
+/// ```
+pub fn print_kv(level: Level, message: &str, fields: &[(&str, &str)]) {
+    let fields = fields
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    PENDING.with(|pending| *pending.borrow_mut() = Some(fields));
+    match level {
+        Level::ERROR => tracing::error!(target: PRINT_TARGET, "{message}"),
+        Level::WARN => tracing::warn!(target: PRINT_TARGET, "{message}"),
+        Level::INFO => tracing::info!(target: PRINT_TARGET, "{message}"),
+        Level::DEBUG => tracing::debug!(target: PRINT_TARGET, "{message}"),
+        Level::TRACE => tracing::trace!(target: PRINT_TARGET, "{message}"),
+    }
+    PENDING.with(|pending| *pending.borrow_mut() = None);
+}
+
+/// The fields as the text formats show them, e.g. ` contract=0x5fbd gas=1201`, dimmed if `ansi`
+/// is set.
+fn render_text(fields: &[(String, String)], ansi: bool) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| {
+            let quote = value.is_empty() || value.contains(char::is_whitespace);
+            let pair = match quote {
+                true => format!("{key}={value:?}"),
+                false => format!("{key}={value}"),
+            };
+            match ansi {
+                true => format!(" {}", color::paint(&pair, Style::new().dimmed())),
+                false => format!(" {pair}"),
+            }
+        })
+        .collect()
+}
+
+/// The fields as members of a JSON object, each followed by a comma.
+fn render_json(fields: &[(String, String)]) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| {
+            let key = serde_json::to_string(key).expect("strings serialize to JSON");
+            let value = serde_json::to_string(value).expect("strings serialize to JSON");
+            format!("{key}:{value},")
+        })
+        .collect()
+}
+
+/// Adds the fields of `print_kv` to the events written through the wrapped writer, after the
+/// message in the text formats and as members of the object in the JSON format.
+pub(crate) struct KeyValueWriter<W> {
+    inner: W,
+    ansi: bool,
+    json: bool,
+}
+
+impl<W> KeyValueWriter<W> {
+    pub(crate) fn new(inner: W, ansi: bool, json: bool) -> Self {
+        Self { inner, ansi, json }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for KeyValueWriter<W> {
+    type Writer = EitherWriter<BoxedEvent<'a, W::Writer>, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        EitherWriter::B(self.inner.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        let inner = self.inner.make_writer_for(meta);
+        if meta.target() != PRINT_TARGET {
+            return EitherWriter::B(inner);
+        }
+        let fields = PENDING.with(|pending| {
+            let pending = pending.borrow();
+            pending.as_ref().map(|fields| match self.json {
+                true => render_json(fields),
+                false => render_text(fields, self.ansi),
+            })
+        });
+        let Some(fields) = fields else {
+            return EitherWriter::B(inner);
+        };
+        // Written out with the fields, after the message or as the first members of the object.
+        let json = self.json;
+        EitherWriter::A(BufferedEvent::boxed(inner, move |buffer, inner| {
+            match (json, buffer.strip_prefix(b"{")) {
+                (true, Some(members)) => {
+                    write!(inner, "{{{fields}")?;
+                    inner.write_all(members)
+                }
+                _ => {
+                    let line = buffer.strip_suffix(b"\n").unwrap_or(buffer);
+                    inner.write_all(line)?;
+                    inner.write_all(fields.as_bytes())?;
+                    inner.write_all(&buffer[line.len()..])
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::with_captured_logs;
+    use crate::{LogFormat, TracingSubscriberOptions, ENV_LOCK};
+
+    fn deployed() {
+        print_kv(
+            Level::INFO,
+            "Deployed",
+            &[("contract", "0x5fbd"), ("salt", ""), ("network", "beta 4")],
+        );
+    }
+
+    #[test]
+    fn test_print_kv_text() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let output = with_captured_logs(TracingSubscriberOptions::default(), || {
+            deployed();
+            tracing::info!(target: "forc", "Finished");
+        });
+        assert_eq!(
+            output,
+            "Deployed contract=0x5fbd salt=\"\" network=\"beta 4\"\nFinished\n"
+        );
+
+        let ansi = TracingSubscriberOptions::builder().ansi(true).build();
+        let output = with_captured_logs(ansi, || {
+            print_kv(Level::WARN, "Unused", &[("name", "x")]);
+        });
+        assert_eq!(output, "Unused \x1b[2mname=x\x1b[0m\n");
+    }
+
+    #[test]
+    fn test_print_kv_json() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .build();
+        let output = with_captured_logs(options, deployed);
+        let event: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            event,
+            serde_json::json!({
+                "contract": "0x5fbd",
+                "salt": "",
+                "network": "beta 4",
+                "level": "INFO",
+                "target": "forc",
+                "message": "Deployed",
+            })
+        );
+    }
+}
@@ -1,136 +1,1716 @@
 //! Utility items shared between forc crates.
 
-use ansi_term::Colour;
+use std::io;
+use std::path::PathBuf;
 use std::str;
-use std::{env, io};
-use tracing::{Level, Metadata};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::{metadata::Kind, Level, Subscriber};
 use tracing_subscriber::{
-    filter::{EnvFilter, LevelFilter},
-    fmt::MakeWriter,
+    filter::{filter_fn, LevelFilter},
+    fmt::{
+        format::{DefaultFields, FormatEvent, FormatFields},
+        MakeWriter,
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::{SubscriberInitExt, TryInitError},
+    Layer, Registry,
 };
 
+mod align;
+mod backtrace;
+mod blank;
+mod buffered;
+mod build_summary;
+mod capture;
+mod color;
+mod config;
+mod counts;
+mod dedupe;
+mod defer;
+mod diagnostic;
+mod diff;
+mod fatal;
+mod filter;
+mod flush;
+mod group;
+mod hooks;
+mod hyperlink;
+mod json_array;
+mod kv;
+mod logfmt;
+mod macros;
+mod message_format;
+mod namespace;
+mod notice;
+#[cfg(feature = "opentelemetry")]
+mod otlp;
+mod panic;
+mod pid;
+mod plain;
+#[cfg(feature = "indicatif")]
+mod progress;
+mod report;
+mod ring;
+mod rotation;
+#[cfg(feature = "net")]
+mod socket;
+mod spinner;
+mod status;
+mod style;
+mod symbols;
+#[cfg(feature = "syslog")]
+mod system_log;
+pub mod testing;
+mod theme;
+mod timer;
+mod timings;
+mod transcript;
+mod tree;
+mod truncate;
+mod wrap;
+mod writer;
+
+pub use align::{pad_visible, truncate_middle};
+use backtrace::BacktraceWriter;
+use blank::BlankLineWriter;
+pub use build_summary::{write_summary_json, BuildSummary, PhaseSummary};
+pub use capture::{with_writer_override, WriterOverride};
+pub use color::{detect_capabilities, global_color_enabled, set_global_color, OutputCapabilities};
+pub use counts::{log_counts, print_summary, reset_log_counts, LogCounts};
+use counts::{CountingLayer, COUNTERS};
+use dedupe::dedupe;
+pub use defer::flush_deferred_warnings;
+use defer::{DeferLayer, DEFERRED};
+use diagnostic::JsonDiagnosticWriter;
+pub use diagnostic::{
+    emit_diagnostic, Diagnostic, Label, LineCol, Severity, SourceSpan, DIAGNOSTICS_TARGET,
+    DIAGNOSTIC_SCHEMA_VERSION,
+};
+pub use diff::print_diff;
+pub use fatal::{format_error_chain, report_fatal, report_fatal_with_code};
+use filter::own_targets_filter;
+use filter::reloadable_env_filter;
+pub use filter::{
+    is_enabled, level_filter_from_verbosity, resolve_env_filter, resolve_filter_directives,
+    with_min_level, ReloadError, ReloadHandle,
+};
+pub use flush::FlushPolicy;
+pub use group::log_group;
+pub use hooks::{on_event, LogRecord};
+use hooks::{HookLayer, CALLBACKS};
+pub use hyperlink::{hyperlink, print_file_link};
+use json_array::JsonArrayWriter;
+pub use kv::print_kv;
+use kv::KeyValueWriter;
+use logfmt::Logfmt;
+pub use message_format::{MessageFormat, ParseMessageFormatError};
+pub use namespace::with_log_namespace;
+use namespace::{JsonNamespaceWriter, Namespaced};
+pub use notice::{print_notice, NoticeStyle};
+use pid::{JsonPidWriter, ProcessId};
+use plain::PlainWriter;
+#[cfg(feature = "indicatif")]
+pub use progress::{clear_progress, set_progress};
+pub use report::{
+    report_progress, time_phase, LoggingReporter, NoopReporter, PhaseTimer, ProgressReporter,
+    PROGRESS_TARGET, TIMING_TARGET,
+};
+pub use ring::{drain_ring_buffer, install_ring_buffer_panic_hook};
+use ring::{ring_buffer_layer, RING_BUFFER};
+use rotation::Rolling;
+pub use rotation::Rotation;
+#[cfg(feature = "net")]
+pub use socket::SocketTarget;
+pub use spinner::Spinner;
+pub use status::{print_action, CommandTimer, StatusPrinter, ACTION_WIDTH, STANDARD_ACTION_VERBS};
+pub use style::{bold, dim, styled, Color, Style};
+use symbols::{LevelSymbols, Symbols};
+pub use theme::{set_theme, ColorTheme};
+pub use timer::TimeFormat;
+use timer::Timer;
+pub use timings::print_timings_report;
+use timings::{TimingLayer, TIMINGS};
+use transcript::transcript_layer;
+pub use tree::{print_tree, TreeNode};
+use truncate::{JsonTruncateWriter, Truncation};
+use wrap::{Layout, WrapFields, WrapFormat, WrapWriter};
+pub use writer::{
+    channel_writer, flush, set_writer_mode, CustomWriter, LineEnding, SplitFiles, TracingGuard,
+    TracingWriterMode,
+};
+use writer::{DestinationHandle, StdioTracingWriter, LEVELS};
+
 pub fn println_red(txt: &str) {
-    println_std_out(txt, Colour::Red);
+    println_styled(txt, theme::theme().error.on_default(), false);
 }
 
 pub fn println_green(txt: &str) {
-    println_std_out(txt, Colour::Green);
+    println_styled(txt, theme::theme().success.on_default(), false);
+}
+
+pub fn println_blue(txt: &str) {
+    println_styled(txt, theme::theme().info.on_default(), false);
+}
+
+pub fn println_cyan(txt: &str) {
+    println_colored(txt, Color::Cyan, false);
+}
+
+pub fn println_magenta(txt: &str) {
+    println_colored(txt, Color::Magenta, false);
+}
+
+pub fn println_bold_white(txt: &str) {
+    println_styled(txt, Color::White.on_default().bold(), false);
 }
 
 pub fn println_yellow_err(txt: &str) {
-    println_std_err(txt, Colour::Yellow);
+    println_styled(txt, theme::theme().warn.on_default(), true);
 }
 
 pub fn println_red_err(txt: &str) {
-    println_std_err(txt, Colour::Red);
+    println_styled(txt, theme::theme().error.on_default(), true);
 }
 
-fn println_std_out(txt: &str, color: Colour) {
-    tracing::info!("{}", color.paint(txt));
+/// Print `txt` in `color`, logging it at ERROR if `to_stderr` is set and at INFO otherwise, so that
+/// it is routed to stderr or stdout respectively.
+///
+/// The color is omitted if the stream the line is routed to doesn't show color.
+pub fn println_colored(txt: &str, color: Color, to_stderr: bool) {
+    println_styled(txt, color.on_default(), to_stderr);
 }
 
-fn println_std_err(txt: &str, color: Colour) {
-    tracing::error!("{}", color.paint(txt));
+fn println_styled(txt: &str, style: Style, to_stderr: bool) {
+    match to_stderr {
+        true => log_styled(Level::ERROR, txt, style),
+        false => log_styled(Level::INFO, txt, style),
+    }
 }
 
-const LOG_FILTER: &str = "RUST_LOG";
+/// The target of the events logged by the `println_*` helpers and the colored print macros.
+///
+/// It is one of forc's own targets, so that the level, verbosity, quiet and silent options apply.
+pub(crate) const PRINT_TARGET: &str = "forc";
 
-// This allows us to write ERROR and WARN level logs to stderr and everything else to stdout.
-// https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/trait.MakeWriter.html
-struct StdioTracingWriter {
-    writer_mode: TracingWriterMode,
+/// Log `txt` painted with `style` at `level`, omitting the color if the stream that `level` is
+/// routed to doesn't show it.
+fn log_styled(level: Level, txt: &str, style: Style) {
+    let Some(txt) = paint_enabled(level, txt, style) else {
+        return;
+    };
+    match level {
+        Level::ERROR => tracing::error!(target: PRINT_TARGET, "{txt}"),
+        Level::WARN => tracing::warn!(target: PRINT_TARGET, "{txt}"),
+        Level::INFO => tracing::info!(target: PRINT_TARGET, "{txt}"),
+        Level::DEBUG => tracing::debug!(target: PRINT_TARGET, "{txt}"),
+        Level::TRACE => tracing::trace!(target: PRINT_TARGET, "{txt}"),
+    }
 }
 
-impl<'a> MakeWriter<'a> for StdioTracingWriter {
-    type Writer = Box<dyn io::Write>;
+/// `txt` painted for `log_styled`, or `None` without painting it if the event would be filtered
+/// out anyway.
+///
+/// The check is for an event rather than for any kind of callsite, as spans are hidden from the
+/// formatting layer unless `display_span_context` is set.
+fn paint_enabled(level: Level, txt: &str, style: Style) -> Option<String> {
+    let enabled = match level {
+        Level::ERROR => tracing::enabled!(kind: Kind::EVENT, target: PRINT_TARGET, Level::ERROR),
+        Level::WARN => tracing::enabled!(kind: Kind::EVENT, target: PRINT_TARGET, Level::WARN),
+        Level::INFO => tracing::enabled!(kind: Kind::EVENT, target: PRINT_TARGET, Level::INFO),
+        Level::DEBUG => tracing::enabled!(kind: Kind::EVENT, target: PRINT_TARGET, Level::DEBUG),
+        Level::TRACE => tracing::enabled!(kind: Kind::EVENT, target: PRINT_TARGET, Level::TRACE),
+    };
+    enabled.then(|| paint(txt, style, color::level_color(&level)))
+}
 
-    fn make_writer(&'a self) -> Self::Writer {
-        if self.writer_mode == TracingWriterMode::Stderr {
-            Box::new(io::stderr())
-        } else {
-            // We must have an implementation of `make_writer` that makes
-            // a "default" writer without any configuring metadata. Let's
-            // just return stdout in that case.
-            Box::new(io::stdout())
-        }
-    }
+// Used by the exported macros, which can't name the items of this crate's dependencies.
+#[doc(hidden)]
+pub mod __private {
+    pub use tracing::Level;
 
-    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
-        // Here's where we can implement our special behavior. We'll
-        // check if the metadata's verbosity level is WARN or ERROR,
-        // and return stderr in that case.
-        if self.writer_mode == TracingWriterMode::Stderr
-            || (self.writer_mode == TracingWriterMode::Stdio && meta.level() <= &Level::WARN)
-        {
-            return Box::new(io::stderr());
-        }
+    pub fn log_colored(level: Level, txt: &str, color: crate::Color) {
+        crate::log_styled(level, txt, color.on_default())
+    }
+}
 
-        // Otherwise, we'll return stdout.
-        Box::new(io::stdout())
+/// Paint `txt` with `style` only if `ansi` is set.
+fn paint(txt: &str, style: impl Into<anstyle::Style>, ansi: bool) -> String {
+    if ansi {
+        color::paint(txt, style)
+    } else {
+        txt.to_string()
     }
 }
 
-#[derive(PartialEq, Eq)]
-pub enum TracingWriterMode {
-    /// Write ERROR and WARN to stderr and everything else to stdout.
-    Stdio,
-    /// Write everything to stdout.
-    Stdout,
-    /// Write everything to stderr.
-    Stderr,
+/// The format events are written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable text, one line per event.
+    #[default]
+    Pretty,
+    /// Human-readable text with span context abbreviated.
+    Compact,
+    /// One JSON object per line, with `timestamp`, `level`, `target` and `message` fields.
+    Json,
+    /// The JSON objects of the `Json` format as the elements of one array, e.g. for tools that
+    /// read the whole output as one JSON document. The `[` is written with the first event and
+    /// the `]` when the `TracingGuard` is dropped, so use `init_tracing_subscriber_with_guard`.
+    ///
+    /// If the process exits without dropping the guard, e.g. when killed, the array is left open
+    /// and the output isn't valid JSON, so prefer `Json` where the output must be robust. All
+    /// events are written to the destination of INFO events, as the array can't be split across
+    /// streams.
+    JsonArray,
+    /// One line of `key=value` pairs per event, with `ts`, `level`, `target` and `msg` keys
+    /// followed by the fields of the event, e.g. `level=info target=forc msg="Compiling std"`.
+    /// Values are quoted if they contain whitespace, `=` or `"`. The timestamp and the process id
+    /// are included as in the JSON format, and the other display options don't apply.
+    Logfmt,
+}
+
+/// How the level of each event is shown in the text formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LevelStyle {
+    /// The level isn't shown.
+    None,
+    /// The name of the level, e.g. ` WARN`.
+    Full,
+    /// The initial of the level in brackets, colored by the level, e.g. `[W]`.
+    Short,
 }
 
-#[derive(Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct TracingSubscriberOptions {
     pub verbosity: Option<u8>,
     pub silent: Option<bool>,
+    /// Whether to log errors only, e.g. for `--quiet`. Takes precedence over `log_level` and
+    /// `verbosity`, but not over `RUST_LOG`, so that a quiet command can still be debugged. Silent
+    /// mode still suppresses the errors too.
+    pub quiet: Option<bool>,
     pub log_level: Option<LevelFilter>,
     pub writer_mode: Option<TracingWriterMode>,
+    /// Files to append the events to besides the destinations of `writer_mode`, one with every
+    /// event and one with only the WARN and ERROR events, e.g. for triaging CI failures. Both are
+    /// written without ANSI color, and missing parent directories are created.
+    pub split_files: Option<SplitFiles>,
+    /// A file to append every event to without ANSI color, at any level and of any target, e.g. to
+    /// keep a complete record of an interactive session while the terminal stays in color and shows
+    /// INFO only. Missing parent directories are created, and the file isn't rotated.
+    pub transcript_file: Option<PathBuf>,
+    /// When to rotate the log files of `writer_mode` and `split_files`, so that they don't grow
+    /// without bound, e.g. in watch mode. A log file keeps its path, and is renamed to make way
+    /// for a new one when it is rotated. Off by default.
+    pub rotation: Option<Rotation>,
+    /// How many rotated files to keep of each log file, deleting the oldest beyond that. All are
+    /// kept by default.
+    pub max_log_files: Option<usize>,
+    /// Whether to gzip the log files once rotated, to e.g. `forc.log.1.gz`, on a background
+    /// thread. The compressed files count towards `max_log_files`. Off by default.
+    pub compress_rotated: Option<bool>,
+    /// The line ending of the events in the log files of `writer_mode`, `split_files` and
+    /// `transcript_file`, e.g. `LineEnding::CrLf` for logs shared with Windows users. The terminal
+    /// always gets `\n`. `LineEnding::Native` by default, i.e. `\r\n` on Windows only.
+    pub file_line_ending: Option<LineEnding>,
+    /// Whether to emit ANSI color codes. `None` enables color only for the streams that are a
+    /// terminal, taking into account which stream each level is routed to by `writer_mode`.
+    pub ansi: Option<bool>,
+    /// Whether to write the bare messages only, e.g. for scripts that parse the output: without
+    /// color, timestamps, levels or any of the other `display_*` decoration, without wrapping or
+    /// indentation, and with the trailing whitespace of each line trimmed. Overrides the decoration
+    /// options set individually. Off by default.
+    pub plain: Option<bool>,
+    /// The format events are written in, `Pretty` by default.
+    pub format: Option<LogFormat>,
+    /// The format selected like cargo's `--message-format`, for tooling that reads cargo's output.
+    /// The JSON formats take precedence over `format`, and add the `rendered` text to diagnostics
+    /// the way each says, see `MessageFormat`. `Human` keeps the text format of `format`.
+    pub message_format: Option<MessageFormat>,
+    /// Whether to show the level of each event. Off by default for the text formats and on for the
+    /// JSON format.
+    pub display_level: Option<bool>,
+    /// How to show the level of each event in the text formats, taking precedence over
+    /// `display_level`. The JSON format includes the level unless this is `LevelStyle::None`.
+    /// Initialization fails if this is set along with `level_symbols`.
+    pub level_style: Option<LevelStyle>,
+    /// Whether to show the target of each event, usually the module it was logged from. Off by
+    /// default for the text formats and on for the JSON format.
+    pub display_target: Option<bool>,
+    /// Whether to show the source file each event was logged from, off by default.
+    pub display_file: Option<bool>,
+    /// Whether to show the source line each event was logged from, off by default.
+    pub display_line_number: Option<bool>,
+    /// Whether to show the id of the thread each event was logged from, off by default.
+    pub display_thread_ids: Option<bool>,
+    /// Whether to show the name of the thread each event was logged from, off by default.
+    pub display_thread_names: Option<bool>,
+    /// Whether to show the id of the process, as a `[pid]` prefix in the text formats and a `pid`
+    /// field in the JSON format, e.g. to tell apart the output of parallel invocations in CI. Off
+    /// by default.
+    pub display_pid: Option<bool>,
+    /// Whether to show the spans each event was logged within, from the outermost, e.g.
+    /// `compile{pkg=foo}:typecheck:` in the text formats and `span` and `spans` fields in the JSON
+    /// format. Off by default.
+    pub display_span_context: Option<bool>,
+    /// Whether to prefix events with a timestamp. Off by default for the text formats unless a
+    /// `time_format` is given, and on for the JSON format.
+    pub display_time: Option<bool>,
+    /// The format of the timestamp, an RFC 3339 UTC time by default.
+    pub time_format: Option<TimeFormat>,
+    /// Whether to write log output on a dedicated background thread per destination, so that
+    /// logging a large volume of events doesn't slow down the caller. Use
+    /// `init_tracing_subscriber_with_guard` and keep the guard alive so that pending events are
+    /// written out at exit.
+    pub non_blocking: Option<bool>,
+    /// When the output for stdout and stderr is written out to them, e.g. `FlushPolicy::Interval`
+    /// to keep a slow terminal or SSH session from stalling the build. Streams moved onto a
+    /// background thread by `non_blocking` aren't affected. `FlushPolicy::Immediate` by default.
+    pub flush_policy: Option<FlushPolicy>,
+    /// Drop events that repeat the level and message of an event seen less than this long ago.
+    /// Once the window of a message closes, the number of times it was repeated is logged in its
+    /// place. ERROR events are never dropped. The repetitions of windows still open at exit are
+    /// logged as the `TracingGuard` is dropped, or by `flush` if the guard isn't kept, e.g. with
+    /// `init_tracing_subscriber`. Off by default.
+    pub dedupe_window: Option<Duration>,
+    /// Whether to hold the WARN events back instead of writing them as they are logged, until
+    /// `flush_deferred_warnings` writes them grouped and sorted, e.g. at the end of a large build
+    /// where they would be easy to miss. They are still counted when they are logged. ERROR events
+    /// are written immediately. Off by default.
+    pub defer_warnings: Option<bool>,
+    /// Whether to log panics as ERROR events, so that they end up wherever the other events are
+    /// written to, including the ring buffer. The backtrace is included if enabled by
+    /// `RUST_BACKTRACE`. The previous panic hook still runs afterwards, unless the event was
+    /// written to stderr, where the default hook would print the panic a second time. Off by
+    /// default.
+    pub capture_panics: Option<bool>,
+    /// Whether to attach a backtrace of where ERROR events were logged from, if enabled by
+    /// `RUST_BACKTRACE`, e.g. for the bug report of an internal error. It is written below the
+    /// event in the text formats and as the `backtrace` field in the JSON format. Off by default,
+    /// as capturing backtraces is slow.
+    pub error_backtraces: Option<bool>,
+    /// Retain the most recent events of this many in memory, to be taken with
+    /// `drain_ring_buffer`, e.g. for a crash report. These include the events of forc's own
+    /// targets and `extra_targets` at all levels, even if they aren't shown. Off by default.
+    pub ring_buffer_capacity: Option<usize>,
+    /// Whether to record the time spent in each span, by name and across threads, for
+    /// `print_timings_report` to print, e.g. at the end of a build. These include the spans of
+    /// forc's own targets and `extra_targets` at all levels. Off by default.
+    pub report_timings: Option<bool>,
+    /// The OTLP/HTTP endpoint of an OpenTelemetry collector to export spans to as they close, e.g.
+    /// `http://localhost:4318/v1/traces`, to profile the phases of a build. Like the ring buffer,
+    /// this includes the spans of forc's own targets and `extra_targets` at all levels. Only used
+    /// with the `opentelemetry` feature, and off by default.
+    pub otlp_endpoint: Option<String>,
+    /// Whether to mirror the log output to the local syslog, e.g. for journald on a server, tagged
+    /// `forc` and at the severity of each level: `err` for ERROR, `warning` for WARN, `info` for
+    /// INFO and `debug` for DEBUG and TRACE. The same events are sent as are shown. Only used with
+    /// the `syslog` feature, and off by default.
+    pub syslog: Option<bool>,
+    /// The width to soft-wrap long messages to at word boundaries in the pretty format, with the
+    /// lines they are wrapped onto aligned under the start of the message. `None` uses the width
+    /// of the terminal, or 80 columns if it can't be detected. Messages are never wrapped unless
+    /// all of the output goes to a terminal, so that piped output stays one line per event.
+    pub wrap_width: Option<usize>,
+    /// Whether to indent the lines after the first of a multi-line message in the text formats,
+    /// e.g. of a diagnostic with a source snippet, to the column the message starts at, so that it
+    /// is clear where one event ends and the next begins. Off by default.
+    pub indent_continuation: Option<bool>,
+    /// Whether to write only the first of consecutive events whose output is blank, e.g. the empty
+    /// lines logged as spacers, so that the events deduplicated or filtered out between them don't
+    /// leave runs of blank lines. Events with any visible text are always written. Off by default.
+    pub collapse_blank_lines: Option<bool>,
+    /// The length in bytes to truncate longer messages to, e.g. of events logging a serialized AST,
+    /// marking where they were cut with `… (truncated, N bytes total)`. Messages are only cut
+    /// between characters, and in the JSON format only the `message` field is truncated. Off by
+    /// default.
+    pub max_message_len: Option<usize>,
+    /// Whether to leave the messages of ERROR events whole regardless of `max_message_len`, off
+    /// by default.
+    pub never_truncate_errors: Option<bool>,
+    /// Targets besides forc's own `forc`, `sway` and `test` that the level, verbosity and silent
+    /// options apply to, e.g. the crates of a plugin. Other targets are logged at INFO at most.
+    pub extra_targets: Vec<String>,
+    /// Levels for individual targets, e.g. `("sway_core", LevelFilter::TRACE)`, that take
+    /// precedence over the level of the target they are a part of, whether that is set by
+    /// `RUST_LOG` or by the level, verbosity and silent options. A later level for the same target
+    /// replaces an earlier one. Silent mode still suppresses everything.
+    ///
+    /// If a target isn't valid in a filter directive, `try_init_tracing_subscriber` and
+    /// `build_subscriber` return `TracingInitError::InvalidFilter`, and `init_tracing_subscriber`
+    /// panics.
+    pub target_levels: Vec<(String, LevelFilter)>,
+    /// Targets to log nothing of, e.g. a noisy dependency like `salsa`, whatever `RUST_LOG`, the
+    /// level, verbosity and `target_levels` say. A directive for a longer target, e.g.
+    /// `salsa::runtime=debug` in `RUST_LOG`, still applies to that part of it.
+    ///
+    /// If a target isn't valid in a filter directive, `try_init_tracing_subscriber` and
+    /// `build_subscriber` return `TracingInitError::InvalidFilter`, and `init_tracing_subscriber`
+    /// panics.
+    pub suppress_targets: Vec<String>,
+    /// Whether to prefix events in the text formats with a symbol colored by their level, e.g. a
+    /// yellow `⚠` for WARN and a red `✗` for ERROR, off by default.
+    pub level_symbols: Option<bool>,
+    /// Whether to show the ASCII equivalents of the `level_symbols`, e.g. `!` and `x`. `None` does
+    /// so unless the locale uses UTF-8, or on Windows, the terminal is known to render them.
+    pub no_unicode: Option<bool>,
+}
+
+impl TracingSubscriberOptions {
+    /// Start building options with chainable setters, as an alternative to a struct literal.
+    pub fn builder() -> TracingSubscriberOptionsBuilder {
+        TracingSubscriberOptionsBuilder::default()
+    }
+}
+
+/// Builds `TracingSubscriberOptions`, see the fields of the same name for what each setter does.
+#[derive(Clone, Debug, Default)]
+pub struct TracingSubscriberOptionsBuilder {
+    options: TracingSubscriberOptions,
+}
+
+impl TracingSubscriberOptionsBuilder {
+    pub fn verbosity(mut self, verbosity: u8) -> Self {
+        self.options.verbosity = Some(verbosity);
+        self
+    }
+
+    pub fn silent(mut self, silent: bool) -> Self {
+        self.options.silent = Some(silent);
+        self
+    }
+
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.options.quiet = Some(quiet);
+        self
+    }
+
+    pub fn log_level(mut self, log_level: LevelFilter) -> Self {
+        self.options.log_level = Some(log_level);
+        self
+    }
+
+    pub fn writer_mode(mut self, writer_mode: TracingWriterMode) -> Self {
+        self.options.writer_mode = Some(writer_mode);
+        self
+    }
+
+    pub fn split_files(mut self, split_files: SplitFiles) -> Self {
+        self.options.split_files = Some(split_files);
+        self
+    }
+
+    pub fn transcript_file(mut self, transcript_file: PathBuf) -> Self {
+        self.options.transcript_file = Some(transcript_file);
+        self
+    }
+
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.options.rotation = Some(rotation);
+        self
+    }
+
+    pub fn file_line_ending(mut self, file_line_ending: LineEnding) -> Self {
+        self.options.file_line_ending = Some(file_line_ending);
+        self
+    }
+
+    pub fn max_log_files(mut self, max_log_files: usize) -> Self {
+        self.options.max_log_files = Some(max_log_files);
+        self
+    }
+
+    pub fn compress_rotated(mut self, compress_rotated: bool) -> Self {
+        self.options.compress_rotated = Some(compress_rotated);
+        self
+    }
+
+    pub fn ansi(mut self, ansi: bool) -> Self {
+        self.options.ansi = Some(ansi);
+        self
+    }
+
+    pub fn plain(mut self, plain: bool) -> Self {
+        self.options.plain = Some(plain);
+        self
+    }
+
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.options.format = Some(format);
+        self
+    }
+
+    pub fn message_format(mut self, message_format: MessageFormat) -> Self {
+        self.options.message_format = Some(message_format);
+        self
+    }
+
+    pub fn display_level(mut self, display_level: bool) -> Self {
+        self.options.display_level = Some(display_level);
+        self
+    }
+
+    pub fn level_style(mut self, level_style: LevelStyle) -> Self {
+        self.options.level_style = Some(level_style);
+        self
+    }
+
+    pub fn display_target(mut self, display_target: bool) -> Self {
+        self.options.display_target = Some(display_target);
+        self
+    }
+
+    pub fn display_file(mut self, display_file: bool) -> Self {
+        self.options.display_file = Some(display_file);
+        self
+    }
+
+    pub fn display_line_number(mut self, display_line_number: bool) -> Self {
+        self.options.display_line_number = Some(display_line_number);
+        self
+    }
+
+    pub fn display_thread_ids(mut self, display_thread_ids: bool) -> Self {
+        self.options.display_thread_ids = Some(display_thread_ids);
+        self
+    }
+
+    pub fn display_thread_names(mut self, display_thread_names: bool) -> Self {
+        self.options.display_thread_names = Some(display_thread_names);
+        self
+    }
+
+    pub fn display_pid(mut self, display_pid: bool) -> Self {
+        self.options.display_pid = Some(display_pid);
+        self
+    }
+
+    pub fn display_span_context(mut self, display_span_context: bool) -> Self {
+        self.options.display_span_context = Some(display_span_context);
+        self
+    }
+
+    pub fn display_time(mut self, display_time: bool) -> Self {
+        self.options.display_time = Some(display_time);
+        self
+    }
+
+    pub fn time_format(mut self, time_format: TimeFormat) -> Self {
+        self.options.time_format = Some(time_format);
+        self
+    }
+
+    pub fn non_blocking(mut self, non_blocking: bool) -> Self {
+        self.options.non_blocking = Some(non_blocking);
+        self
+    }
+
+    pub fn flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.options.flush_policy = Some(flush_policy);
+        self
+    }
+
+    pub fn dedupe_window(mut self, dedupe_window: Duration) -> Self {
+        self.options.dedupe_window = Some(dedupe_window);
+        self
+    }
+
+    pub fn defer_warnings(mut self, defer_warnings: bool) -> Self {
+        self.options.defer_warnings = Some(defer_warnings);
+        self
+    }
+
+    pub fn report_timings(mut self, report_timings: bool) -> Self {
+        self.options.report_timings = Some(report_timings);
+        self
+    }
+
+    pub fn capture_panics(mut self, capture_panics: bool) -> Self {
+        self.options.capture_panics = Some(capture_panics);
+        self
+    }
+
+    pub fn error_backtraces(mut self, error_backtraces: bool) -> Self {
+        self.options.error_backtraces = Some(error_backtraces);
+        self
+    }
+
+    pub fn ring_buffer_capacity(mut self, ring_buffer_capacity: usize) -> Self {
+        self.options.ring_buffer_capacity = Some(ring_buffer_capacity);
+        self
+    }
+
+    pub fn otlp_endpoint(mut self, otlp_endpoint: impl Into<String>) -> Self {
+        self.options.otlp_endpoint = Some(otlp_endpoint.into());
+        self
+    }
+
+    pub fn syslog(mut self, syslog: bool) -> Self {
+        self.options.syslog = Some(syslog);
+        self
+    }
+
+    pub fn wrap_width(mut self, wrap_width: usize) -> Self {
+        self.options.wrap_width = Some(wrap_width);
+        self
+    }
+
+    pub fn max_message_len(mut self, max_message_len: usize) -> Self {
+        self.options.max_message_len = Some(max_message_len);
+        self
+    }
+
+    pub fn never_truncate_errors(mut self, never_truncate_errors: bool) -> Self {
+        self.options.never_truncate_errors = Some(never_truncate_errors);
+        self
+    }
+
+    pub fn indent_continuation(mut self, indent_continuation: bool) -> Self {
+        self.options.indent_continuation = Some(indent_continuation);
+        self
+    }
+
+    pub fn collapse_blank_lines(mut self, collapse_blank_lines: bool) -> Self {
+        self.options.collapse_blank_lines = Some(collapse_blank_lines);
+        self
+    }
+
+    pub fn level_symbols(mut self, level_symbols: bool) -> Self {
+        self.options.level_symbols = Some(level_symbols);
+        self
+    }
+
+    pub fn no_unicode(mut self, no_unicode: bool) -> Self {
+        self.options.no_unicode = Some(no_unicode);
+        self
+    }
+
+    /// Add `target` to the targets the level, verbosity and silent options apply to.
+    pub fn extra_target(mut self, target: impl Into<String>) -> Self {
+        self.options.extra_targets.push(target.into());
+        self
+    }
+
+    /// Log `target` at `level`, see `TracingSubscriberOptions::target_levels`.
+    pub fn target_level(mut self, target: impl Into<String>, level: LevelFilter) -> Self {
+        self.options.target_levels.push((target.into(), level));
+        self
+    }
+
+    /// Log nothing of `target`, see `TracingSubscriberOptions::suppress_targets`.
+    pub fn suppress_target(mut self, target: impl Into<String>) -> Self {
+        self.options.suppress_targets.push(target.into());
+        self
+    }
+
+    pub fn build(self) -> TracingSubscriberOptions {
+        self.options
+    }
 }
 
 /// A subscriber built from default `tracing_subscriber::fmt::SubscriberBuilder` such that it would match directly using `println!` throughout the repo.
 ///
 /// `RUST_LOG` environment variable can be used to set different minimum level for the subscriber, default is `INFO`.
+/// The `log_level`, `verbosity` and `silent` options override it, applying to forc's own targets
+/// and `extra_targets` while other crates are logged at `INFO` at most.
+///
+/// A `verbosity` of 1 (`-v`) logs at `DEBUG` and 2 (`-vv`) at `TRACE`. From 3 (`-vvv`) on, other
+/// crates are logged at `TRACE` too.
+///
+/// Unless `ansi` is set explicitly, color is only emitted on streams that are a terminal, so
+/// redirected output stays free of escape codes. The `NO_COLOR`, `CLICOLOR` and `CLICOLOR_FORCE`
+/// conventions and `env_logger`'s `RUST_LOG_STYLE` are honored too: `CLICOLOR_FORCE` beats an
+/// explicit `ansi` option, which beats `RUST_LOG_STYLE=always` or `never`, which beat `NO_COLOR`,
+/// `CLICOLOR=0` and `TERM=dumb`, which beat terminal detection. The `println_*` helpers follow the same
+/// decision for the stream their level is written to, and `set_global_color` turns color off and
+/// on again for both at runtime. Their colors can be adjusted with `FORC_LOG_COLORS`, see
+/// `set_theme`.
+///
+/// Panics if a global default subscriber has already been set or the options are invalid, e.g. a
+/// `target_levels` directive, see `try_init_tracing_subscriber` for a fallible alternative.
 pub fn init_tracing_subscriber(options: TracingSubscriberOptions) {
-    let env_filter = match env::var_os(LOG_FILTER) {
-        Some(_) => EnvFilter::try_from_default_env().expect("Invalid `RUST_LOG` provided"),
-        None => EnvFilter::new("info"),
+    // Forgetting the guard keeps the background writers of `non_blocking` running for the rest of
+    // the program, at the cost of not waiting for them to finish writing at exit.
+    init_tracing_subscriber_with_guard(options).forget();
+}
+
+/// Like `init_tracing_subscriber`, but returns a guard that flushes the log output when dropped.
+///
+/// Keep the guard alive until the program exits, e.g. by binding it in `main`, so that no logs
+/// are lost when calling `std::process::exit`.
+///
+/// Panics if a global default subscriber has already been set or the options are invalid, e.g. a
+/// `target_levels` directive, see `try_init_tracing_subscriber` for a fallible alternative.
+pub fn init_tracing_subscriber_with_guard(options: TracingSubscriberOptions) -> TracingGuard {
+    try_init_tracing_subscriber(options).expect("failed to initialize the tracing subscriber")
+}
+
+/// Like `init_tracing_subscriber`, but only installs the subscriber if no global default subscriber
+/// has been set yet, e.g. by an embedder. Returns whether it was installed.
+///
+/// Still panics if the options are invalid, e.g. if a log file can't be opened, see
+/// `try_init_tracing_subscriber` for a fallible alternative.
+///
+/// ```
+/// use forc_tracing::{init_tracing_subscriber_once, is_initialized, TracingSubscriberOptions};
+///
+/// assert!(init_tracing_subscriber_once(TracingSubscriberOptions::default()));
+/// assert!(!init_tracing_subscriber_once(TracingSubscriberOptions::default()));
+/// assert!(is_initialized());
+/// ```
+pub fn init_tracing_subscriber_once(options: TracingSubscriberOptions) -> bool {
+    if tracing::dispatcher::has_been_set() {
+        return false;
+    }
+    match install(options, Vec::new()) {
+        Ok((guard, _)) => {
+            guard.forget();
+            true
+        }
+        // Another thread set one in the meantime.
+        Err(TracingInitError::SetGlobalDefault(_)) => false,
+        Err(err) => panic!("failed to initialize the tracing subscriber: {err:?}"),
+    }
+}
+
+// Whether `install` has set forc's subscriber as the global default.
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Whether forc's subscriber has been installed as the global default, by any of the
+/// `init_tracing_subscriber` functions. A subscriber set by other means doesn't count.
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::Relaxed)
+}
+
+/// The error returned when the tracing subscriber could not be installed.
+#[derive(Debug, thiserror::Error)]
+pub enum TracingInitError {
+    /// A global default subscriber has already been set.
+    #[error("failed to set the global default subscriber: {0}")]
+    SetGlobalDefault(#[from] TryInitError),
+    /// The log file of `TracingWriterMode::File`, `split_files` or `transcript_file` could not be
+    /// opened.
+    #[error("failed to open log file `{}`: {source}", path.display())]
+    OpenLogFile { path: PathBuf, source: io::Error },
+    /// The pattern of `TimeFormat::Custom` is invalid.
+    #[error("invalid time format `{0}`")]
+    InvalidTimeFormat(String),
+    /// The filter directives resolved from `RUST_LOG`, `target_levels` and `suppress_targets` are
+    /// invalid, e.g. as a target contains `[` or `=`.
+    #[error("invalid `RUST_LOG`, `target_levels` or `suppress_targets`: {0}")]
+    InvalidFilter(String),
+    /// `set_writer_mode` was called while forc's subscriber wasn't installed as the global default.
+    #[error("no subscriber has been installed by `init_tracing_subscriber`")]
+    NotInstalled,
+    /// Both `level_style` and `level_symbols` were set, which both prefix events with their level.
+    #[error("`level_style` and `level_symbols` can't be set together")]
+    ConflictingLevelOptions,
+    /// The exporter for `otlp_endpoint` could not be set up, e.g. as the endpoint isn't a URL.
+    #[cfg(feature = "opentelemetry")]
+    #[error("failed to set up the OTLP exporter: {0}")]
+    Otlp(#[from] opentelemetry::trace::TraceError),
+    /// The local syslog for the `syslog` option could not be connected to.
+    #[cfg(feature = "syslog")]
+    #[error("failed to connect to syslog: {0}")]
+    Syslog(#[from] syslog::Error),
+}
+
+/// Like `init_tracing_subscriber_with_guard`, but returns an error instead of panicking if the
+/// subscriber could not be installed.
+pub fn try_init_tracing_subscriber(
+    options: TracingSubscriberOptions,
+) -> Result<TracingGuard, TracingInitError> {
+    install(options, Vec::new()).map(|(guard, _)| guard)
+}
+
+/// Like `init_tracing_subscriber`, but returns a handle for changing the level or filter at
+/// runtime, e.g. from a long-running server.
+///
+/// Panics if a global default subscriber has already been set or the options are invalid, e.g. a
+/// `target_levels` directive, see `try_init_tracing_subscriber` for a fallible alternative.
+pub fn init_reloadable_tracing_subscriber(options: TracingSubscriberOptions) -> ReloadHandle {
+    let (guard, handle) =
+        install(options, Vec::new()).expect("failed to initialize the tracing subscriber");
+    guard.forget();
+    handle
+}
+
+/// Like `init_tracing_subscriber`, but with `layers` of the caller's own in the subscriber too,
+/// e.g. to collect metrics from the events, without giving up forc's formatting.
+///
+/// The layers see every event and span, as the options only filter forc's own layers. Give them a
+/// filter of their own with `Layer::with_filter` to see less.
+///
+/// ```no_run
+/// use forc_tracing::{init_tracing_subscriber_with_layers, TracingSubscriberOptions};
+/// use tracing_subscriber::{filter::LevelFilter, Layer};
+///
+/// let metrics = tracing_subscriber::fmt::layer()
+///     .json()
+///     .with_writer(std::io::sink)
+///     .with_filter(LevelFilter::WARN)
+///     .boxed();
+/// init_tracing_subscriber_with_layers(TracingSubscriberOptions::default(), vec![metrics]);
+/// ```
+///
+/// Panics if a global default subscriber has already been set or the options are invalid, e.g. a
+/// `target_levels` directive, see `try_init_tracing_subscriber` for a fallible alternative.
+pub fn init_tracing_subscriber_with_layers(
+    options: TracingSubscriberOptions,
+    layers: Vec<Box<dyn Layer<Registry> + Send + Sync>>,
+) {
+    let (guard, _) = install(options, layers).expect("failed to initialize the tracing subscriber");
+    guard.forget();
+}
+
+/// Build the subscriber configured by `options` without installing it, so that it can be used for
+/// a scope only with `tracing::subscriber::with_default` or `tracing::subscriber::set_default`,
+/// e.g. by a library embedding forc or a test harness.
+///
+/// The subscriber owns what `init_tracing_subscriber_with_guard` returns a guard for, so the log
+/// output is flushed when the subscriber is dropped. Building it has no effect on the rest of the
+/// process: panics are only captured by the global subscriber, `set_writer_mode` only switches that
+/// one, and the color of the `println_*` helpers and `set_global_color` stay as it decided.
+///
+/// ```
+/// use forc_tracing::{build_subscriber, TracingSubscriberOptions};
+///
+/// let subscriber = build_subscriber(TracingSubscriberOptions::default()).unwrap();
+/// tracing::subscriber::with_default(subscriber, || tracing::info!("Compiling"));
+/// ```
+pub fn build_subscriber(
+    options: TracingSubscriberOptions,
+) -> Result<impl Subscriber + for<'span> LookupSpan<'span> + Send + Sync, TracingInitError> {
+    let (subscriber, guard, _) = build(&options, Vec::new())?;
+    Ok(subscriber.with(OwnedGuard { _guard: guard }))
+}
+
+/// Keeps the guard of a subscriber built by `build_subscriber` alive for as long as it is.
+struct OwnedGuard {
+    _guard: TracingGuard,
+}
+
+impl<S: Subscriber> Layer<S> for OwnedGuard {}
+
+fn install(
+    options: TracingSubscriberOptions,
+    layers: Vec<Box<dyn Layer<Registry> + Send + Sync>>,
+) -> Result<(TracingGuard, ReloadHandle), TracingInitError> {
+    let (subscriber, guard, globals) = build(&options, layers)?;
+    subscriber.try_init()?;
+    INITIALIZED.store(true, Ordering::Relaxed);
+    let reload_handle = globals.reload_handle;
+    filter::set_installed(&reload_handle);
+    writer::set_installed(globals.destination);
+    for (level, enabled) in LEVELS.iter().zip(globals.level_colors) {
+        color::set_level_color(level, enabled);
+    }
+    color::set_global_color(globals.any_color);
+    theme::set_theme_from_env();
+    if options.capture_panics == Some(true) {
+        panic::install_panic_hook();
+    }
+    Ok((guard, reload_handle))
+}
+
+/// What `install` makes global along with the subscriber built by `build`, so that subscribers that
+/// aren't installed leave the process-wide state as it is.
+struct Globals {
+    reload_handle: ReloadHandle,
+    destination: DestinationHandle,
+    // Whether the events of each of `LEVELS` are written to a stream that shows color, for the
+    // `println_*` helpers.
+    level_colors: [bool; 5],
+    // Whether any stream shows color, which seeds `set_global_color`.
+    any_color: bool,
+}
+
+/// Build the subscriber configured by `options`, with `layers` directly on top of its registry.
+fn build(
+    options: &TracingSubscriberOptions,
+    layers: Vec<Box<dyn Layer<Registry> + Send + Sync>>,
+) -> Result<
+    (
+        impl Subscriber + for<'span> LookupSpan<'span> + Send + Sync,
+        TracingGuard,
+        Globals,
+    ),
+    TracingInitError,
+> {
+    let plain = plain::undecorated(options);
+    let options = plain.as_ref().unwrap_or(options);
+    let (env_filter, reload_handle) = reloadable_env_filter(options)?;
+    let format = log_format(options);
+    let mut writer = StdioTracingWriter::new(
+        options
+            .writer_mode
+            .clone()
+            .unwrap_or(TracingWriterMode::Stdio),
+        options.ansi,
+        options.rotation.map(|rotation| Rolling {
+            rotation,
+            max_files: options.max_log_files,
+            compress: options.compress_rotated == Some(true),
+        }),
+        options.file_line_ending.unwrap_or_default(),
+    )?;
+    if let Some(split_files) = &options.split_files {
+        writer.split_files(split_files)?;
+    }
+    let destination = writer.destination_handle();
+    let workers = match options.non_blocking {
+        Some(true) => writer.make_non_blocking(),
+        _ => Vec::new(),
     };
+    let flusher = options
+        .flush_policy
+        .and_then(|policy| writer.buffer_output(policy));
+    if matches!(
+        format,
+        LogFormat::Json | LogFormat::JsonArray | LogFormat::Logfmt
+    ) {
+        // Escape codes would only end up as noise inside the JSON string and logfmt values.
+        writer.disable_ansi();
+    }
+    #[cfg(windows)]
+    writer.enable_virtual_terminal();
+    let level_colors = LEVELS.map(|level| writer.ansi_for(&level));
 
-    let level_filter = options
-        .log_level
-        .or_else(|| {
-            options.verbosity.and_then(|verbosity| {
-                match verbosity {
-                    1 => Some(LevelFilter::DEBUG), // matches --verbose or -v
-                    2 => Some(LevelFilter::TRACE), // matches -vv
-                    _ => None,
-                }
-            })
+    let ansi = writer.any_ansi();
+    let wrap_width = (format == LogFormat::Pretty && writer.is_terminal())
+        .then(|| options.wrap_width.unwrap_or_else(wrap::terminal_width));
+    let writer = JsonArrayWriter::new(writer, format == LogFormat::JsonArray);
+    let json_array = writer.closer();
+    let (terminal, dedupe_guard) = dedupe(
+        fmt_layer(options, ansi, wrap_width, writer)?,
+        options.dedupe_window,
+    );
+    #[cfg(feature = "syslog")]
+    let terminal = match options.syslog {
+        Some(true) => terminal
+            .and_then(system_log::syslog_layer(options)?)
+            .boxed(),
+        _ => terminal,
+    };
+    let (terminal, deferral) = match options.defer_warnings {
+        Some(true) => {
+            let terminal = terminal
+                .with_filter(filter_fn(|metadata| !defer::is_deferred(metadata)))
+                .boxed();
+            (terminal, Some(DeferLayer::new(&DEFERRED)))
+        }
+        _ => (terminal, None),
+    };
+    let ring_buffer = options
+        .ring_buffer_capacity
+        .map(|capacity| ring_buffer_layer(options, capacity, &RING_BUFFER))
+        .transpose()?;
+    let timings = (options.report_timings == Some(true))
+        .then(|| TimingLayer::new(&TIMINGS).with_filter(own_targets_filter(options)));
+    let transcript = options
+        .transcript_file
+        .as_deref()
+        .map(|path| transcript_layer(options, path))
+        .transpose()?;
+    let subscriber = tracing_subscriber::registry()
+        .with(layers)
+        // The level filter only applies to the output, its counts and the `on_event` callbacks, the
+        // ring buffer and the timings have a filter of their own and the transcript none.
+        .with(
+            terminal
+                .and_then(CountingLayer::new(&COUNTERS))
+                .and_then(HookLayer::new(&CALLBACKS))
+                .and_then(deferral)
+                .with_filter(env_filter),
+        )
+        .with(ring_buffer)
+        .with(timings)
+        .with(transcript);
+    let mut guard = TracingGuard::new(workers, dedupe_guard, flusher);
+    if let Some(closer) = json_array {
+        guard.close_json_array(closer);
+    }
+    #[cfg(feature = "opentelemetry")]
+    let subscriber = {
+        let otlp = match &options.otlp_endpoint {
+            Some(endpoint) => {
+                let (layer, provider) = otlp::otlp_layer(endpoint)?;
+                guard.keep_tracer_provider(provider);
+                Some(layer.with_filter(own_targets_filter(options)))
+            }
+            None => None,
+        };
+        subscriber.with(otlp)
+    };
+    let globals = Globals {
+        reload_handle,
+        destination,
+        level_colors,
+        any_color: ansi,
+    };
+    Ok((subscriber, guard, globals))
+}
+
+/// The format events are written in, with the JSON formats of `message_format` taking precedence
+/// over `format`.
+fn log_format(options: &TracingSubscriberOptions) -> LogFormat {
+    let format = options.format.unwrap_or_default();
+    match options.message_format {
+        Some(message_format) => message_format.log_format(format),
+        None => format,
+    }
+}
+
+/// Build the formatting layer that writes events to `writer` in the format given by `options`.
+///
+/// The text formats match directly using `println!`, so by default they omit the level, target and
+/// location of the event. The JSON format includes the level and target by default.
+///
+/// Messages in the pretty format are wrapped to `wrap_width` columns if one is given, regardless of
+/// whether `writer` is a terminal.
+pub(crate) fn fmt_layer<S, W>(
+    options: &TracingSubscriberOptions,
+    ansi: bool,
+    wrap_width: Option<usize>,
+    writer: W,
+) -> Result<Box<dyn Layer<S> + Send + Sync>, TracingInitError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let plain = plain::undecorated(options);
+    let options = plain.as_ref().unwrap_or(options);
+    let (ansi, wrap_width) = match plain {
+        Some(_) => (false, None),
+        None => (ansi, wrap_width),
+    };
+    let format = log_format(options);
+    let timer: Option<Timer> =
+        timer::timer(format, options.display_time, options.time_format.as_ref())?;
+    let is_json = matches!(format, LogFormat::Json | LogFormat::JsonArray);
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(ansi)
+        .with_level(match options.level_style {
+            Some(LevelStyle::Full) => true,
+            Some(LevelStyle::Short) => is_json,
+            Some(LevelStyle::None) => false,
+            None => options.display_level.unwrap_or(is_json),
         })
-        .or_else(|| {
-            options.silent.and_then(|silent| match silent {
-                true => Some(LevelFilter::OFF),
-                _ => None,
+        .with_file(options.display_file.unwrap_or(false))
+        .with_line_number(options.display_line_number.unwrap_or(false))
+        .with_target(options.display_target.unwrap_or(is_json))
+        .with_thread_ids(options.display_thread_ids.unwrap_or(false))
+        .with_thread_names(options.display_thread_names.unwrap_or(false));
+    let symbols = match (options.level_symbols, options.level_style) {
+        (Some(true), Some(_)) => return Err(TracingInitError::ConflictingLevelOptions),
+        (Some(true), None) => {
+            let unicode = match options.no_unicode {
+                Some(no_unicode) => !no_unicode,
+                None => symbols::supports_unicode(|var| std::env::var(var).ok()),
+            };
+            Some(if unicode {
+                Symbols::Unicode
+            } else {
+                Symbols::Ascii
             })
+        }
+        (_, Some(LevelStyle::Short)) => Some(Symbols::Tags),
+        _ => None,
+    };
+    // Innermost, so that what the other writers add counts too.
+    let writer = BlankLineWriter::new(writer, options.collapse_blank_lines == Some(true));
+    // Likewise, so that the trailing whitespace of whatever the other writers add is trimmed too.
+    let writer = PlainWriter::new(writer, plain.is_some());
+    let writer = BacktraceWriter::new(writer, options.error_backtraces == Some(true), is_json);
+    // Inside the backtraces, so that the fields end up on the line of the message.
+    let writer = KeyValueWriter::new(writer, ansi, is_json);
+    let pid = options.display_pid == Some(true);
+    let indent_lines = options.indent_continuation == Some(true);
+    let truncation = Truncation {
+        max_len: options.max_message_len,
+        keep_errors: options.never_truncate_errors == Some(true),
+    };
+    let pretty = Layout {
+        width: wrap_width,
+        indent_lines,
+        truncation,
+    };
+    let compact = Layout {
+        width: None,
+        indent_lines,
+        truncation,
+    };
+    let layer = match (format, timer) {
+        (LogFormat::Pretty, Some(timer)) => {
+            text(layer.with_timer(timer), symbols, pid, pretty, writer)
+        }
+        (LogFormat::Pretty, None) => text(layer.without_time(), symbols, pid, pretty, writer),
+        (LogFormat::Compact, Some(timer)) => text(
+            layer.compact().with_timer(timer),
+            symbols,
+            pid,
+            compact,
+            writer,
+        ),
+        (LogFormat::Compact, None) => text(
+            layer.compact().without_time(),
+            symbols,
+            pid,
+            compact,
+            writer,
+        ),
+        (LogFormat::Json | LogFormat::JsonArray, Some(timer)) => json(
+            layer.json().flatten_event(true).with_timer(timer),
+            pid,
+            options.message_format,
+            truncation,
+            writer,
+        ),
+        (LogFormat::Json | LogFormat::JsonArray, None) => json(
+            layer.json().flatten_event(true).without_time(),
+            pid,
+            options.message_format,
+            truncation,
+            writer,
+        ),
+        // The fields of `print_kv` are appended as in the text formats, which is valid logfmt.
+        (LogFormat::Logfmt, timer) => layer
+            .event_format(Logfmt::new(timer, pid))
+            .with_writer(writer)
+            .boxed(),
+    };
+    // The formatters always show the spans the layer is aware of, so hide them from it instead.
+    let layer = match options.display_span_context {
+        Some(true) => layer,
+        _ => layer
+            .with_filter(filter_fn(|metadata| metadata.is_event()))
+            .boxed(),
+    };
+    Ok(layer)
+}
+
+/// Finish the text formatting `layer`, prefixing events with the symbol of their level if
+/// `symbols` are given, and before that with the process id if `pid` is set, and laying out
+/// messages as `layout` says.
+fn text<S, E, W>(
+    layer: tracing_subscriber::fmt::Layer<S, DefaultFields, E>,
+    symbols: Option<Symbols>,
+    pid: bool,
+    layout: Layout,
+    writer: W,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    E: FormatEvent<S, DefaultFields> + FormatEvent<S, WrapFields> + Send + Sync + 'static,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match symbols {
+        Some(symbols) => with_pid(
+            layer.map_event_format(|format| LevelSymbols::new(format, symbols)),
+            pid,
+            layout,
+            writer,
+        ),
+        None => with_pid(layer, pid, layout, writer),
+    }
+}
+
+/// Finish the text formatting `layer`, prefixing events with the namespaces of `with_log_namespace`,
+/// and before that with the process id if `pid` is set.
+fn with_pid<S, E, W>(
+    layer: tracing_subscriber::fmt::Layer<S, DefaultFields, E>,
+    pid: bool,
+    layout: Layout,
+    writer: W,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    E: FormatEvent<S, DefaultFields> + FormatEvent<S, WrapFields> + Send + Sync + 'static,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = layer.map_event_format(Namespaced::new);
+    match pid {
+        true => wrapped(layer.map_event_format(ProcessId::new), layout, writer),
+        false => wrapped(layer, layout, writer),
+    }
+}
+
+/// Finish the JSON formatting `layer`, adding a `pid` field to events if `pid` is set, the
+/// `diagnostic` object rendered as `message_format` says to those of `emit_diagnostic` and the
+/// `namespace` array to those logged within `with_log_namespace`, and truncating the message as
+/// `truncation` says.
+fn json<S, N, E, W>(
+    layer: tracing_subscriber::fmt::Layer<S, N, E>,
+    pid: bool,
+    message_format: Option<MessageFormat>,
+    truncation: Truncation,
+    writer: W,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + Send + Sync + 'static,
+    E: FormatEvent<S, N> + Send + Sync + 'static,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let writer = JsonNamespaceWriter::new(JsonDiagnosticWriter::new(writer, message_format));
+    // Outside of the diagnostics, which have a `message` of their own.
+    let writer = JsonTruncateWriter::new(writer, truncation);
+    match pid {
+        true => layer.with_writer(JsonPidWriter::new(writer)).boxed(),
+        false => layer.with_writer(writer).boxed(),
+    }
+}
+
+/// Finish `layer`, laying out messages as `layout` says unless it leaves them as they are.
+fn wrapped<S, E, W>(
+    layer: tracing_subscriber::fmt::Layer<S, DefaultFields, E>,
+    layout: Layout,
+    writer: W,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    E: FormatEvent<S, DefaultFields> + FormatEvent<S, WrapFields> + Send + Sync + 'static,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match layout.is_plain() {
+        true => layer.with_writer(writer).boxed(),
+        false => layer
+            .fmt_fields(WrapFields::default())
+            .map_event_format(WrapFormat)
+            .with_writer(WrapWriter::new(writer, layout))
+            .boxed(),
+    }
+}
+
+// Held by the tests that modify the process environment, e.g. `RUST_LOG` that the filter is taken
+// from or the color variables, and by those that build a subscriber, which reads them, or that
+// change or depend on the color decision of the `println_*` helpers.
+#[cfg(test)]
+static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use tracing_subscriber::layer::Context;
+
+    /// Format the events logged by `f` with the given options, returning the output.
+    fn capture(options: TracingSubscriberOptions, f: impl FnOnce()) -> String {
+        let capture = CapturingWriter::new();
+        let subscriber =
+            Registry::default().with(fmt_layer(&options, false, None, capture.clone()).unwrap());
+        tracing::subscriber::with_default(subscriber, f);
+        capture.contents()
+    }
+
+    #[test]
+    fn test_pretty_format_matches_println() {
+        let output = capture(Default::default(), || tracing::info!("Compiling"));
+        assert_eq!(output, "Compiling\n");
+    }
+
+    #[test]
+    fn test_json_format() {
+        let json = || TracingSubscriberOptions::builder().format(LogFormat::Json);
+        let output = capture(json().build(), || {
+            tracing::info!("Compiling");
+            tracing::warn!("Unused import");
         });
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["message"], "Compiling");
+        assert_eq!(events[0]["level"], "INFO");
+        assert_eq!(events[1]["level"], "WARN");
+        assert_eq!(events[1]["target"], module_path!());
+        assert!(events[1]["timestamp"].is_string());
 
-    let builder = tracing_subscriber::fmt::Subscriber::builder()
-        .with_env_filter(env_filter)
-        .with_ansi(true)
-        .with_level(false)
-        .with_file(false)
-        .with_line_number(false)
-        .without_time()
-        .with_target(false)
-        .with_writer(StdioTracingWriter {
-            writer_mode: options.writer_mode.unwrap_or(TracingWriterMode::Stdio),
+        let output = capture(json().display_time(false).build(), || {
+            tracing::info!("Compiling")
         });
+        let event: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(event.get("timestamp").is_none());
+    }
 
-    // If log level, verbosity, or silent mode is set, it overrides the RUST_LOG setting
-    if let Some(level_filter) = level_filter {
-        builder.with_max_level(level_filter).init();
-    } else {
-        builder.init();
+    #[test]
+    fn test_display_options() {
+        let line = |options: TracingSubscriberOptionsBuilder| {
+            capture(
+                options.build(),
+                || tracing::warn!(target: "forc_pkg", "Unused"),
+            )
+        };
+        let options = TracingSubscriberOptions::builder;
+        assert_eq!(line(options()), "Unused\n");
+        assert_eq!(line(options().display_level(true)), " WARN Unused\n");
+        assert_eq!(line(options().display_target(true)), "forc_pkg: Unused\n");
+        let file = line(options().display_file(true));
+        assert_eq!(file, format!("{}: Unused\n", file!()));
+        let line_number = line(options().display_line_number(true));
+        assert!(line_number.ends_with(": Unused\n"));
+        assert!(line_number
+            .trim_end_matches(": Unused\n")
+            .parse::<u32>()
+            .is_ok());
+
+        // The JSON format shows the level and target unless disabled.
+        let json = || options().format(LogFormat::Json).display_time(false);
+        let event: serde_json::Value =
+            serde_json::from_str(&line(json().display_level(false))).unwrap();
+        assert!(event.get("level").is_none());
+        assert_eq!(event["target"], "forc_pkg");
+    }
+
+    #[test]
+    fn test_display_thread_ids() {
+        let options = TracingSubscriberOptions::builder()
+            .display_thread_ids(true)
+            .display_thread_names(true)
+            .build();
+        let output = capture(options, || {
+            let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+            let log = move || {
+                tracing::dispatcher::with_default(&dispatch, || tracing::info!("Compiling"))
+            };
+            for name in ["runner-0", "runner-1"] {
+                std::thread::Builder::new()
+                    .name(name.to_string())
+                    .spawn(log.clone())
+                    .unwrap()
+                    .join()
+                    .unwrap();
+            }
+        });
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("runner-0 ThreadId("), "{}", lines[0]);
+        assert!(lines[1].starts_with("runner-1 ThreadId("), "{}", lines[1]);
+        let id = |line: &str| line.split_whitespace().nth(1).unwrap().to_string();
+        assert_ne!(id(lines[0]), id(lines[1]));
+    }
+
+    #[test]
+    fn test_time_formats() {
+        let timestamp = |time_format| {
+            let options = TracingSubscriberOptions::builder()
+                .time_format(time_format)
+                .build();
+            let output = capture(options, || tracing::info!("Compiling"));
+            output.strip_suffix(" Compiling\n").unwrap().to_string()
+        };
+
+        let utc = timestamp(TimeFormat::Rfc3339);
+        assert_eq!(utc.len(), "2023-07-25T14:03:54.123456Z".len(), "{utc}");
+        assert_eq!(&utc[10..11], "T");
+        assert!(utc.ends_with('Z'));
+
+        let local = timestamp(TimeFormat::LocalRfc3339);
+        assert_eq!(&local[10..11], "T");
+        assert!(local.ends_with('Z') || local[local.len() - 6..].starts_with(['+', '-']));
+
+        let uptime = timestamp(TimeFormat::Uptime);
+        let seconds = uptime.trim().strip_suffix('s').unwrap();
+        assert!(seconds.parse::<f64>().unwrap() < 60.0);
+
+        assert_eq!(timestamp(TimeFormat::Delta), "+0.000s");
+
+        let custom = timestamp(TimeFormat::Custom("%Y/%m".to_string()));
+        assert_eq!(custom.len(), "2023/07".len());
+        assert_eq!(&custom[4..5], "/");
+
+        // Setting `display_time` to false still omits the timestamp.
+        let options = TracingSubscriberOptions::builder()
+            .time_format(TimeFormat::Uptime)
+            .display_time(false)
+            .build();
+        let output = capture(options, || tracing::info!("Compiling"));
+        assert_eq!(output, "Compiling\n");
+    }
+
+    #[test]
+    fn test_delta_time_format() {
+        let options = TracingSubscriberOptions::builder()
+            .time_format(TimeFormat::Delta)
+            .build();
+        let sleep = Duration::from_millis(50);
+        let output = capture(options, || {
+            tracing::info!("Compiling");
+            std::thread::sleep(sleep);
+            // Measured from the previous event, whichever stream it went to.
+            tracing::error!("Failed");
+        });
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[0], "+0.000s Compiling");
+        let delta = lines[1].strip_suffix("s Failed").unwrap();
+        let delta: f64 = delta.strip_prefix('+').unwrap().parse().unwrap();
+        assert!(delta >= sleep.as_secs_f64(), "{delta}");
+    }
+
+    #[test]
+    fn test_println_colored_routing() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .build();
+        let output = capture(options, || {
+            println_colored("out", Color::Blue, false);
+            println_colored("err", Color::Blue, true);
+            println_yellow_err("warning");
+            println_bold_white("bold");
+        });
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let levels: Vec<_> = events.iter().map(|event| &event["level"]).collect();
+        assert_eq!(levels, ["INFO", "ERROR", "ERROR", "INFO"]);
+        // No subscriber was initialized to enable color.
+        assert_eq!(events[0]["message"], "out");
+        assert_eq!(events[3]["message"], "bold");
+    }
+
+    #[test]
+    fn test_println_silent() {
+        // Holds off tests from setting `RUST_LOG`, which quiet mode defers to.
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let style = Color::Green.on_default();
+        let silent = TracingSubscriberOptions::builder().silent(true).build();
+        let output = testing::with_captured_logs(silent, || {
+            println_green("Finished");
+            println_red_err("Failed");
+            assert_eq!(paint_enabled(Level::INFO, "Finished", style), None);
+        });
+        assert_eq!(output, "");
+
+        let quiet = TracingSubscriberOptions::builder().quiet(true).build();
+        let output = testing::with_captured_logs(quiet, || {
+            println_green("Finished");
+            println_red_err("Failed");
+            assert_eq!(paint_enabled(Level::INFO, "Finished", style), None);
+            assert!(paint_enabled(Level::ERROR, "Failed", style).is_some());
+        });
+        assert_eq!(output, "Failed\n");
+    }
+
+    #[test]
+    fn test_global_color() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        color::set_level_color(&Level::INFO, true);
+        let painted = capture(Default::default(), || {
+            set_global_color(false);
+            // Only installing a subscriber seeds the switch.
+            let options = TracingSubscriberOptions::builder().ansi(true).build();
+            drop(build_subscriber(options).unwrap());
+            println_red("Failed");
+            set_global_color(true);
+            println_red("Failed");
+        });
+        color::set_level_color(&Level::INFO, false);
+        let red = paint("Failed", theme::theme().error.on_default(), true);
+        assert_eq!(painted, format!("Failed\n{red}\n"));
+        assert!(global_color_enabled());
+    }
+
+    #[test]
+    fn test_paint() {
+        let style = Color::Cyan.on_default();
+        assert_eq!(paint("txt", style, false), "txt");
+        assert_eq!(paint("txt", style, true), "\x1b[36mtxt\x1b[0m");
+    }
+
+    #[test]
+    fn test_options_debug_and_clone() {
+        let options = TracingSubscriberOptions {
+            verbosity: Some(2),
+            writer_mode: Some(TracingWriterMode::File("forc.log".into())),
+            ..Default::default()
+        };
+        assert_eq!(options.clone(), options);
+        let debug = format!("{options:?}");
+        assert!(debug.contains("verbosity: Some(2)"));
+        assert!(debug.contains("File(\"forc.log\")"));
+    }
+
+    #[test]
+    fn test_options_builder() {
+        let built = TracingSubscriberOptions::builder()
+            .verbosity(2)
+            .silent(false)
+            .writer_mode(TracingWriterMode::Stderr)
+            .format(LogFormat::Json)
+            .extra_target("my_plugin")
+            .build();
+        let literal = TracingSubscriberOptions {
+            verbosity: Some(2),
+            silent: Some(false),
+            writer_mode: Some(TracingWriterMode::Stderr),
+            format: Some(LogFormat::Json),
+            extra_targets: vec!["my_plugin".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(built, literal);
+        assert_eq!(
+            TracingSubscriberOptions::builder().build(),
+            TracingSubscriberOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_try_init_twice() {
+        let options = || TracingSubscriberOptions {
+            silent: Some(true),
+            ..Default::default()
+        };
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // Nothing else in the test binary installs a global subscriber, but don't rely on it.
+        let _ = try_init_tracing_subscriber(options());
+        assert!(try_init_tracing_subscriber(options()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_filter_is_an_error() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var("RUST_LOG");
+        let options = || {
+            TracingSubscriberOptions::builder()
+                .target_level("forc[", LevelFilter::DEBUG)
+                .build()
+        };
+        assert!(matches!(
+            try_init_tracing_subscriber(options()),
+            Err(TracingInitError::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            build_subscriber(options()),
+            Err(TracingInitError::InvalidFilter(_))
+        ));
+    }
+
+    #[test]
+    fn test_build_subscriber_is_scoped() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::File(path.clone()))
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        tracing::info!("Before");
+        tracing::subscriber::with_default(subscriber, || tracing::info!("Compiling"));
+        tracing::info!("After");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "Compiling\n");
+    }
+
+    #[test]
+    fn test_build_subscriber_leaves_color() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let info_color = color::level_color(&Level::INFO);
+        set_global_color(true);
+        color::set_level_color(&Level::INFO, true);
+        let options = TracingSubscriberOptions::builder().ansi(false).build();
+        drop(build_subscriber(options).unwrap());
+        assert!(global_color_enabled());
+        assert!(color::level_color(&Level::INFO));
+        color::set_level_color(&Level::INFO, info_color);
+    }
+
+    #[test]
+    fn test_null_writer_still_counts() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Null)
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        let before = log_counts();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "forc", "Unused");
+            tracing::error!(target: "forc", "Failed");
+        });
+        let after = log_counts();
+        assert!(after.warnings > before.warnings);
+        assert!(after.errors > before.errors);
+    }
+
+    #[test]
+    fn test_custom_writer() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let capture = CapturingWriter::new();
+        let custom = {
+            let capture = capture.clone();
+            CustomWriter::new(move || Box::new(capture.clone()))
+        };
+        assert_eq!(custom, custom.clone());
+        assert_ne!(custom, CustomWriter::new(|| Box::new(io::sink())));
+
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Custom(custom))
+            .ansi(true)
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "forc", "Compiling");
+            tracing::error!(target: "forc", "Failed");
+        });
+        assert_eq!(capture.contents(), "Compiling\nFailed\n");
+    }
+
+    #[test]
+    fn test_extra_layers() {
+        struct EventCounter(Arc<AtomicUsize>);
+
+        impl Layer<Registry> for EventCounter {
+            fn on_event(&self, _event: &tracing::Event<'_>, _ctx: Context<'_, Registry>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var("RUST_LOG");
+        let capture = CapturingWriter::new();
+        let custom = {
+            let capture = capture.clone();
+            CustomWriter::new(move || Box::new(capture.clone()))
+        };
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Custom(custom))
+            .build();
+        let events = Arc::new(AtomicUsize::new(0));
+        let layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> =
+            vec![Box::new(EventCounter(events.clone()))];
+        let (subscriber, _guard, _) = build(&options, layers).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "forc", "Compiling");
+            tracing::error!(target: "forc", "Failed");
+            // Not filtered out for the layers of the caller.
+            tracing::debug!(target: "forc", "resolved");
+        });
+        assert_eq!(capture.contents(), "Compiling\nFailed\n");
+        assert_eq!(events.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_display_span_context() {
+        let log = || {
+            let _compile = tracing::info_span!("compile", pkg = "foo").entered();
+            let _typecheck = tracing::info_span!("typecheck").entered();
+            tracing::info!("Checking");
+        };
+        assert_eq!(capture(Default::default(), log), "Checking\n");
+
+        let options = TracingSubscriberOptions::builder().display_span_context(true);
+        assert_eq!(
+            capture(options.clone().build(), log),
+            "compile{pkg=\"foo\"}:typecheck: Checking\n"
+        );
+        let output = capture(options.format(LogFormat::Json).build(), log);
+        let event: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(event["spans"][0]["name"], "compile");
+        assert_eq!(event["spans"][0]["pkg"], "foo");
+        assert_eq!(event["span"]["name"], "typecheck");
     }
 }
@@ -0,0 +1,162 @@
+//! The `key=value` lines of `LogFormat::Logfmt`.
+
+use crate::timer::Timer;
+use std::fmt::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Formats each event as one line of `key=value` pairs: `ts` if there is a `timer`, `level`,
+/// `target`, `pid` if `pid` is set, and `msg`, followed by the fields of the event.
+pub(crate) struct Logfmt {
+    timer: Option<Timer>,
+    pid: bool,
+}
+
+impl Logfmt {
+    pub(crate) fn new(timer: Option<Timer>, pid: bool) -> Self {
+        Self { timer, pid }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for Logfmt
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        if let Some(timer) = &self.timer {
+            let mut ts = String::new();
+            timer.write(&mut ts)?;
+            // The uptime is padded to align the text formats.
+            write!(writer, "ts={} ", value(ts.trim()))?;
+        }
+        write!(
+            writer,
+            "level={} target={}",
+            metadata.level().as_str().to_ascii_lowercase(),
+            value(metadata.target())
+        )?;
+        if self.pid {
+            write!(writer, " pid={}", std::process::id())?;
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        write!(writer, " msg={}", value(&fields.message))?;
+        for (key, field) in &fields.others {
+            write!(writer, " {key}={}", value(field))?;
+        }
+        writeln!(writer)
+    }
+}
+
+/// `text` as a logfmt value, quoted and escaped if it is empty or contains whitespace, `=` or `"`.
+fn value(text: &str) -> String {
+    let quote = text.is_empty()
+        || text
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '=' || c == '"');
+    match quote {
+        true => format!("{text:?}"),
+        false => text.to_string(),
+    }
+}
+
+/// The message of an event and its other fields, in the order they were given.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    others: Vec<(&'static str, String)>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => self.others.push((name, value.to_string())),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let mut text = String::new();
+        let _ = write!(text, "{value:?}");
+        match field.name() {
+            "message" => self.message = text,
+            name => self.others.push((name, text)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, LogFormat, TracingSubscriberOptions};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn capture(options: TracingSubscriberOptions, f: impl FnOnce()) -> String {
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&options, false, None, capture.clone()).unwrap();
+        let subscriber = Registry::default().with(layer);
+        tracing::subscriber::with_default(subscriber, f);
+        capture.contents()
+    }
+
+    fn logfmt() -> crate::TracingSubscriberOptionsBuilder {
+        TracingSubscriberOptions::builder().format(LogFormat::Logfmt)
+    }
+
+    #[test]
+    fn test_logfmt_quoting() {
+        let output = capture(logfmt().display_time(false).build(), || {
+            tracing::warn!(
+                target: "forc_pkg",
+                path = "/my project/Forc.toml",
+                filter = "forc=debug",
+                quote = "say \"hi\"",
+                attempts = 3,
+                empty = "",
+                "fetch failed"
+            )
+        });
+        assert_eq!(
+            output,
+            "level=warn target=forc_pkg msg=\"fetch failed\" path=\"/my project/Forc.toml\" \
+             filter=\"forc=debug\" quote=\"say \\\"hi\\\"\" attempts=3 empty=\"\"\n"
+        );
+        let output = capture(
+            logfmt().display_time(false).build(),
+            || tracing::info!(target: "forc", "Compiling"),
+        );
+        assert_eq!(output, "level=info target=forc msg=Compiling\n");
+    }
+
+    #[test]
+    fn test_logfmt_keys() {
+        // Like JSON, the timestamp is shown by default.
+        let output = capture(logfmt().build(), || tracing::error!("failed"));
+        let keys: Vec<_> = output
+            .split(' ')
+            .map(|pair| pair.split('=').next().unwrap())
+            .collect();
+        assert_eq!(keys, ["ts", "level", "target", "msg"]);
+        assert!(output.ends_with(" msg=failed\n"));
+
+        let pid = std::process::id();
+        let output = capture(
+            logfmt().display_time(false).display_pid(true).build(),
+            || tracing::info!(target: "forc", "Compiling"),
+        );
+        assert_eq!(
+            output,
+            format!("level=info target=forc pid={pid} msg=Compiling\n")
+        );
+    }
+}
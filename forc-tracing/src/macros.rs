@@ -0,0 +1,113 @@
+//! Colored print macros taking format arguments, like the `println_*` functions take a string.
+
+/// Print the formatted text in red at INFO, like `println_red`.
+///
+/// ```
+/// let package = "std";
+/// forc_tracing::info_red!("Failed to fetch {package}");
+/// ```
+#[macro_export]
+macro_rules! info_red {
+    ($($arg:tt)*) => {
+        $crate::__private::log_colored(
+            $crate::__private::Level::INFO,
+            &::std::format!($($arg)*),
+            $crate::Color::Red,
+        )
+    };
+}
+
+/// Print the formatted text in green at INFO, like `println_green`.
+///
+/// ```
+/// let (passed, total) = (3, 3);
+/// forc_tracing::info_green!("{passed}/{total} tests passed");
+/// ```
+#[macro_export]
+macro_rules! info_green {
+    ($($arg:tt)*) => {
+        $crate::__private::log_colored(
+            $crate::__private::Level::INFO,
+            &::std::format!($($arg)*),
+            $crate::Color::Green,
+        )
+    };
+}
+
+/// Print the formatted text in yellow at WARN, so that it is routed to stderr.
+///
+/// ```
+/// let name = "x";
+/// forc_tracing::warn_yellow!("unused variable `{}`", name);
+/// ```
+#[macro_export]
+macro_rules! warn_yellow {
+    ($($arg:tt)*) => {
+        $crate::__private::log_colored(
+            $crate::__private::Level::WARN,
+            &::std::format!($($arg)*),
+            $crate::Color::Yellow,
+        )
+    };
+}
+
+/// Print the formatted text in red at ERROR, so that it is routed to stderr, like `println_red_err`.
+///
+/// ```
+/// let (path, err) = ("Forc.toml", "not found");
+/// forc_tracing::error_red!("failed to open {}: {}", path, err);
+/// ```
+#[macro_export]
+macro_rules! error_red {
+    ($($arg:tt)*) => {
+        $crate::__private::log_colored(
+            $crate::__private::Level::ERROR,
+            &::std::format!($($arg)*),
+            $crate::Color::Red,
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::with_captured_logs;
+    use crate::{LogFormat, TracingSubscriberOptions, ENV_LOCK};
+
+    #[test]
+    fn test_colored_macros() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .build();
+        let (path, err) = ("Forc.toml", "not found");
+        let output = with_captured_logs(options, || {
+            info_red!("plain");
+            info_green!("{} of {}", 1, 2);
+            warn_yellow!("unused `{path}`");
+            error_red!("failed to open {}: {err}", path);
+        });
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let logged: Vec<_> = events
+            .iter()
+            .map(|event| {
+                (
+                    event["level"].as_str().unwrap(),
+                    event["message"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            logged,
+            [
+                ("INFO", "plain"),
+                ("INFO", "1 of 2"),
+                ("WARN", "unused `Forc.toml`"),
+                ("ERROR", "failed to open Forc.toml: not found"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! The output formats of cargo's `--message-format`, for tooling that already reads cargo's output.
+
+use crate::LogFormat;
+use std::str::FromStr;
+
+/// The format of the output selected like cargo's `--message-format`, see
+/// `TracingSubscriberOptions::message_format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// `human`: the text format of the `format` option.
+    Human,
+    /// `json`: the JSON format, with a `rendered` key in each `diagnostic` object holding the
+    /// diagnostic as the text formats show it.
+    Json,
+    /// `json-diagnostic-short`: like `Json`, with the `rendered` diagnostic on one line, e.g.
+    /// `src/main.sw:3:5: error[E0308]: mismatched types`.
+    JsonDiagnosticShort,
+    /// `json-diagnostic-rendered-ansi`: like `Json`, with the `rendered` diagnostic in ANSI color.
+    JsonDiagnosticRenderedAnsi,
+}
+
+impl MessageFormat {
+    /// The format events are written in, `text` being the one `Human` keeps. The JSON formats
+    /// keep `LogFormat::JsonArray` too.
+    pub(crate) fn log_format(self, text: LogFormat) -> LogFormat {
+        match self {
+            MessageFormat::Human => text,
+            _ if text == LogFormat::JsonArray => text,
+            MessageFormat::Json
+            | MessageFormat::JsonDiagnosticShort
+            | MessageFormat::JsonDiagnosticRenderedAnsi => LogFormat::Json,
+        }
+    }
+}
+
+/// The error returned when parsing a `MessageFormat` from a string that isn't one of cargo's.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "invalid message format `{0}`, expected one of `human`, `json`, `json-diagnostic-short` or \
+     `json-diagnostic-rendered-ansi`"
+)]
+pub struct ParseMessageFormatError(String);
+
+impl FromStr for MessageFormat {
+    type Err = ParseMessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            "json-diagnostic-short" => Ok(MessageFormat::JsonDiagnosticShort),
+            "json-diagnostic-rendered-ansi" => Ok(MessageFormat::JsonDiagnosticRenderedAnsi),
+            _ => Err(ParseMessageFormatError(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::with_captured_logs;
+    use crate::{
+        emit_diagnostic, Diagnostic, LineCol, Severity, SourceSpan, TracingSubscriberOptions,
+    };
+
+    fn unused_variable() -> Diagnostic {
+        Diagnostic {
+            code: None,
+            severity: Severity::Warning,
+            message: "unused variable `x`".to_string(),
+            file: Some("src/main.sw".into()),
+            span: Some(SourceSpan {
+                start: LineCol { line: 2, col: 9 },
+                end: LineCol { line: 2, col: 10 },
+            }),
+            labels: Vec::new(),
+        }
+    }
+
+    /// The event of `unused_variable` logged with `message_format`.
+    fn emit(message_format: MessageFormat) -> serde_json::Value {
+        let options = TracingSubscriberOptions::builder()
+            .message_format(message_format)
+            .display_time(false)
+            .build();
+        let output = with_captured_logs(options, || emit_diagnostic(unused_variable()));
+        serde_json::from_str(&output).unwrap()
+    }
+
+    #[test]
+    fn test_parse_message_format() {
+        let formats = [
+            "human",
+            "json",
+            "json-diagnostic-short",
+            "json-diagnostic-rendered-ansi",
+        ]
+        .map(|format| format.parse());
+        assert_eq!(
+            formats,
+            [
+                Ok(MessageFormat::Human),
+                Ok(MessageFormat::Json),
+                Ok(MessageFormat::JsonDiagnosticShort),
+                Ok(MessageFormat::JsonDiagnosticRenderedAnsi),
+            ]
+        );
+        assert_eq!(
+            "short".parse::<MessageFormat>(),
+            Err(ParseMessageFormatError("short".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_message_format_log_format() {
+        assert_eq!(
+            MessageFormat::Human.log_format(LogFormat::Compact),
+            LogFormat::Compact
+        );
+        assert_eq!(
+            MessageFormat::JsonDiagnosticShort.log_format(LogFormat::Compact),
+            LogFormat::Json
+        );
+        assert_eq!(
+            MessageFormat::Json.log_format(LogFormat::JsonArray),
+            LogFormat::JsonArray
+        );
+
+        let options = TracingSubscriberOptions::builder()
+            .message_format(MessageFormat::Human)
+            .build();
+        let output = with_captured_logs(options, || emit_diagnostic(unused_variable()));
+        assert_eq!(
+            output,
+            "warning: unused variable `x`\n  --> src/main.sw:2:9\n"
+        );
+    }
+
+    #[test]
+    fn test_message_format_rendered() {
+        let event = emit(MessageFormat::Json);
+        assert_eq!(event["level"], "WARN");
+        assert_eq!(
+            event["diagnostic"]["rendered"],
+            "warning: unused variable `x`\n  --> src/main.sw:2:9"
+        );
+
+        let event = emit(MessageFormat::JsonDiagnosticShort);
+        assert_eq!(
+            event["diagnostic"]["rendered"],
+            "src/main.sw:2:9: warning: unused variable `x`"
+        );
+
+        let event = emit(MessageFormat::JsonDiagnosticRenderedAnsi);
+        let rendered = event["diagnostic"]["rendered"].as_str().unwrap();
+        assert!(rendered.starts_with("\x1b["));
+        assert!(rendered.contains("unused variable `x`"));
+        // The escape codes are only in the rendered text, not in the message of the event.
+        assert!(!event["message"].as_str().unwrap().contains('\x1b'));
+    }
+}
@@ -0,0 +1,221 @@
+//! Tags for the events logged on a thread, e.g. the package being built, see `with_log_namespace`.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use crate::color;
+use anstyle::Style;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::writer::EitherWriter;
+use tracing_subscriber::fmt::{FmtContext, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+
+thread_local! {
+    // The namespaces of this thread, outermost first.
+    static NAMESPACES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Run `f` with the events logged on the current thread tagged with `tag`, e.g. the name of the
+/// package being built when several are built in parallel.
+///
+/// The text formats prefix the events with the dimmed tags of all enclosing calls, e.g.
+/// `[workspace][my_contract] Compiling`, and the JSON format adds them as the `namespace` array.
+/// Other threads are unaffected, so each can be given a tag of its own.
+///
+/// ```
+/// forc_tracing::with_log_namespace("my_contract", || {
+///     forc_tracing::print_action("Compiling", "my_contract");
+/// });
+/// ```
+pub fn with_log_namespace<R>(tag: &str, f: impl FnOnce() -> R) -> R {
+    NAMESPACES.with(|namespaces| namespaces.borrow_mut().push(tag.to_string()));
+    let _restore = Restore;
+    f()
+}
+
+/// Removes the innermost namespace of the thread when dropped, even if `f` panics.
+struct Restore;
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        NAMESPACES.with(|namespaces| namespaces.borrow_mut().pop());
+    }
+}
+
+/// Prefixes the events formatted by the wrapped text formatter with the namespaces of the thread.
+pub(crate) struct Namespaced<E> {
+    inner: E,
+}
+
+impl<E> Namespaced<E> {
+    pub(crate) fn new(inner: E) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, N, E> FormatEvent<S, N> for Namespaced<E>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+    E: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let prefix = NAMESPACES.with(|namespaces| {
+            let namespaces = namespaces.borrow();
+            (!namespaces.is_empty()).then(|| {
+                let tags: String = namespaces.iter().map(|tag| format!("[{tag}]")).collect();
+                match writer.has_ansi_escapes() {
+                    true => color::paint(&tags, Style::new().dimmed()),
+                    false => tags,
+                }
+            })
+        });
+        if let Some(prefix) = prefix {
+            write!(writer, "{prefix} ")?;
+        }
+        self.inner.format_event(ctx, writer, event)
+    }
+}
+
+/// Adds a `namespace` field to the JSON objects written through the wrapped writer for the events
+/// logged within `with_log_namespace`.
+pub(crate) struct JsonNamespaceWriter<W> {
+    inner: W,
+}
+
+impl<W> JsonNamespaceWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for JsonNamespaceWriter<W> {
+    type Writer = EitherWriter<BoxedEvent<'a, W::Writer>, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        EitherWriter::B(self.inner.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        let inner = self.inner.make_writer_for(meta);
+        let namespace = NAMESPACES.with(|namespaces| {
+            let namespaces = namespaces.borrow();
+            (!namespaces.is_empty())
+                .then(|| serde_json::to_string(&*namespaces).expect("strings serialize to JSON"))
+        });
+        let Some(namespace) = namespace else {
+            return EitherWriter::B(inner);
+        };
+        EitherWriter::A(BufferedEvent::boxed(
+            inner,
+            move |buffer, inner| match buffer.strip_prefix(b"{") {
+                Some(fields) => {
+                    write!(inner, "{{\"namespace\":{namespace},")?;
+                    inner.write_all(fields)
+                }
+                None => inner.write_all(buffer),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, LogFormat, TracingSubscriberOptions};
+    use tracing::Dispatch;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn subscriber(
+        options: &TracingSubscriberOptions,
+        ansi: bool,
+        capture: &CapturingWriter,
+    ) -> Dispatch {
+        let layer = fmt_layer(options, ansi, None, capture.clone()).unwrap();
+        Dispatch::new(Registry::default().with(layer))
+    }
+
+    #[test]
+    fn test_log_namespace() {
+        let capture = CapturingWriter::new();
+        let dispatch = subscriber(&TracingSubscriberOptions::default(), false, &capture);
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::info!("Resolving");
+            with_log_namespace("my_contract", || tracing::info!("Compiling"));
+            tracing::info!("Finished");
+        });
+        assert_eq!(
+            capture.contents(),
+            "Resolving\n[my_contract] Compiling\nFinished\n"
+        );
+
+        let capture = CapturingWriter::new();
+        let dispatch = subscriber(&TracingSubscriberOptions::default(), true, &capture);
+        tracing::dispatcher::with_default(&dispatch, || {
+            with_log_namespace("std", || tracing::info!("Compiling"));
+        });
+        assert_eq!(capture.contents(), "\x1b[2m[std]\x1b[0m Compiling\n");
+    }
+
+    #[test]
+    fn test_nested_log_namespaces() {
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .build();
+        let capture = CapturingWriter::new();
+        let dispatch = subscriber(&options, false, &capture);
+        tracing::dispatcher::with_default(&dispatch, || {
+            with_log_namespace("workspace", || {
+                with_log_namespace("my_contract", || tracing::info!("Compiling"));
+                tracing::info!("Finished");
+            });
+        });
+        let events: Vec<serde_json::Value> = capture
+            .contents()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            events[0]["namespace"],
+            serde_json::json!(["workspace", "my_contract"])
+        );
+        assert_eq!(events[0]["message"], "Compiling");
+        assert_eq!(events[1]["namespace"], serde_json::json!(["workspace"]));
+
+        let capture = CapturingWriter::new();
+        let dispatch = subscriber(&TracingSubscriberOptions::default(), false, &capture);
+        tracing::dispatcher::with_default(&dispatch, || {
+            with_log_namespace("workspace", || {
+                with_log_namespace("my_contract", || tracing::info!("Compiling"));
+            });
+        });
+        assert_eq!(capture.contents(), "[workspace][my_contract] Compiling\n");
+    }
+
+    #[test]
+    fn test_log_namespace_is_thread_local() {
+        let capture = CapturingWriter::new();
+        let dispatch = subscriber(&TracingSubscriberOptions::default(), false, &capture);
+        with_log_namespace("core", || {
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    with_log_namespace("std", || {
+                        tracing::dispatcher::with_default(&dispatch, || {
+                            tracing::info!("Compiling");
+                        })
+                    })
+                });
+            });
+        });
+        assert_eq!(capture.contents(), "[std] Compiling\n");
+    }
+}
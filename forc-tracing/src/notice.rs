@@ -0,0 +1,258 @@
+//! Notices framed in a box, e.g. of breaking changes, see `print_notice`.
+
+use crate::align::{pad_visible, truncate_middle, visible_width};
+use crate::{color, symbols, theme, wrap, Color, Style, PRINT_TARGET};
+use tracing::Level;
+
+/// How prominent a notice of `print_notice` is: the color of its title bar and the level it is
+/// logged at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoticeStyle {
+    /// In the info color of the theme, at INFO.
+    Info,
+    /// In the warn color of the theme, at WARN.
+    Warning,
+    /// In the error color of the theme, at ERROR.
+    Error,
+}
+
+impl NoticeStyle {
+    fn level(self) -> Level {
+        match self {
+            Self::Info => Level::INFO,
+            Self::Warning => Level::WARN,
+            Self::Error => Level::ERROR,
+        }
+    }
+
+    fn color(self) -> Color {
+        let theme = theme::theme();
+        match self {
+            Self::Info => theme.info,
+            Self::Warning => theme.warn,
+            Self::Error => theme.error,
+        }
+    }
+}
+
+/// The characters that the box of a notice is drawn with.
+struct Frame {
+    top_left: char,
+    top_right: char,
+    bottom_left: char,
+    bottom_right: char,
+    horizontal: char,
+    vertical: char,
+}
+
+const UNICODE: Frame = Frame {
+    top_left: '┌',
+    top_right: '┐',
+    bottom_left: '└',
+    bottom_right: '┘',
+    horizontal: '─',
+    vertical: '│',
+};
+
+const ASCII: Frame = Frame {
+    top_left: '+',
+    top_right: '+',
+    bottom_left: '+',
+    bottom_right: '+',
+    horizontal: '-',
+    vertical: '|',
+};
+
+/// The narrowest a notice is drawn, however narrow the terminal.
+const MIN_WIDTH: usize = 20;
+
+/// Print `body` in a box as wide as the terminal, below a title bar with `title` colored as `style`
+/// says, one event per line at the level of `style`:
+///
+/// ```text
+/// ┌─ Breaking change ──────────────────┐
+/// │ The `abi` field of Forc.toml is    │
+/// │ renamed to `abi_path`.             │
+/// └────────────────────────────────────┘
+/// ```
+///
+/// The body is wrapped at spaces to fit inside the box, and its own newlines are kept. A title
+/// too long for the bar is shortened in the middle. The box is drawn with `+`, `-` and `|` instead
+/// where color is omitted or the terminal isn't expected to render box-drawing characters.
+///
+/// ```no_run
+/// use forc_tracing::{print_notice, NoticeStyle};
+///
+/// print_notice(
+///     "Breaking change",
+///     "The `abi` field of Forc.toml is renamed to `abi_path`.",
+///     NoticeStyle::Warning,
+/// );
+/// ```
+pub fn print_notice(title: &str, body: &str, style: NoticeStyle) {
+    let level = style.level();
+    let ansi = color::level_color(&level);
+    let unicode = ansi && symbols::supports_unicode(|var| std::env::var(var).ok());
+    let width = wrap::terminal_width();
+    for line in notice_lines(title, body, style, width, unicode, ansi) {
+        match level {
+            Level::ERROR => tracing::error!(target: PRINT_TARGET, "{line}"),
+            Level::WARN => tracing::warn!(target: PRINT_TARGET, "{line}"),
+            _ => tracing::info!(target: PRINT_TARGET, "{line}"),
+        }
+    }
+}
+
+/// The lines of the notice, each taking up `width` columns, or `MIN_WIDTH` if that is less.
+fn notice_lines(
+    title: &str,
+    body: &str,
+    style: NoticeStyle,
+    width: usize,
+    unicode: bool,
+    ansi: bool,
+) -> Vec<String> {
+    let frame = match unicode {
+        true => &UNICODE,
+        false => &ASCII,
+    };
+    let width = width.max(MIN_WIDTH);
+    let horizontal = |len: usize| frame.horizontal.to_string().repeat(len);
+    let color = style.color().on_default();
+    let paint = |text: &str, style: Style| match ansi {
+        true => color::paint(text, style),
+        false => text.to_string(),
+    };
+    // The bar is `┌─ title ─…─┐`, with the title in bold and at least one `─` after it.
+    let title = truncate_middle(title, width - 6);
+    let rest = horizontal(width - 5 - visible_width(&title));
+    let bar = format!(
+        "{}{}{}",
+        paint(&format!("{}{} ", frame.top_left, frame.horizontal), color),
+        paint(&title, color.bold()),
+        paint(&format!(" {rest}{}", frame.top_right), color),
+    );
+    let mut lines = vec![bar];
+    // Inside `│ ` and ` │`.
+    let inner = width - 4;
+    for line in wrap::wrap(body, inner, 0, false).split('\n') {
+        lines.push(format!(
+            "{} {} {}",
+            frame.vertical,
+            pad_visible(line, inner),
+            frame.vertical
+        ));
+    }
+    lines.push(format!(
+        "{}{}{}",
+        frame.bottom_left,
+        horizontal(width - 2),
+        frame.bottom_right
+    ));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "The `abi` field of Forc.toml is renamed to `abi_path`.";
+
+    #[test]
+    fn test_notice_lines() {
+        assert_eq!(
+            notice_lines(
+                "Breaking change",
+                BODY,
+                NoticeStyle::Warning,
+                30,
+                true,
+                false
+            ),
+            [
+                "┌─ Breaking change ──────────┐",
+                "│ The `abi` field of         │",
+                "│ Forc.toml is renamed to    │",
+                "│ `abi_path`.                │",
+                "└────────────────────────────┘",
+            ]
+        );
+        assert_eq!(
+            notice_lines(
+                "Note",
+                "Run `forc update`.",
+                NoticeStyle::Info,
+                30,
+                false,
+                false
+            ),
+            [
+                "+- Note ---------------------+",
+                "| Run `forc update`.         |",
+                "+----------------------------+",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_notice_body_wraps_inside() {
+        let body = format!("{BODY}\n\nSee the migration guide for the steps to take.");
+        for width in [MIN_WIDTH, 37, 80] {
+            let lines = notice_lines(
+                "Breaking change",
+                &body,
+                NoticeStyle::Error,
+                width,
+                true,
+                false,
+            );
+            for line in &lines {
+                assert_eq!(visible_width(line), width, "{line:?}");
+            }
+            // The body is only interrupted by its own blank line.
+            let words: Vec<_> = lines[1..lines.len() - 1]
+                .iter()
+                .flat_map(|line| line.trim_matches(['│', ' ']).split_whitespace())
+                .collect();
+            assert_eq!(
+                words.join(" "),
+                body.split_whitespace().collect::<Vec<_>>().join(" ")
+            );
+        }
+        // Too long for the bar the title is shortened, and wider than the terminal the box is kept
+        // at its narrowest.
+        let lines = notice_lines(
+            "A very long title for a notice",
+            BODY,
+            NoticeStyle::Info,
+            10,
+            true,
+            false,
+        );
+        assert_eq!(lines[0], "┌─ A very …notice ─┐");
+    }
+
+    #[test]
+    fn test_notice_title_painted() {
+        let color = theme::theme().warn.on_default();
+        let lines = notice_lines(
+            "Note",
+            "Run `forc update`.",
+            NoticeStyle::Warning,
+            24,
+            true,
+            true,
+        );
+        assert_eq!(
+            lines[0],
+            format!(
+                "{}{}{}",
+                color::paint("┌─ ", color),
+                color::paint("Note", color.bold()),
+                color::paint(" ───────────────┐", color)
+            )
+        );
+        // Only the title bar is colored.
+        assert_eq!(lines[1], "│ Run `forc update`.   │");
+    }
+}
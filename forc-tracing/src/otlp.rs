@@ -0,0 +1,83 @@
+//! Export of spans to an OpenTelemetry collector, see `TracingSubscriberOptions::otlp_endpoint`.
+
+use crate::TracingInitError;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporterBuilder, WithExportConfig};
+use opentelemetry_sdk::export::trace::SpanExporter;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// The layer that exports the spans that close to the OTLP/HTTP collector at `endpoint`, and the
+/// provider of its tracer, which must be kept alive for as long as spans are exported.
+pub(crate) fn otlp_layer<S>(
+    endpoint: &str,
+) -> Result<(OpenTelemetryLayer<S, Tracer>, TracerProvider), TracingInitError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(endpoint);
+    let exporter = SpanExporterBuilder::from(exporter).build_span_exporter()?;
+    Ok(exporting_layer(exporter))
+}
+
+/// The layer that exports the spans that close to `exporter` one at a time, on a background thread,
+/// rather than in batches on an async runtime that forc doesn't have. Dropping the provider waits
+/// for the pending spans to be exported.
+fn exporting_layer<S>(
+    exporter: impl SpanExporter + 'static,
+) -> (OpenTelemetryLayer<S, Tracer>, TracerProvider)
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter)
+        .build();
+    let tracer = provider.tracer("forc");
+    (tracing_opentelemetry::layer().with_tracer(tracer), provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::export::trace::{ExportResult, SpanData};
+    use std::future::{self, Future};
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    /// Collects the exported spans in memory.
+    #[derive(Clone, Debug, Default)]
+    struct InMemoryExporter(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for InMemoryExporter {
+        fn export(
+            &mut self,
+            batch: Vec<SpanData>,
+        ) -> Pin<Box<dyn Future<Output = ExportResult> + Send>> {
+            self.0.lock().unwrap().extend(batch);
+            Box::pin(future::ready(Ok(())))
+        }
+    }
+
+    #[test]
+    fn test_spans_are_exported() {
+        let exporter = InMemoryExporter::default();
+        let (layer, provider) = exporting_layer(exporter.clone());
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            let _span = tracing::info_span!("compile", pkg = "std").entered();
+            tracing::info!("Compiling");
+        });
+        drop(provider);
+        let spans = exporter.0.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "compile");
+        assert!(spans[0]
+            .attributes
+            .iter()
+            .any(|(key, value)| key.as_str() == "pkg" && value.as_str() == "std"));
+    }
+}
@@ -0,0 +1,144 @@
+//! Logging of panics through the subscriber, see `TracingSubscriberOptions::capture_panics`.
+
+use crate::writer;
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::panic::Location;
+
+/// Install a panic hook that logs the panic as an ERROR event, before running the previous hook.
+///
+/// The previous hook is skipped if the event was written to stderr, as the default hook would
+/// print the panic there a second time.
+pub(crate) fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        writer::take_panic_on_stderr();
+        let thread = std::thread::current();
+        let message = panic_message(thread.name(), info.payload(), info.location());
+        // Captured only if enabled by `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`.
+        let backtrace = Backtrace::capture();
+        match backtrace.status() {
+            BacktraceStatus::Captured => {
+                tracing::error!(target: PANIC_TARGET, "{message}\nstack backtrace:\n{backtrace}")
+            }
+            _ => tracing::error!(target: PANIC_TARGET, "{message}"),
+        }
+        if !writer::take_panic_on_stderr() {
+            previous(info);
+        }
+    }));
+}
+
+pub(crate) const PANIC_TARGET: &str = "forc_tracing::panic";
+
+/// Describe a panic like the default hook does, e.g.
+/// `thread 'main' panicked at src/main.rs:2:5:\nexplicit panic`.
+fn panic_message(
+    thread: Option<&str>,
+    payload: &(dyn Any + Send),
+    location: Option<&Location<'_>>,
+) -> String {
+    let payload = match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(payload), _) => payload,
+        (_, Some(payload)) => payload.as_str(),
+        _ => "Box<dyn Any>",
+    };
+    let thread = thread.unwrap_or("<unnamed>");
+    match location {
+        Some(location) => format!("thread '{thread}' panicked at {location}:\n{payload}"),
+        None => format!("thread '{thread}' panicked:\n{payload}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::writer::StdioTracingWriter;
+    use crate::{fmt_layer, LineEnding, TracingSubscriberOptions, TracingWriterMode};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    /// Panic on a thread named `worker` that logs to `writer`.
+    fn panic_logged_to<W>(writer: W)
+    where
+        W: for<'writer> tracing_subscriber::fmt::MakeWriter<'writer> + Send + Sync + 'static,
+    {
+        let options = TracingSubscriberOptions::builder()
+            .display_level(true)
+            .build();
+        let subscriber =
+            Registry::default().with(fmt_layer(&options, false, None, writer).unwrap());
+        let dispatch = tracing::Dispatch::new(subscriber);
+        let result = std::thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || panic!("worker failed"));
+            })
+            .unwrap()
+            .join();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let location = Location::caller();
+        assert_eq!(
+            panic_message(Some("main"), &"explicit panic", Some(location)),
+            format!("thread 'main' panicked at {location}:\nexplicit panic")
+        );
+        assert_eq!(
+            panic_message(None, &String::from("formatted 1"), None),
+            "thread '<unnamed>' panicked:\nformatted 1"
+        );
+    }
+
+    #[test]
+    fn test_capture_panics() {
+        // Stands in for the default hook, counting the panics of the workers it would print.
+        let previous_runs = Arc::new(AtomicUsize::new(0));
+        std::panic::set_hook({
+            let previous_runs = previous_runs.clone();
+            Box::new(move |_| {
+                if std::thread::current().name() == Some("worker") {
+                    previous_runs.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        });
+        install_panic_hook();
+
+        let capture = CapturingWriter::new();
+        panic_logged_to(capture.clone());
+        let previous_runs_elsewhere = previous_runs.load(Ordering::Relaxed);
+
+        // Logged to stderr, the panic isn't printed by the previous hook too.
+        let stderr = CapturingWriter::new();
+        let mut writer =
+            StdioTracingWriter::new(TracingWriterMode::Stdio, Some(false), None, LineEnding::Lf)
+                .unwrap();
+        writer.capture_stderr(stderr.clone());
+        panic_logged_to(writer);
+
+        // Restore the default hook, as other tests may panic on failure.
+        drop(std::panic::take_hook());
+
+        let output = capture.contents();
+        assert!(
+            output.starts_with("ERROR thread 'worker' panicked at "),
+            "{output}"
+        );
+        assert!(output.contains("worker failed"), "{output}");
+        assert_eq!(previous_runs_elsewhere, 1);
+        let output = stderr.contents();
+        assert!(
+            output.starts_with("ERROR thread 'worker' panicked at "),
+            "{output}"
+        );
+        assert_eq!(output.matches("worker failed").count(), 1, "{output}");
+        assert_eq!(previous_runs.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,106 @@
+//! The id of the process in front of or among the fields of events, see
+//! `TracingSubscriberOptions::display_pid`.
+
+use crate::buffered::BufferedEvent;
+use std::{fmt, io};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Prefixes the events formatted by the wrapped text formatter with `[pid] `.
+pub(crate) struct ProcessId<E> {
+    inner: E,
+}
+
+impl<E> ProcessId<E> {
+    pub(crate) fn new(inner: E) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, N, E> FormatEvent<S, N> for ProcessId<E>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+    E: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        write!(writer, "[{}] ", std::process::id())?;
+        self.inner.format_event(ctx, writer, event)
+    }
+}
+
+/// Adds a `pid` field to the JSON objects written through the wrapped writer.
+///
+/// The JSON formatter of `tracing_subscriber` can't be given fields of its own, so the field is
+/// inserted into each object as it's written out instead.
+pub(crate) struct JsonPidWriter<W> {
+    inner: W,
+}
+
+impl<W> JsonPidWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for JsonPidWriter<W> {
+    type Writer = BufferedEvent<W::Writer, InsertPid<W::Writer>>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        BufferedEvent::new(self.inner.make_writer(), insert_pid)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        BufferedEvent::new(self.inner.make_writer_for(meta), insert_pid)
+    }
+}
+
+type InsertPid<W> = fn(&[u8], &mut W) -> io::Result<()>;
+
+/// Write the JSON object of one event to `inner` with the `pid` field.
+fn insert_pid<W: io::Write>(buffer: &[u8], inner: &mut W) -> io::Result<()> {
+    match buffer.strip_prefix(b"{") {
+        Some(fields) => {
+            write!(inner, "{{\"pid\":{},", std::process::id())?;
+            inner.write_all(fields)
+        }
+        None => inner.write_all(buffer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, LogFormat, TracingSubscriberOptions};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn capture(options: TracingSubscriberOptions) -> String {
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&options, false, None, capture.clone()).unwrap();
+        let subscriber = Registry::default().with(layer);
+        tracing::subscriber::with_default(subscriber, || tracing::info!("Compiling"));
+        capture.contents()
+    }
+
+    #[test]
+    fn test_display_pid() {
+        let pid = std::process::id();
+        let options = TracingSubscriberOptions::builder().display_pid(true);
+        assert_eq!(
+            capture(options.clone().build()),
+            format!("[{pid}] Compiling\n")
+        );
+
+        let output = capture(options.format(LogFormat::Json).display_time(false).build());
+        let event: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(event["pid"], pid);
+        assert_eq!(event["message"], "Compiling");
+    }
+}
@@ -0,0 +1,104 @@
+//! Output without any decoration for scripts to parse, see `TracingSubscriberOptions::plain`.
+
+use crate::buffered::BufferedEvent;
+use crate::{LevelStyle, TracingSubscriberOptions};
+use std::io;
+use tracing::Metadata;
+use tracing_subscriber::fmt::writer::EitherWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// `options` with all decoration turned off, if `plain` is set.
+pub(crate) fn undecorated(options: &TracingSubscriberOptions) -> Option<TracingSubscriberOptions> {
+    if options.plain != Some(true) {
+        return None;
+    }
+    Some(TracingSubscriberOptions {
+        ansi: Some(false),
+        display_level: Some(false),
+        level_style: Some(LevelStyle::None),
+        level_symbols: Some(false),
+        display_target: Some(false),
+        display_file: Some(false),
+        display_line_number: Some(false),
+        display_thread_ids: Some(false),
+        display_thread_names: Some(false),
+        display_pid: Some(false),
+        display_span_context: Some(false),
+        display_time: Some(false),
+        indent_continuation: Some(false),
+        ..options.clone()
+    })
+}
+
+/// Trims the trailing whitespace of each line written through the wrapped writer, if `enabled`.
+pub(crate) struct PlainWriter<W> {
+    inner: W,
+    enabled: bool,
+}
+
+impl<W> PlainWriter<W> {
+    pub(crate) fn new(inner: W, enabled: bool) -> Self {
+        Self { inner, enabled }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for PlainWriter<W> {
+    type Writer = EitherWriter<BufferedEvent<W::Writer, Trim<W::Writer>>, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.wrap(self.inner.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.wrap(self.inner.make_writer_for(meta))
+    }
+}
+
+impl<W> PlainWriter<W> {
+    fn wrap<E: io::Write>(&self, inner: E) -> EitherWriter<BufferedEvent<E, Trim<E>>, E> {
+        match self.enabled {
+            true => EitherWriter::A(BufferedEvent::new(inner, trim)),
+            false => EitherWriter::B(inner),
+        }
+    }
+}
+
+type Trim<W> = fn(&[u8], &mut W) -> io::Result<()>;
+
+/// Write the output of one event to `inner` with the lines trimmed.
+fn trim<W: io::Write>(buffer: &[u8], inner: &mut W) -> io::Result<()> {
+    for (i, line) in buffer.split(|&byte| byte == b'\n').enumerate() {
+        if i > 0 {
+            inner.write_all(b"\n")?;
+        }
+        inner.write_all(line.trim_ascii_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::with_captured_logs;
+    use crate::{LevelStyle, TimeFormat, TracingSubscriberOptions, ENV_LOCK};
+
+    #[test]
+    fn test_plain() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // The decoration set explicitly is turned off too.
+        let options = TracingSubscriberOptions::builder()
+            .plain(true)
+            .ansi(true)
+            .display_time(true)
+            .time_format(TimeFormat::Uptime)
+            .level_style(LevelStyle::Full)
+            .display_target(true)
+            .display_thread_names(true)
+            .display_pid(true)
+            .build();
+        let output = with_captured_logs(options, || {
+            tracing::warn!(target: "forc", "unused import  \t");
+            tracing::info!(target: "forc", "Compiling\nmy_contract  ");
+        });
+        assert_eq!(output, "unused import\nCompiling\nmy_contract\n");
+    }
+}
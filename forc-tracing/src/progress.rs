@@ -0,0 +1,90 @@
+//! Writing events above `indicatif` progress bars, so that the bars don't smear across log lines.
+
+use crate::buffered::BufferedEvent;
+use indicatif::MultiProgress;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+
+static PROGRESS: Mutex<Option<MultiProgress>> = Mutex::new(None);
+
+fn lock() -> MutexGuard<'static, Option<MultiProgress>> {
+    PROGRESS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Write the events logged to stdout and stderr above the bars of `progress` from now on, by
+/// hiding the bars while each event is written.
+///
+/// Events written by the background threads of `TracingSubscriberOptions::non_blocking` are written
+/// above the bars too, as the threads write them out.
+///
+/// ```no_run
+/// use indicatif::{MultiProgress, ProgressBar};
+///
+/// let progress = MultiProgress::new();
+/// let bar = progress.add(ProgressBar::new(3));
+/// forc_tracing::set_progress(progress);
+/// for dependency in ["core", "std", "my_lib"] {
+///     tracing::info!("Compiling {dependency}");
+///     bar.inc(1);
+/// }
+/// bar.finish_and_clear();
+/// forc_tracing::clear_progress();
+/// ```
+pub fn set_progress(progress: MultiProgress) {
+    *lock() = Some(progress);
+}
+
+/// Stop coordinating events with the progress bars registered by `set_progress`.
+pub fn clear_progress() {
+    *lock() = None;
+}
+
+/// Wrap `writer` so that it writes above the registered progress bars, if any.
+pub(crate) fn above_progress<'a>(writer: impl io::Write + 'a) -> Box<dyn io::Write + 'a> {
+    let Some(progress) = lock().clone() else {
+        return Box::new(writer);
+    };
+    Box::new(suspended(progress, writer))
+}
+
+/// A writer for one event that writes it to `writer` with the progress bars hidden.
+fn suspended<'a, W: io::Write + 'a>(progress: MultiProgress, writer: W) -> impl io::Write + 'a {
+    BufferedEvent::new(writer, move |buffer: &[u8], inner: &mut W| {
+        progress.suspend(|| {
+            inner.write_all(buffer)?;
+            inner.flush()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use indicatif::{ProgressBar, ProgressDrawTarget};
+    use std::io::Write;
+
+    #[test]
+    fn test_writes_through_suspend() {
+        let progress = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+        let bar = progress.add(ProgressBar::new(2));
+        let capture = CapturingWriter::new();
+
+        let mut writer = suspended(progress, capture.clone());
+        writer.write_all(b"Compiling core\n").unwrap();
+        // Nothing is written until the bars are hidden for the whole event.
+        assert_eq!(capture.contents(), "");
+        drop(writer);
+        bar.inc(1);
+        assert_eq!(capture.contents(), "Compiling core\n");
+    }
+
+    #[test]
+    fn test_above_progress_without_progress() {
+        clear_progress();
+        let capture = CapturingWriter::new();
+        let mut writer = above_progress(capture.clone());
+        writer.write_all(b"Compiling core\n").unwrap();
+        assert_eq!(capture.contents(), "Compiling core\n");
+    }
+}
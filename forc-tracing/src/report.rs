@@ -0,0 +1,299 @@
+//! Structured events for tools that consume the log output, e.g. an IDE reading the JSON format.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// The target of the events logged by `report_progress`.
+///
+/// These are logged at DEBUG, so they can be enabled on their own, e.g. with
+/// `RUST_LOG=info,forc::progress=debug` or the `target_levels` option.
+pub const PROGRESS_TARGET: &str = "forc::progress";
+
+/// Log that `current` of the `total` steps of `phase` are done, e.g. `report_progress("compile",
+/// 2, 5)` once the second of five packages has been compiled.
+///
+/// The event has `phase`, `current` and `total` fields, which the JSON format writes as the values
+/// of the keys of the same name. Its message is for the text formats, e.g. `compile 2/5`.
+pub fn report_progress(phase: &str, current: u64, total: u64) {
+    tracing::debug!(
+        target: PROGRESS_TARGET,
+        phase,
+        current,
+        total,
+        "{phase} {current}/{total}"
+    );
+}
+
+/// Where the progress of a long-running step goes, so that the code doing the work doesn't decide
+/// how it is shown: the CLI can pass a reporter that draws a spinner, and the language server one
+/// that logs structured events, i.e. `LoggingReporter`.
+///
+/// ```
+/// use forc_tracing::{LoggingReporter, ProgressReporter};
+///
+/// fn compile(packages: &[&str], progress: &dyn ProgressReporter) {
+///     progress.set_total(packages.len() as u64);
+///     for package in packages {
+///         progress.set_message(package);
+///         progress.inc(1);
+///     }
+///     progress.finish("done");
+/// }
+///
+/// compile(&["core", "std"], &LoggingReporter::new("compile"));
+/// ```
+pub trait ProgressReporter {
+    /// Set the number of steps there are to do.
+    fn set_total(&self, total: u64);
+    /// Note that `delta` more steps are done.
+    fn inc(&self, delta: u64);
+    /// Describe what is being done now, e.g. the package being compiled.
+    fn set_message(&self, message: &str);
+    /// Note that the work is over, with `message` describing how it went.
+    fn finish(&self, message: &str);
+}
+
+/// A `ProgressReporter` that shows nothing, e.g. for tests or when the output is quiet.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopReporter;
+
+impl ProgressReporter for NoopReporter {
+    fn set_total(&self, _total: u64) {}
+
+    fn inc(&self, _delta: u64) {}
+
+    fn set_message(&self, _message: &str) {}
+
+    fn finish(&self, _message: &str) {}
+}
+
+/// A `ProgressReporter` that logs the progress of `phase` as the events of `report_progress`.
+///
+/// Each `inc` logs the steps done so far, and `set_message` and `finish` log events with the same
+/// `phase` field and theirs as the `status` field, which `finish` marks with `finished = true`.
+#[derive(Debug)]
+pub struct LoggingReporter {
+    phase: String,
+    current: AtomicU64,
+    total: AtomicU64,
+}
+
+impl LoggingReporter {
+    /// A reporter for `phase`, with no steps done out of 0 yet.
+    pub fn new(phase: impl Into<String>) -> Self {
+        Self {
+            phase: phase.into(),
+            current: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ProgressReporter for LoggingReporter {
+    fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    fn inc(&self, delta: u64) {
+        let current = self.current.fetch_add(delta, Ordering::Relaxed) + delta;
+        report_progress(&self.phase, current, self.total.load(Ordering::Relaxed));
+    }
+
+    fn set_message(&self, message: &str) {
+        let phase = &self.phase;
+        tracing::debug!(
+            target: PROGRESS_TARGET,
+            phase,
+            status = message,
+            "{phase}: {message}"
+        );
+    }
+
+    fn finish(&self, message: &str) {
+        let phase = &self.phase;
+        let current = self.current.load(Ordering::Relaxed);
+        let total = self.total.load(Ordering::Relaxed);
+        tracing::debug!(
+            target: PROGRESS_TARGET,
+            phase,
+            current,
+            total,
+            status = message,
+            finished = true,
+            "{phase} {message}"
+        );
+    }
+}
+
+/// The target of the events logged by `PhaseTimer` and `time_phase`.
+///
+/// Like those of `PROGRESS_TARGET`, these are logged at DEBUG, e.g. for
+/// `RUST_LOG=info,forc::timing=debug`.
+pub const TIMING_TARGET: &str = "forc::timing";
+
+/// Logs how long a phase took when dropped, e.g. parsing or type-checking a package.
+///
+/// The event has `phase` and `elapsed_ms` fields, and a message for the text formats, e.g.
+/// `typecheck took 120ms`.
+///
+/// ```
+/// let _timer = forc_tracing::PhaseTimer::start("typecheck");
+/// // Type-check the package, until `_timer` goes out of scope.
+/// ```
+#[must_use = "the phase ends as soon as the timer is dropped"]
+#[derive(Debug)]
+pub struct PhaseTimer {
+    phase: String,
+    start: Instant,
+}
+
+impl PhaseTimer {
+    /// Start timing `phase`.
+    pub fn start(phase: impl Into<String>) -> Self {
+        Self {
+            phase: phase.into(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        let phase = &self.phase;
+        let elapsed_ms = u64::try_from(self.start.elapsed().as_millis()).unwrap_or(u64::MAX);
+        tracing::debug!(
+            target: TIMING_TARGET,
+            phase,
+            elapsed_ms,
+            "{phase} took {elapsed_ms}ms"
+        );
+    }
+}
+
+/// Run `f` as the phase `phase`, logging how long it took like `PhaseTimer`.
+///
+/// ```
+/// let ast = forc_tracing::time_phase("parse", || "fn main() {}".len());
+/// ```
+pub fn time_phase<R>(phase: &str, f: impl FnOnce() -> R) -> R {
+    let _timer = PhaseTimer::start(phase);
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, resolve_env_filter, LogFormat, TracingSubscriberOptions};
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::{layer::SubscriberExt, Layer, Registry};
+
+    #[test]
+    fn test_report_progress() {
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .log_level(LevelFilter::INFO)
+            .target_level(PROGRESS_TARGET, LevelFilter::DEBUG)
+            .build();
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&options, false, None, capture.clone())
+            .unwrap()
+            .with_filter(resolve_env_filter(&options));
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            tracing::debug!(target: "forc_pkg", "Resolving");
+            report_progress("compile", 2, 5);
+        });
+        let output = capture.contents();
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // Progress events are enabled without the other DEBUG events.
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["target"], PROGRESS_TARGET);
+        assert_eq!(events[0]["phase"], "compile");
+        assert_eq!(events[0]["current"], 2);
+        assert_eq!(events[0]["total"], 5);
+        assert_eq!(events[0]["message"], "compile 2/5");
+    }
+
+    #[test]
+    fn test_logging_reporter() {
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .target_level(PROGRESS_TARGET, LevelFilter::DEBUG)
+            .build();
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&options, false, None, capture.clone())
+            .unwrap()
+            .with_filter(resolve_env_filter(&options));
+        let reporter = LoggingReporter::new("compile");
+        let progress: &dyn ProgressReporter = &reporter;
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            progress.set_total(3);
+            progress.set_message("core");
+            progress.inc(1);
+            progress.inc(2);
+            progress.finish("done");
+            // Reports nothing.
+            NoopReporter.inc(1);
+        });
+        let events: Vec<serde_json::Value> = capture
+            .contents()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let fields = |event: &serde_json::Value| {
+            let mut event = event.as_object().unwrap().clone();
+            assert_eq!(event.remove("target").unwrap(), PROGRESS_TARGET);
+            assert_eq!(event.remove("level").unwrap(), "DEBUG");
+            serde_json::Value::Object(event)
+        };
+        let events: Vec<_> = events.iter().map(fields).collect();
+        assert_eq!(
+            events,
+            [
+                serde_json::json!({"message": "compile: core", "phase": "compile", "status": "core"}),
+                serde_json::json!({"message": "compile 1/3", "phase": "compile", "current": 1, "total": 3}),
+                serde_json::json!({"message": "compile 3/3", "phase": "compile", "current": 3, "total": 3}),
+                serde_json::json!({
+                    "message": "compile done",
+                    "phase": "compile",
+                    "current": 3,
+                    "total": 3,
+                    "status": "done",
+                    "finished": true,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_phase() {
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .log_level(LevelFilter::INFO)
+            .target_level(TIMING_TARGET, LevelFilter::DEBUG)
+            .build();
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&options, false, None, capture.clone())
+            .unwrap()
+            .with_filter(resolve_env_filter(&options));
+        let result = tracing::subscriber::with_default(Registry::default().with(layer), || {
+            time_phase("typecheck", || {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                42
+            })
+        });
+        assert_eq!(result, 42);
+        let event: serde_json::Value = serde_json::from_str(&capture.contents()).unwrap();
+        assert_eq!(event["target"], TIMING_TARGET);
+        assert_eq!(event["phase"], "typecheck");
+        let elapsed_ms = event["elapsed_ms"].as_u64().unwrap();
+        assert!(elapsed_ms >= 5);
+        assert_eq!(event["message"], format!("typecheck took {elapsed_ms}ms"));
+    }
+}
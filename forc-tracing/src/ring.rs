@@ -0,0 +1,129 @@
+//! Retention of the most recent events in memory, e.g. to attach them to a crash report.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use crate::filter::own_targets_filter;
+use crate::{fmt_layer, TracingInitError, TracingSubscriberOptions};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use tracing::Subscriber;
+use tracing_subscriber::{fmt::MakeWriter, registry::LookupSpan, Layer};
+
+/// A bounded buffer of formatted events, which evicts the oldest once full.
+pub(crate) struct RingBuffer {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    pub(crate) const fn new() -> Self {
+        Self {
+            lines: VecDeque::new(),
+            capacity: 0,
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.lines.len() >= self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+}
+
+/// The buffer of the global subscriber.
+pub(crate) static RING_BUFFER: Mutex<RingBuffer> = Mutex::new(RingBuffer::new());
+
+fn lock(buffer: &Mutex<RingBuffer>) -> MutexGuard<'_, RingBuffer> {
+    buffer.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The layer formatting events into `buffer`, keeping the most recent `capacity` of them.
+///
+/// It has a level filter of its own, so that events are retained even if they aren't shown, e.g.
+/// the DEBUG events of forc's own crates without `-v`.
+pub(crate) fn ring_buffer_layer<S>(
+    options: &TracingSubscriberOptions,
+    capacity: usize,
+    buffer: &'static Mutex<RingBuffer>,
+) -> Result<Box<dyn Layer<S> + Send + Sync>, TracingInitError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    {
+        let mut buffer = lock(buffer);
+        buffer.capacity = capacity;
+        buffer.lines.clear();
+    }
+    let layer = fmt_layer(options, false, None, RingWriter(buffer))?
+        .with_filter(own_targets_filter(options))
+        .boxed();
+    Ok(layer)
+}
+
+/// Take the events currently held by the ring buffer of `ring_buffer_capacity`, oldest first.
+///
+/// Returns nothing if the ring buffer is disabled.
+pub fn drain_ring_buffer() -> Vec<String> {
+    lock(&RING_BUFFER).lines.drain(..).collect()
+}
+
+/// Install a panic hook that prints the events held by the ring buffer to stderr after the
+/// message of the previous hook, to give context to panics.
+pub fn install_ring_buffer_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        let lines = drain_ring_buffer();
+        if !lines.is_empty() {
+            eprintln!("note: the last {} log lines before the panic:", lines.len());
+            for line in lines {
+                eprintln!("  {line}");
+            }
+        }
+    }));
+}
+
+struct RingWriter(&'static Mutex<RingBuffer>);
+
+impl<'a> MakeWriter<'a> for RingWriter {
+    type Writer = BoxedEvent<'a, io::Sink>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        // The formatted bytes of the event are pushed to the buffer as one line.
+        BufferedEvent::boxed(io::sink(), |bytes, _| {
+            let line = String::from_utf8_lossy(bytes);
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            if !line.is_empty() {
+                lock(self.0).push(line.to_string());
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    #[test]
+    fn test_ring_buffer_keeps_most_recent() {
+        static BUFFER: Mutex<RingBuffer> = Mutex::new(RingBuffer::new());
+        let layer = ring_buffer_layer(&Default::default(), 3, &BUFFER).unwrap();
+        let subscriber = Registry::default().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..5 {
+                tracing::info!("event {i}");
+            }
+            // Retained regardless of the level that is shown, which is INFO by default.
+            tracing::debug!(target: "forc", "event 5");
+            tracing::debug!(target: "hyper", "dependency");
+        });
+        let lines: Vec<_> = lock(&BUFFER).lines.drain(..).collect();
+        assert_eq!(lines, ["event 3", "event 4", "event 5"]);
+    }
+}
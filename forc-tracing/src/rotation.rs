@@ -0,0 +1,338 @@
+//! Rotation of log files, see `TracingSubscriberOptions::rotation`.
+//!
+//! The file being written to always keeps its configured path, so that it can be followed with
+//! `tail -f`, and rotated files are renamed next to it.
+
+use chrono::{DateTime, Local};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+/// When a log file is rotated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    /// On the first event of each local day, renaming the file to e.g. `forc.log.2023-07-25`.
+    Daily,
+    /// On the first event of each local hour, renaming the file to e.g. `forc.log.2023-07-25-14`.
+    Hourly,
+    /// Before an event that would grow the file past this many bytes, renaming it to `forc.log.1`
+    /// and the files rotated before to `forc.log.2`, `forc.log.3` and so on.
+    Size(u64),
+}
+
+/// How the log files are rotated, and how many rotated files are kept.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Rolling {
+    pub(crate) rotation: Rotation,
+    pub(crate) max_files: Option<usize>,
+    pub(crate) compress: bool,
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Appends to the file at `path`, rotating it as configured by `rolling`.
+///
+/// Each write is expected to be a whole event, so that events aren't split across files.
+pub(crate) struct RollingFile {
+    path: PathBuf,
+    rolling: Rolling,
+    file: File,
+    size: u64,
+    // The period of `Daily` and `Hourly` rotation the file was started in.
+    period: String,
+    // The compression of the file rotated last, if it may still be running.
+    compressing: Option<JoinHandle<()>>,
+}
+
+impl RollingFile {
+    pub(crate) fn open(path: &Path, rolling: Rolling) -> io::Result<Self> {
+        let file = append(path)?;
+        let metadata = file.metadata()?;
+        // A file left over from an earlier run is only rotated once its period has passed.
+        let started: DateTime<Local> = match metadata.len() {
+            0 => Local::now(),
+            _ => metadata
+                .modified()
+                .map(Into::into)
+                .unwrap_or_else(|_| Local::now()),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            rolling,
+            file,
+            size: metadata.len(),
+            period: period(rolling.rotation, started),
+            compressing: None,
+        })
+    }
+
+    /// Whether the file should be rotated before writing an event of `len` bytes.
+    fn is_due(&self, len: usize) -> bool {
+        match self.rolling.rotation {
+            Rotation::Size(max) => self.size > 0 && self.size + len as u64 > max,
+            rotation => self.size > 0 && period(rotation, Local::now()) != self.period,
+        }
+    }
+
+    /// Rename the file out of the way and start a new one at its path.
+    fn rotate(&mut self) -> io::Result<()> {
+        // The rotated files are renamed and deleted below, so let the last compression finish.
+        if let Some(compressing) = self.compressing.take() {
+            let _ = compressing.join();
+        }
+        let rotated = match self.rolling.rotation {
+            Rotation::Size(_) => self.shift_numbered()?,
+            _ => {
+                let rotated = suffixed(&self.path, &self.period);
+                fs::rename(&self.path, &rotated)?;
+                self.prune_dated()?;
+                Some(rotated)
+            }
+        };
+        if let Some(rotated) = rotated.filter(|_| self.rolling.compress) {
+            // Compressing a whole file takes a while, so don't hold up the event that is waiting.
+            self.compressing = Some(thread::spawn(move || {
+                if let Err(err) = compress(&rotated) {
+                    eprintln!(
+                        "warning: failed to compress rotated log file `{}`: {err}",
+                        rotated.display()
+                    );
+                }
+            }));
+        }
+        self.file = append(&self.path)?;
+        self.size = 0;
+        self.period = period(self.rolling.rotation, Local::now());
+        Ok(())
+    }
+
+    /// Rename `name.N` to `name.N+1` for every rotated file, compressed or not, and the file
+    /// itself to `name.1`, deleting those beyond `max_files`. Returns the path of the file that was
+    /// rotated, unless it was deleted right away.
+    fn shift_numbered(&self) -> io::Result<Option<PathBuf>> {
+        let numbered = |n: usize| {
+            let path = suffixed(&self.path, &n.to_string());
+            let compressed = suffixed(&path, "gz");
+            [path, compressed]
+        };
+        let mut count = 0;
+        while numbered(count + 1).iter().any(|path| path.exists()) {
+            count += 1;
+        }
+        if let Some(max_files) = self.rolling.max_files {
+            while count >= max_files && count > 0 {
+                for path in numbered(count).iter().filter(|path| path.exists()) {
+                    fs::remove_file(path)?;
+                }
+                count -= 1;
+            }
+        }
+        for n in (1..=count).rev() {
+            for (from, to) in numbered(n).into_iter().zip(numbered(n + 1)) {
+                if from.exists() {
+                    fs::rename(from, to)?;
+                }
+            }
+        }
+        if self.rolling.max_files == Some(0) {
+            fs::remove_file(&self.path)?;
+            return Ok(None);
+        }
+        let [rotated, _] = numbered(1);
+        fs::rename(&self.path, &rotated)?;
+        Ok(Some(rotated))
+    }
+
+    /// Delete the oldest of the files rotated by date beyond `max_files`.
+    fn prune_dated(&self) -> io::Result<()> {
+        let Some(max_files) = self.rolling.max_files else {
+            return Ok(());
+        };
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name()) else {
+            return Ok(());
+        };
+        let dir = match dir.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => dir,
+        };
+        let prefix = format!("{}.", name.to_string_lossy());
+        let mut rotated: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|file_name| {
+                file_name.strip_prefix(&prefix).is_some_and(|date| {
+                    let date = date.strip_suffix(".gz").unwrap_or(date);
+                    !date.is_empty() && date.chars().all(|c| c.is_ascii_digit() || c == '-')
+                })
+            })
+            .collect();
+        // The dates sort chronologically as text, so the oldest come first.
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(max_files);
+        for file_name in &rotated[..excess] {
+            fs::remove_file(dir.join(file_name))?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.is_due(buf.len()) {
+            // Keep appending to the current file rather than losing the event.
+            let _ = self.rotate();
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Compress the file at `path` to `path.gz`, and remove it once done.
+fn compress(path: &Path) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let compressed = File::create(suffixed(path, "gz"))?;
+    let mut encoder = GzEncoder::new(io::BufWriter::new(compressed), Compression::default());
+    io::copy(&mut file, &mut encoder)?;
+    encoder.finish()?.flush()?;
+    fs::remove_file(path)
+}
+
+/// The period of `rotation` that `time` falls in, as the suffix of the files rotated in it.
+fn period(rotation: Rotation, time: DateTime<Local>) -> String {
+    match rotation {
+        Rotation::Daily => time.format("%Y-%m-%d").to_string(),
+        Rotation::Hourly => time.format("%Y-%m-%d-%H").to_string(),
+        Rotation::Size(_) => String::new(),
+    }
+}
+
+/// `path` with `.suffix` appended to its file name.
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size_rolling(max: u64, max_files: Option<usize>) -> Rolling {
+        Rolling {
+            rotation: Rotation::Size(max),
+            max_files,
+            compress: false,
+        }
+    }
+
+    #[test]
+    fn test_size_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        let mut file = RollingFile::open(&path, size_rolling(16, Some(2))).unwrap();
+        for event in [
+            "event 1\n",
+            "event 2\n",
+            "event 3\n",
+            "event 4\n",
+            "event 5\n",
+        ] {
+            file.write_all(event.as_bytes()).unwrap();
+        }
+        let read = |suffix: &str| fs::read_to_string(suffixed(&path, suffix)).ok();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "event 5\n");
+        // Rotated files are capped at the size, and only the two most recent are kept.
+        assert_eq!(read("1").as_deref(), Some("event 3\nevent 4\n"));
+        assert_eq!(read("2").as_deref(), Some("event 1\nevent 2\n"));
+        assert_eq!(read("3"), None);
+    }
+
+    #[test]
+    fn test_size_rotation_of_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        fs::write(&path, "earlier run\n").unwrap();
+        let mut file = RollingFile::open(&path, size_rolling(16, None)).unwrap();
+        file.write_all(b"event 1\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "event 1\n");
+        assert_eq!(
+            fs::read_to_string(suffixed(&path, "1")).unwrap(),
+            "earlier run\n"
+        );
+    }
+
+    #[test]
+    fn test_daily_rotation_prunes_old_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        for date in ["2023-07-23", "2023-07-24", "2023-07-25"] {
+            fs::write(suffixed(&path, date), date).unwrap();
+        }
+        fs::write(dir.path().join("forc.log.unrelated"), "").unwrap();
+        let rolling = Rolling {
+            rotation: Rotation::Daily,
+            max_files: Some(2),
+            compress: false,
+        };
+        let mut file = RollingFile::open(&path, rolling).unwrap();
+        file.write_all(b"yesterday\n").unwrap();
+        // Pretend the file was started on an earlier day.
+        file.period = "2023-07-26".to_string();
+        file.write_all(b"today\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "today\n");
+        assert_eq!(
+            fs::read_to_string(suffixed(&path, "2023-07-26")).unwrap(),
+            "yesterday\n"
+        );
+        assert!(suffixed(&path, "2023-07-25").exists());
+        assert!(!suffixed(&path, "2023-07-24").exists());
+        assert!(!suffixed(&path, "2023-07-23").exists());
+        assert!(dir.path().join("forc.log.unrelated").exists());
+    }
+
+    #[test]
+    fn test_compressed_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        let rolling = Rolling {
+            compress: true,
+            ..size_rolling(16, Some(2))
+        };
+        let mut file = RollingFile::open(&path, rolling).unwrap();
+        for event in [
+            "event 1\n",
+            "event 2\n",
+            "event 3\n",
+            "event 4\n",
+            "event 5\n",
+        ] {
+            file.write_all(event.as_bytes()).unwrap();
+        }
+        file.compressing.take().unwrap().join().unwrap();
+
+        let decompress = |suffix: &str| {
+            let compressed = File::open(suffixed(&path, suffix)).unwrap();
+            let mut contents = String::new();
+            io::Read::read_to_string(&mut flate2::read::GzDecoder::new(compressed), &mut contents)
+                .unwrap();
+            contents
+        };
+        assert_eq!(decompress("1.gz"), "event 3\nevent 4\n");
+        assert_eq!(decompress("2.gz"), "event 1\nevent 2\n");
+        assert!(!suffixed(&path, "1").exists());
+        // The compressed files count towards the files kept.
+        assert!(!suffixed(&path, "3.gz").exists());
+    }
+}
@@ -0,0 +1,226 @@
+//! Shipping the log output to a collector over a socket, see `TracingWriterMode::Socket`.
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// The most output kept while the collector can't be reached. The oldest lines are dropped first.
+const MAX_PENDING: usize = 1 << 20;
+
+/// How long connecting, or sending one event, may take before the collector is given up on.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long after failing to reach the collector it is connected to again, so that an unreachable
+/// one doesn't slow down every event.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where `TracingWriterMode::Socket` sends the log output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketTarget {
+    /// A TCP endpoint, e.g. `127.0.0.1:5170`.
+    Tcp(SocketAddr),
+    /// A Unix domain socket at the given path.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl SocketTarget {
+    fn connect(&self) -> io::Result<Connection> {
+        match self {
+            SocketTarget::Tcp(addr) => {
+                let stream = TcpStream::connect_timeout(addr, TIMEOUT)?;
+                stream.set_write_timeout(Some(TIMEOUT))?;
+                Ok(Connection::Tcp(stream))
+            }
+            #[cfg(unix)]
+            SocketTarget::Unix(path) => {
+                let stream = UnixStream::connect(path)?;
+                stream.set_write_timeout(Some(TIMEOUT))?;
+                Ok(Connection::Unix(stream))
+            }
+        }
+    }
+}
+
+/// An open connection to the collector.
+enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.write(buf),
+        }
+    }
+}
+
+/// Sends the output written to it to `target` as it's flushed, i.e. once per event.
+///
+/// The connection is made with the first event, and made again after it fails. Until then the
+/// output is kept, up to `MAX_PENDING` bytes, and failures are never reported, so that a missing
+/// collector doesn't fail the build.
+pub(crate) struct SocketWriter {
+    target: SocketTarget,
+    connection: Option<Connection>,
+    // The output not sent yet.
+    pending: Vec<u8>,
+    // When the collector last couldn't be reached.
+    failed_at: Option<Instant>,
+}
+
+impl SocketWriter {
+    pub(crate) fn new(target: SocketTarget) -> Self {
+        Self {
+            target,
+            connection: None,
+            pending: Vec::new(),
+            failed_at: None,
+        }
+    }
+
+    /// The connection to the collector, connecting if there is none and it's time to retry.
+    fn connection(&mut self) -> Option<&mut Connection> {
+        if self.connection.is_none() {
+            if self
+                .failed_at
+                .is_some_and(|failed_at| failed_at.elapsed() < RETRY_INTERVAL)
+            {
+                return None;
+            }
+            match self.target.connect() {
+                Ok(connection) => self.connection = Some(connection),
+                Err(_) => {
+                    self.failed_at = Some(Instant::now());
+                    return None;
+                }
+            }
+        }
+        self.connection.as_mut()
+    }
+
+    /// Send as much of the pending output as the collector takes.
+    fn send(&mut self) {
+        while !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            let written = match self.connection() {
+                Some(connection) => connection.write(&pending),
+                None => Ok(0),
+            };
+            self.pending = pending;
+            match written {
+                Ok(0) if self.connection.is_none() => return,
+                Ok(written) if written > 0 => {
+                    self.pending.drain(..written);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                // The collector went away, or stopped reading.
+                _ => {
+                    self.connection = None;
+                    self.failed_at = Some(Instant::now());
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Write for SocketWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if let Some(excess) = self.pending.len().checked_sub(MAX_PENDING) {
+            // Drop whole lines, so that the collector never receives part of one.
+            let end = self.pending[excess..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .map_or(self.pending.len(), |newline| excess + newline + 1);
+            self.pending.drain(..end);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        build_subscriber, LogFormat, TracingSubscriberOptions, TracingWriterMode, ENV_LOCK,
+    };
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_socket_writer_mode() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var("RUST_LOG");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Socket(SocketTarget::Tcp(addr)))
+            .format(LogFormat::Compact)
+            .display_level(true)
+            .ansi(true)
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "forc", "Unused variable");
+            tracing::info!(target: "forc", "Compiling");
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        // One connection for all events, without color.
+        assert_eq!(lines.next().unwrap().unwrap(), " WARN Unused variable");
+        assert_eq!(lines.next().unwrap().unwrap(), " INFO Compiling");
+    }
+
+    #[test]
+    fn test_socket_writer_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        // Nobody is listening, so the output is kept.
+        let mut writer = SocketWriter::new(SocketTarget::Tcp(addr));
+        writer.write_all(b"first\n").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.pending, b"first\n");
+        writer.write_all(b"second\n").unwrap();
+        writer.flush().unwrap();
+        assert!(writer.connection.is_none());
+
+        let listener = TcpListener::bind(addr).unwrap();
+        writer.failed_at = None;
+        writer.write_all(b"third\n").unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "first\nsecond\nthird\n");
+    }
+
+    #[test]
+    fn test_socket_writer_drops_oldest_lines() {
+        let mut writer = SocketWriter::new(SocketTarget::Tcp(([127, 0, 0, 1], 9).into()));
+        writer.failed_at = Some(Instant::now());
+        let line = format!("{}\n", "x".repeat(999));
+        for _ in 0..MAX_PENDING / line.len() + 10 {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        assert!(writer.pending.len() <= MAX_PENDING);
+        assert!(writer.pending.starts_with(line.as_bytes()));
+    }
+}
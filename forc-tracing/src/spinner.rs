@@ -0,0 +1,263 @@
+//! An in-place spinner for long-running steps, that log events are written above.
+
+use crate::buffered::BufferedEvent;
+use crate::{print_action, symbols};
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Moves the cursor to the start of the line and erases it.
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+const UNICODE_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const ASCII_FRAMES: &[&str] = &["|", "/", "-", "\\"];
+
+// The spinner drawn on stderr, that events written to the terminal are written above.
+static ACTIVE: Mutex<Option<Arc<Mutex<State>>>> = Mutex::new(None);
+
+struct State {
+    out: Box<dyn io::Write + Send>,
+    frames: &'static [&'static str],
+    frame: usize,
+    message: String,
+    // Whether the spinner is still drawn, as events may still hold on to it after it finished.
+    drawn: bool,
+}
+
+impl State {
+    fn draw(&mut self) -> io::Result<()> {
+        write!(
+            self.out,
+            "{CLEAR_LINE}{} {}",
+            self.frames[self.frame], self.message
+        )?;
+        self.out.flush()
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.out.write_all(CLEAR_LINE.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A spinner on stderr that shows a long-running step is still making progress, and that the events
+/// logged to the terminal meanwhile are written above.
+///
+/// If stderr isn't a terminal, the message is printed once as a line of its own instead. Events
+/// written by the background threads of `TracingSubscriberOptions::non_blocking` are written above
+/// it too, as the threads write them out.
+///
+/// ```no_run
+/// use forc_tracing::Spinner;
+///
+/// let spinner = Spinner::start("Resolving dependencies");
+/// for dependency in ["core", "std"] {
+///     tracing::info!("Fetching {dependency}");
+///     spinner.tick();
+/// }
+/// spinner.finish_with("Finished", "resolving dependencies");
+/// ```
+#[must_use = "the spinner is cleared as soon as it is dropped"]
+pub struct Spinner {
+    // `None` if stderr isn't a terminal, or once the spinner is cleared.
+    state: Option<Arc<Mutex<State>>>,
+}
+
+impl Spinner {
+    /// Draw a spinner with `message` on stderr, replacing any other spinner that is active.
+    pub fn start(message: &str) -> Self {
+        let unicode = symbols::supports_unicode(|var| std::env::var(var).ok());
+        let stderr = io::stderr();
+        let tty = stderr.is_terminal();
+        let spinner = Self::start_on(Box::new(stderr), tty, unicode, message);
+        if let Some(state) = &spinner.state {
+            *lock(&ACTIVE) = Some(state.clone());
+        }
+        spinner
+    }
+
+    fn start_on(
+        mut out: Box<dyn io::Write + Send>,
+        tty: bool,
+        unicode: bool,
+        message: &str,
+    ) -> Self {
+        if !tty {
+            // The message is only informational, so a failed write doesn't stop the work.
+            let _ = writeln!(out, "{message}");
+            return Self { state: None };
+        }
+        let mut state = State {
+            out,
+            frames: if unicode {
+                UNICODE_FRAMES
+            } else {
+                ASCII_FRAMES
+            },
+            frame: 0,
+            message: message.to_string(),
+            drawn: true,
+        };
+        let _ = state.draw();
+        Self {
+            state: Some(Arc::new(Mutex::new(state))),
+        }
+    }
+
+    /// Advance the spinner by one frame.
+    pub fn tick(&self) {
+        if let Some(state) = &self.state {
+            let mut state = lock(state);
+            state.frame = (state.frame + 1) % state.frames.len();
+            let _ = state.draw();
+        }
+    }
+
+    /// Clear the spinner, and print `message` with `verb` as its status, see `print_action`.
+    pub fn finish_with(mut self, verb: &str, message: &str) {
+        self.clear();
+        print_action(verb, message);
+    }
+
+    fn clear(&mut self) {
+        let Some(state) = self.state.take() else {
+            return;
+        };
+        {
+            let mut active = lock(&ACTIVE);
+            if active
+                .as_ref()
+                .is_some_and(|active| Arc::ptr_eq(active, &state))
+            {
+                *active = None;
+            }
+        }
+        let mut state = lock(&state);
+        state.drawn = false;
+        let _ = state.clear();
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// A writer for one event to a terminal that writes it above the active spinner, if any, using
+/// the writers made by `make_writer`, e.g. by locking the stream, once the spinner is locked.
+pub(crate) fn above_spinner<'a>(
+    make_writer: impl Fn() -> Box<dyn io::Write + 'a> + 'a,
+) -> Option<Box<dyn io::Write + 'a>> {
+    let spinner = lock(&ACTIVE).clone()?;
+    Some(Box::new(above(spinner, make_writer)))
+}
+
+/// A writer for one event that writes it with `spinner` cleared, with a writer made by
+/// `make_writer`.
+fn above<'a>(
+    spinner: Arc<Mutex<State>>,
+    make_writer: impl Fn() -> Box<dyn io::Write + 'a> + 'a,
+) -> impl io::Write + 'a {
+    // The writer is made only once the spinner is locked.
+    BufferedEvent::new(io::sink(), move |buffer: &[u8], _: &mut io::Sink| {
+        // The spinner is locked before the stream, in the same order as it is drawn in, so that
+        // the two can't deadlock.
+        let mut spinner = lock(&spinner);
+        if spinner.drawn {
+            spinner.clear()?;
+        }
+        let mut inner = make_writer();
+        inner.write_all(buffer)?;
+        inner.flush()?;
+        if spinner.drawn {
+            spinner.draw()?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::writer::AboveTerminal;
+    use crate::ENV_LOCK;
+
+    fn start(capture: &CapturingWriter, tty: bool) -> Spinner {
+        Spinner::start_on(Box::new(capture.clone()), tty, false, "Resolving")
+    }
+
+    #[test]
+    fn test_spinner_without_terminal() {
+        let capture = CapturingWriter::new();
+        let spinner = start(&capture, false);
+        spinner.tick();
+        spinner.finish_with("Finished", "resolving");
+        assert_eq!(capture.contents(), "Resolving\n");
+    }
+
+    #[test]
+    fn test_spinner_clears_on_finish() {
+        let capture = CapturingWriter::new();
+        let spinner = start(&capture, true);
+        spinner.tick();
+        spinner.finish_with("Finished", "resolving");
+        assert_eq!(
+            capture.contents(),
+            "\r\x1b[2K| Resolving\r\x1b[2K/ Resolving\r\x1b[2K"
+        );
+    }
+
+    #[test]
+    fn test_events_are_written_above_spinner() {
+        let capture = CapturingWriter::new();
+        let mut spinner = start(&capture, true);
+        let events = CapturingWriter::new();
+        let state = spinner.state.clone().unwrap();
+        let mut writer = {
+            let events = events.clone();
+            above(state.clone(), move || Box::new(events.clone()))
+        };
+        writer.write_all(b"Fetching std\n").unwrap();
+        drop(writer);
+        assert_eq!(events.contents(), "Fetching std\n");
+        assert_eq!(
+            capture.contents(),
+            "\r\x1b[2K| Resolving\r\x1b[2K\r\x1b[2K| Resolving"
+        );
+
+        // An event that was started before the spinner finished doesn't draw it again.
+        let mut writer = above(state, move || Box::new(events.clone()));
+        spinner.clear();
+        writer.write_all(b"Fetching core\n").unwrap();
+        drop(writer);
+        assert!(capture.contents().ends_with("| Resolving\r\x1b[2K"));
+    }
+
+    #[test]
+    fn test_non_blocking_events_are_written_above_spinner() {
+        // Other tests writing to stdout or stderr meanwhile would clear and draw the spinner too.
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let capture = CapturingWriter::new();
+        let mut spinner = start(&capture, true);
+        *lock(&ACTIVE) = spinner.state.clone();
+        let events = CapturingWriter::new();
+        let (mut writer, worker) = tracing_appender::non_blocking(AboveTerminal::new({
+            let events = events.clone();
+            move || Box::new(events.clone()) as Box<dyn io::Write>
+        }));
+        writer.write_all(b"Fetching std\n").unwrap();
+        // Waits for the background thread to write the event.
+        drop(worker);
+        spinner.clear();
+        assert_eq!(events.contents(), "Fetching std\n");
+        assert_eq!(
+            capture.contents(),
+            "\r\x1b[2K| Resolving\r\x1b[2K\r\x1b[2K| Resolving\r\x1b[2K"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Cargo-style status lines, e.g. `   Compiling my_contract (/path/to/my_contract)`.
+
+use crate::align::{pad_visible_start, truncate_middle, visible_width};
+use crate::{color, theme, wrap, Color};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+/// The verbs used for status lines across forc.
+pub const STANDARD_ACTION_VERBS: &[&str] = &[
+    "Building",
+    "Compiling",
+    "Creating",
+    "Deploying",
+    "Downloading",
+    "Error",
+    "Fetching",
+    "Finished",
+    "Installing",
+    "Removing",
+    "Running",
+    "Testing",
+    "Updating",
+    "Warning",
+];
+
+/// The width the verb column is right-aligned to, that of the longest standard verb.
+pub const ACTION_WIDTH: usize = longest(STANDARD_ACTION_VERBS);
+
+const fn longest(verbs: &[&str]) -> usize {
+    let mut longest = 0;
+    let mut i = 0;
+    while i < verbs.len() {
+        if verbs[i].len() > longest {
+            longest = verbs[i].len();
+        }
+        i += 1;
+    }
+    longest
+}
+
+/// Prints status lines with the verb right-aligned to a fixed width, so successive lines line up.
+///
+/// The width is `ACTION_WIDTH` unless verbs are registered with `register_verbs` or it is set with
+/// `set_action_width`. Verbs longer than the width are not truncated, the message just starts later.
+///
+/// ```no_run
+/// let mut printer = forc_tracing::StatusPrinter::new();
+/// printer.register_verbs(&["Verifying", "Deploying"]);
+/// printer.print("Verifying", "my_contract");
+/// ```
+///
+/// The verb is painted bold in the `action` color of the theme, or in its `warn` and `error` colors
+/// for the `Warning` and `Error` verbs, which are logged at the WARN and ERROR levels respectively.
+#[derive(Clone, Debug)]
+pub struct StatusPrinter {
+    width: usize,
+    // The length of the longest verb registered, if any.
+    registered: Option<usize>,
+    truncate_messages: bool,
+}
+
+impl Default for StatusPrinter {
+    fn default() -> Self {
+        Self {
+            width: ACTION_WIDTH,
+            registered: None,
+            truncate_messages: false,
+        }
+    }
+}
+
+impl StatusPrinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Align the verbs to the longest of `verbs` and of those registered before, instead of the
+    /// standard verbs. Overrides the width set with `set_action_width`.
+    pub fn register_verbs(&mut self, verbs: &[&str]) {
+        let longest = verbs.iter().map(|verb| verb.len()).max().unwrap_or(0);
+        let registered = self.registered.map_or(longest, |len| len.max(longest));
+        self.registered = Some(registered);
+        self.width = registered;
+    }
+
+    /// Align the verbs to `width` columns.
+    pub fn set_action_width(&mut self, width: usize) {
+        self.width = width;
+    }
+
+    /// Whether to cut the middle out of messages that would make the line wider than the terminal,
+    /// with `truncate_middle`, so that both ends of e.g. long paths stay visible. The verb is never
+    /// cut.
+    /// Has no effect if stdout isn't a terminal. Off by default, so that the lines wrap instead.
+    pub fn set_truncate_messages(&mut self, truncate: bool) {
+        self.truncate_messages = truncate;
+    }
+
+    /// Print `message` with `verb` as its status.
+    pub fn print(&self, verb: &str, message: &str) {
+        let level = verb_level(verb);
+        let terminal_width = self
+            .truncate_messages
+            .then(wrap::detected_terminal_width)
+            .flatten();
+        let message = self.fit(verb, message, terminal_width);
+        let line = self.format(verb, &message, color::level_color(&level));
+        match level {
+            Level::ERROR => tracing::error!("{}", line),
+            Level::WARN => tracing::warn!("{}", line),
+            _ => tracing::info!("{}", line),
+        }
+    }
+
+    /// `message` truncated so that its status line takes up at most `terminal_width` columns, if
+    /// given.
+    fn fit(&self, verb: &str, message: &str, terminal_width: Option<usize>) -> String {
+        match terminal_width {
+            Some(width) => {
+                let verb_width = visible_width(verb).max(self.width);
+                truncate_middle(message, width.saturating_sub(verb_width + 1))
+            }
+            None => message.to_string(),
+        }
+    }
+
+    pub(crate) fn format(&self, verb: &str, message: &str, ansi: bool) -> String {
+        let verb = pad_visible_start(verb, self.width);
+        let verb = if ansi {
+            let style = verb_colour(verb_level(verb.trim_start()))
+                .on_default()
+                .bold();
+            color::paint(&verb, style)
+        } else {
+            verb
+        };
+        format!("{verb} {message}")
+    }
+}
+
+/// Print `message` with `verb` as its status, aligned to the standard verbs.
+///
+/// ```no_run
+/// forc_tracing::print_action("Compiling", "my_contract (/path/to/my_contract)");
+/// ```
+pub fn print_action(verb: &str, message: &str) {
+    StatusPrinter::new().print(verb, message)
+}
+
+/// Measures how long a command takes, to print a status line with the duration at its end.
+///
+/// ```no_run
+/// let timer = forc_tracing::CommandTimer::start();
+/// // Build the package.
+/// timer.finish("Finished"); // `   Finished in 3.42s`
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CommandTimer {
+    start: Instant,
+}
+
+impl CommandTimer {
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    /// How long it has been since the timer was started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Print the status line `verb` with how long the command took, like `print_action`.
+    pub fn finish(self, verb: &str) {
+        print_action(verb, &format!("in {}", format_duration(self.elapsed())));
+    }
+}
+
+/// `duration` for a person to read: in milliseconds under a second, with two decimals under a
+/// millisecond, in seconds with two decimals under a minute, and in minutes and seconds otherwise,
+/// e.g. `0.42ms`, `120ms`, `3.42s` or `2m 05s`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    // Each unit is rounded to before checking whether it fits, so that e.g. 999.7ms is `1.00s`.
+    let rounded = |unit: u128| (nanos + unit / 2) / unit;
+    let hundredths_ms = rounded(10_000);
+    if hundredths_ms < 100 {
+        return format!("0.{hundredths_ms:02}ms");
+    }
+    let ms = rounded(1_000_000);
+    if ms < 1000 {
+        return format!("{ms}ms");
+    }
+    let hundredths_s = rounded(10_000_000);
+    if hundredths_s < 6000 {
+        return format!("{}.{:02}s", hundredths_s / 100, hundredths_s % 100);
+    }
+    let secs = rounded(1_000_000_000);
+    format!("{}m {:02}s", secs / 60, secs % 60)
+}
+
+fn verb_level(verb: &str) -> Level {
+    if verb.eq_ignore_ascii_case("error") {
+        Level::ERROR
+    } else if verb.eq_ignore_ascii_case("warning") {
+        Level::WARN
+    } else {
+        Level::INFO
+    }
+}
+
+fn verb_colour(level: Level) -> Color {
+    let theme = theme::theme();
+    match level {
+        Level::ERROR => theme.error,
+        Level::WARN => theme.warn,
+        _ => theme.action,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ENV_LOCK;
+    use anstyle::AnsiColor;
+
+    #[test]
+    fn test_action_padding() {
+        let printer = StatusPrinter::new();
+        assert_eq!(ACTION_WIDTH, "Downloading".len());
+        assert_eq!(
+            printer.format("Compiling", "my_contract", false),
+            "  Compiling my_contract"
+        );
+        assert_eq!(
+            printer.format("Downloading", "std", false),
+            "Downloading std"
+        );
+    }
+
+    #[test]
+    fn test_registered_verbs() {
+        let mut printer = StatusPrinter::new();
+        printer.register_verbs(&["Verifying", "Deploying"]);
+        printer.register_verbs(&["Built"]);
+        assert_eq!(
+            printer.format("Verifying", "my_contract", false),
+            "Verifying my_contract"
+        );
+        assert_eq!(printer.format("Built", "std", false), "    Built std");
+        // Longer verbs aren't truncated.
+        assert_eq!(
+            printer.format("Downloading", "std", false),
+            "Downloading std"
+        );
+
+        printer.set_action_width(12);
+        assert_eq!(
+            printer.format("Deploying", "my_contract", false),
+            "   Deploying my_contract"
+        );
+    }
+
+    #[test]
+    fn test_truncated_message() {
+        let printer = StatusPrinter::new();
+        let path = "/very/long/path/to/my_contract";
+        assert_eq!(printer.fit("Compiling", path, None), path);
+        assert_eq!(printer.fit("Compiling", path, Some(80)), path);
+        let message = printer.fit("Compiling", path, Some(32));
+        assert_eq!(message, "/very/long…_contract");
+        let line = printer.format("Compiling", &message, false);
+        assert_eq!(visible_width(&line), 32);
+        // Longer verbs than the width aligned to take their own width.
+        let message = printer.fit("Synchronizing", "编译合约成功", Some(22));
+        assert_eq!(message, "编译…功");
+        assert!(visible_width(&printer.format("Synchronizing", &message, false)) <= 22);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        let durations = [
+            Duration::ZERO,
+            Duration::from_nanos(420_400),
+            Duration::from_micros(999),
+            Duration::from_micros(1_600),
+            Duration::from_millis(120),
+            Duration::from_micros(999_700),
+            Duration::from_millis(3_416),
+            Duration::from_millis(59_996),
+            Duration::from_secs(125),
+            Duration::from_secs(3_600),
+        ]
+        .map(format_duration);
+        assert_eq!(
+            durations,
+            [
+                "0.00ms", "0.42ms", "1ms", "2ms", "120ms", "1.00s", "3.42s", "1m 00s", "2m 05s",
+                "60m 00s",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_action_colour() {
+        // Holds off `set_theme` from changing the colors meanwhile.
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let printer = StatusPrinter::new();
+        assert_eq!(
+            printer.format("Finished", "release", true),
+            format!(
+                "{} release",
+                color::paint("   Finished", AnsiColor::Green.on_default().bold())
+            )
+        );
+        assert_eq!(
+            printer.format("Error", "failed", true),
+            format!(
+                "{} failed",
+                color::paint("      Error", AnsiColor::Red.on_default().bold())
+            )
+        );
+        assert_eq!(
+            printer.format("Warning", "unused", true),
+            format!(
+                "{} unused",
+                color::paint("    Warning", AnsiColor::Yellow.on_default().bold())
+            )
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! The colors and styles of the public API, and pre-painted text for building up status lines,
+//! e.g. a dimmed version after a package name.
+//!
+//! These are the crate's own, converted to `anstyle`'s as text is painted, so that the crate
+//! rendering them can change without changing the API.
+
+use crate::color;
+use tracing::Level;
+
+/// A color to paint text in: one of the 16 basic colors, whose exact shade the terminal decides, an
+/// entry of the 256-color palette, or an RGB color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    /// The entry of the 256-color palette with the given index. The first 16 are the basic colors.
+    Fixed(u8),
+    /// The color with the given red, green and blue components.
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// The style of text in this color, on the default background.
+    pub const fn on_default(self) -> Style {
+        Style::new().fg(self)
+    }
+}
+
+impl From<Color> for anstyle::Color {
+    fn from(color: Color) -> Self {
+        use anstyle::AnsiColor;
+        let ansi = anstyle::Color::Ansi;
+        match color {
+            Color::Black => ansi(AnsiColor::Black),
+            Color::Red => ansi(AnsiColor::Red),
+            Color::Green => ansi(AnsiColor::Green),
+            Color::Yellow => ansi(AnsiColor::Yellow),
+            Color::Blue => ansi(AnsiColor::Blue),
+            Color::Magenta => ansi(AnsiColor::Magenta),
+            Color::Cyan => ansi(AnsiColor::Cyan),
+            Color::White => ansi(AnsiColor::White),
+            Color::BrightBlack => ansi(AnsiColor::BrightBlack),
+            Color::BrightRed => ansi(AnsiColor::BrightRed),
+            Color::BrightGreen => ansi(AnsiColor::BrightGreen),
+            Color::BrightYellow => ansi(AnsiColor::BrightYellow),
+            Color::BrightBlue => ansi(AnsiColor::BrightBlue),
+            Color::BrightMagenta => ansi(AnsiColor::BrightMagenta),
+            Color::BrightCyan => ansi(AnsiColor::BrightCyan),
+            Color::BrightWhite => ansi(AnsiColor::BrightWhite),
+            Color::Fixed(index) => anstyle::Color::Ansi256(anstyle::Ansi256Color(index)),
+            Color::Rgb(r, g, b) => anstyle::Color::Rgb(anstyle::RgbColor(r, g, b)),
+        }
+    }
+}
+
+/// The colors and effects to paint text with, e.g. `Color::Green.on_default().bold()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    /// Unstyled text, to add colors and effects to.
+    pub const fn new() -> Self {
+        Self {
+            fg: None,
+            bg: None,
+            bold: false,
+            dimmed: false,
+            italic: false,
+            underline: false,
+        }
+    }
+
+    /// In `color`.
+    pub const fn fg(mut self, color: Color) -> Self {
+        self.fg = Some(color);
+        self
+    }
+
+    /// On a background of `color`.
+    pub const fn bg(mut self, color: Color) -> Self {
+        self.bg = Some(color);
+        self
+    }
+
+    pub const fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub const fn dimmed(mut self) -> Self {
+        self.dimmed = true;
+        self
+    }
+
+    pub const fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    pub const fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+}
+
+impl From<Style> for anstyle::Style {
+    fn from(style: Style) -> Self {
+        use anstyle::Effects;
+        let effects = [
+            (style.bold, Effects::BOLD),
+            (style.dimmed, Effects::DIMMED),
+            (style.italic, Effects::ITALIC),
+            (style.underline, Effects::UNDERLINE),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(Effects::new(), |effects, (_, effect)| effects | effect);
+        anstyle::Style::new()
+            .fg_color(style.fg.map(Into::into))
+            .bg_color(style.bg.map(Into::into))
+            .effects(effects)
+    }
+}
+
+/// `text` dimmed, e.g. for secondary details like versions and paths, see `styled`.
+pub fn dim(text: &str) -> String {
+    styled(text, &[Style::new().dimmed()])
+}
+
+/// `text` in bold, for emphasis, see `styled`.
+pub fn bold(text: &str) -> String {
+    styled(text, &[Style::new().bold()])
+}
+
+/// `text` painted with all of `styles` combined, e.g. `&[Style::new().bold(),
+/// Color::Green.on_default()]` for bold green, a later color taking the place of an earlier
+/// one. The text is returned as it is if stdout doesn't show color, including when it is turned
+/// off by `set_global_color`.
+pub fn styled(text: &str, styles: &[Style]) -> String {
+    match color::level_color(&Level::INFO) {
+        true => color::paint(text, combine(styles)),
+        false => text.to_string(),
+    }
+}
+
+fn combine(styles: &[Style]) -> Style {
+    styles.iter().fold(Style::new(), |combined, style| Style {
+        fg: style.fg.or(combined.fg),
+        bg: style.bg.or(combined.bg),
+        bold: combined.bold || style.bold,
+        dimmed: combined.dimmed || style.dimmed,
+        italic: combined.italic || style.italic,
+        underline: combined.underline || style.underline,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ENV_LOCK;
+
+    #[test]
+    fn test_styled() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        color::set_level_color(&Level::INFO, true);
+        color::set_global_color(true);
+        let painted = [
+            dim("v0.46.0"),
+            bold("my_contract"),
+            styled(
+                "Finished",
+                &[
+                    Color::Red.on_default(),
+                    Style::new().bold(),
+                    Color::Green.on_default(),
+                ],
+            ),
+        ];
+        color::set_level_color(&Level::INFO, false);
+        assert_eq!(
+            painted,
+            [
+                "\x1b[2mv0.46.0\x1b[0m",
+                "\x1b[1mmy_contract\x1b[0m",
+                "\x1b[1m\x1b[32mFinished\x1b[0m",
+            ]
+        );
+        assert_eq!(dim("v0.46.0"), "v0.46.0");
+        assert_eq!(
+            styled(
+                "Finished",
+                &[Style::new().bold(), Color::Green.on_default()]
+            ),
+            "Finished"
+        );
+    }
+
+    #[test]
+    fn test_color_escape_codes() {
+        let painted = |style: Style| color::paint("ok", style);
+        let named = [
+            (Color::Black, "30"),
+            (Color::Red, "31"),
+            (Color::Green, "32"),
+            (Color::Yellow, "33"),
+            (Color::Blue, "34"),
+            (Color::Magenta, "35"),
+            (Color::Cyan, "36"),
+            (Color::White, "37"),
+            (Color::BrightBlack, "90"),
+            (Color::BrightRed, "91"),
+            (Color::BrightGreen, "92"),
+            (Color::BrightYellow, "93"),
+            (Color::BrightBlue, "94"),
+            (Color::BrightMagenta, "95"),
+            (Color::BrightCyan, "96"),
+            (Color::BrightWhite, "97"),
+            (Color::Fixed(208), "38;5;208"),
+            (Color::Rgb(255, 128, 64), "38;2;255;128;64"),
+        ];
+        for (color, code) in named {
+            assert_eq!(
+                painted(color.on_default()),
+                format!("\x1b[{code}mok\x1b[0m"),
+                "{color:?}"
+            );
+        }
+        assert_eq!(
+            painted(Style::new().bg(Color::Blue).italic().underline()),
+            "\x1b[3m\x1b[4m\x1b[44mok\x1b[0m"
+        );
+    }
+}
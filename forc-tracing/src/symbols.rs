@@ -0,0 +1,217 @@
+//! Colored symbols in front of events in place of level words, see
+//! `TracingSubscriberOptions::level_symbols` and `LevelStyle::Short`.
+
+use crate::color;
+use anstyle::AnsiColor;
+use std::fmt;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Prefixes the events formatted by the wrapped formatter with the symbol of their level.
+pub(crate) struct LevelSymbols<E> {
+    inner: E,
+    symbols: Symbols,
+}
+
+/// The set of symbols shown for the levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Symbols {
+    /// `✗`, `⚠`, `ℹ`, `•` and `·`.
+    Unicode,
+    /// The ASCII equivalents of the Unicode symbols, `x`, `!`, `i`, `*` and `.`.
+    Ascii,
+    /// The initial of the level in brackets, e.g. `[W]`.
+    Tags,
+}
+
+impl<E> LevelSymbols<E> {
+    pub(crate) fn new(inner: E, symbols: Symbols) -> Self {
+        Self { inner, symbols }
+    }
+}
+
+impl<S, N, E> FormatEvent<S, N> for LevelSymbols<E>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+    E: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let level = event.metadata().level();
+        let symbol = symbol(level, self.symbols);
+        if writer.has_ansi_escapes() {
+            let style = level_color(level).on_default().bold();
+            write!(writer, "{} ", color::paint(symbol, style))?;
+        } else {
+            write!(writer, "{symbol} ")?;
+        }
+        self.inner.format_event(ctx, writer, event)
+    }
+}
+
+/// The symbol of `symbols` shown for events at `level`.
+fn symbol(level: &Level, symbols: Symbols) -> &'static str {
+    match (*level, symbols) {
+        (Level::ERROR, Symbols::Unicode) => "✗",
+        (Level::ERROR, Symbols::Ascii) => "x",
+        (Level::ERROR, Symbols::Tags) => "[E]",
+        (Level::WARN, Symbols::Unicode) => "⚠",
+        (Level::WARN, Symbols::Ascii) => "!",
+        (Level::WARN, Symbols::Tags) => "[W]",
+        (Level::INFO, Symbols::Unicode) => "ℹ",
+        (Level::INFO, Symbols::Ascii) => "i",
+        (Level::INFO, Symbols::Tags) => "[I]",
+        (Level::DEBUG, Symbols::Unicode) => "•",
+        (Level::DEBUG, Symbols::Ascii) => "*",
+        (Level::DEBUG, Symbols::Tags) => "[D]",
+        (Level::TRACE, Symbols::Unicode) => "·",
+        (Level::TRACE, Symbols::Ascii) => ".",
+        (Level::TRACE, Symbols::Tags) => "[T]",
+    }
+}
+
+/// The same colors as `tracing_subscriber` uses for the level words.
+fn level_color(level: &Level) -> AnsiColor {
+    match *level {
+        Level::ERROR => AnsiColor::Red,
+        Level::WARN => AnsiColor::Yellow,
+        Level::INFO => AnsiColor::Green,
+        Level::DEBUG => AnsiColor::Blue,
+        Level::TRACE => AnsiColor::Magenta,
+    }
+}
+
+/// Whether the terminal can be expected to render the symbols, judging by the environment
+/// variables returned by `var`.
+///
+/// Unix terminals are assumed to if the locale uses UTF-8, and Windows consoles only if they are
+/// known to, as the legacy console doesn't render them in its default font.
+pub(crate) fn supports_unicode(var: impl Fn(&str) -> Option<String>) -> bool {
+    if var("TERM").is_some_and(|term| term == "linux" || term == "dumb") {
+        return false;
+    }
+    if cfg!(windows) {
+        return var("WT_SESSION").is_some()
+            || var("TERM_PROGRAM").is_some_and(|program| program == "vscode");
+    }
+    // The first of these that is set decides the character encoding.
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, LevelStyle, TracingInitError, TracingSubscriberOptions};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn capture_symbols(no_unicode: bool, ansi: bool) -> String {
+        let options = TracingSubscriberOptions::builder()
+            .level_symbols(true)
+            .no_unicode(no_unicode)
+            .build();
+        capture_levels(options, ansi)
+    }
+
+    fn capture_levels(options: TracingSubscriberOptions, ansi: bool) -> String {
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&options, ansi, None, capture.clone()).unwrap();
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            tracing::error!("Failed");
+            tracing::warn!("Unused");
+            tracing::info!("Compiled");
+            tracing::debug!("Resolved");
+            tracing::trace!("Parsed");
+        });
+        capture.contents()
+    }
+
+    #[test]
+    fn test_level_symbols() {
+        assert_eq!(
+            capture_symbols(false, false),
+            "✗ Failed\n⚠ Unused\nℹ Compiled\n• Resolved\n· Parsed\n"
+        );
+        let painted = capture_symbols(false, true);
+        assert!(painted.starts_with(&color::paint("✗", AnsiColor::Red.on_default().bold())));
+    }
+
+    #[test]
+    fn test_level_symbols_ascii_fallback() {
+        assert_eq!(
+            capture_symbols(true, false),
+            "x Failed\n! Unused\ni Compiled\n* Resolved\n. Parsed\n"
+        );
+    }
+
+    #[test]
+    fn test_supports_unicode() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |var: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert!(!supports_unicode(env(&[
+            ("TERM", "linux"),
+            ("LANG", "en_US.UTF-8")
+        ])));
+        if cfg!(windows) {
+            assert!(supports_unicode(env(&[("WT_SESSION", "1")])));
+            assert!(!supports_unicode(env(&[])));
+        } else {
+            assert!(supports_unicode(env(&[("LANG", "en_US.UTF-8")])));
+            assert!(!supports_unicode(env(&[("LANG", "C")])));
+            assert!(!supports_unicode(env(&[
+                ("LC_ALL", "C"),
+                ("LANG", "en_US.utf8")
+            ])));
+            assert!(!supports_unicode(env(&[])));
+        }
+    }
+
+    #[test]
+    fn test_level_style() {
+        let style = |level_style| {
+            let options = TracingSubscriberOptions::builder()
+                .level_style(level_style)
+                .build();
+            capture_levels(options, false)
+        };
+        assert_eq!(
+            style(LevelStyle::Short),
+            "[E] Failed\n[W] Unused\n[I] Compiled\n[D] Resolved\n[T] Parsed\n"
+        );
+        assert_eq!(
+            style(LevelStyle::None),
+            "Failed\nUnused\nCompiled\nResolved\nParsed\n"
+        );
+        assert!(style(LevelStyle::Full).starts_with("ERROR Failed\n WARN Unused\n"));
+    }
+
+    #[test]
+    fn test_level_style_conflicts_with_symbols() {
+        let options = TracingSubscriberOptions::builder()
+            .level_symbols(true)
+            .level_style(LevelStyle::Short)
+            .build();
+        assert!(matches!(
+            fmt_layer::<Registry, _>(&options, false, None, CapturingWriter::new()),
+            Err(TracingInitError::ConflictingLevelOptions)
+        ));
+    }
+}
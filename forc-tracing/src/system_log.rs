@@ -0,0 +1,111 @@
+//! Mirroring of the log output to the local syslog, see `TracingSubscriberOptions::syslog`.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use crate::{fmt_layer, TracingInitError, TracingSubscriberOptions};
+use std::io;
+use std::sync::{Arc, Mutex};
+use syslog::{Facility, Formatter3164, LogFormat, LoggerBackend, Severity};
+use tracing::{Level, Metadata, Subscriber};
+use tracing_subscriber::{fmt::MakeWriter, registry::LookupSpan, Layer};
+
+/// The tag the events are logged to syslog with.
+const IDENTITY: &str = "forc";
+
+type Logger = syslog::Logger<LoggerBackend, Formatter3164>;
+
+/// The layer formatting events like `options` say and sending each to the local syslog as one
+/// message, at the severity of its level.
+pub(crate) fn syslog_layer<S>(
+    options: &TracingSubscriberOptions,
+) -> Result<Box<dyn Layer<S> + Send + Sync>, TracingInitError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let logger = syslog::unix(Formatter3164 {
+        facility: Facility::LOG_USER,
+        hostname: None,
+        process: IDENTITY.to_string(),
+        pid: std::process::id(),
+    })?;
+    // Syslog timestamps the messages itself.
+    let options = TracingSubscriberOptions {
+        display_time: Some(false),
+        ..options.clone()
+    };
+    fmt_layer(
+        &options,
+        false,
+        None,
+        SyslogWriter(Arc::new(Mutex::new(logger))),
+    )
+}
+
+/// The syslog severity of events at `level`.
+fn severity(level: &Level) -> Severity {
+    match *level {
+        Level::ERROR => Severity::LOG_ERR,
+        Level::WARN => Severity::LOG_WARNING,
+        Level::INFO => Severity::LOG_INFO,
+        Level::DEBUG | Level::TRACE => Severity::LOG_DEBUG,
+    }
+}
+
+struct SyslogWriter(Arc<Mutex<Logger>>);
+
+impl<'a> MakeWriter<'a> for SyslogWriter {
+    type Writer = BoxedEvent<'a, io::Sink>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        message(&self.0, &Level::INFO)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        message(&self.0, meta.level())
+    }
+}
+
+/// A writer for one event that sends its formatted bytes to syslog as one message.
+fn message<'a>(logger: &'a Mutex<Logger>, level: &Level) -> BoxedEvent<'a, io::Sink> {
+    let severity = severity(level);
+    BufferedEvent::boxed(io::sink(), move |bytes, _| {
+        let message = String::from_utf8_lossy(bytes);
+        let message = message.trim_end_matches('\n');
+        if message.is_empty() {
+            return Ok(());
+        }
+        let mut logger = logger.lock().unwrap_or_else(|e| e.into_inner());
+        let logger = &mut *logger;
+        logger
+            .formatter
+            .format(&mut logger.backend, severity, message)
+            .map_err(|e| io::Error::other(e.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity() {
+        let severities = [
+            Level::ERROR,
+            Level::WARN,
+            Level::INFO,
+            Level::DEBUG,
+            Level::TRACE,
+        ]
+        .map(|level| severity(&level) as u8);
+        assert_eq!(
+            severities,
+            [
+                Severity::LOG_ERR,
+                Severity::LOG_WARNING,
+                Severity::LOG_INFO,
+                Severity::LOG_DEBUG,
+                Severity::LOG_DEBUG,
+            ]
+            .map(|severity| severity as u8)
+        );
+    }
+}
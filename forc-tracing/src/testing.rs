@@ -0,0 +1,124 @@
+//! Helpers for asserting on the logs of code that uses forc's subscriber, without installing it
+//! as the global default.
+
+use crate::dedupe::dedupe;
+use crate::filter::resolve_env_filter;
+use crate::{fmt_layer, TracingSubscriberOptions};
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, Layer};
+
+/// A writer that collects everything written through it into a shared buffer.
+///
+/// Clones share the buffer, so a clone can be handed to a subscriber while the original is kept
+/// to read the output back with `contents`.
+#[derive(Clone, Debug, Default)]
+pub struct CapturingWriter(Arc<Mutex<Vec<u8>>>);
+
+impl CapturingWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far, with invalid UTF-8 replaced.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer()).into_owned()
+    }
+
+    fn buffer(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl io::Write for CapturingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturingWriter {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Run `f` with a subscriber built from `options` as the default for the current thread, returning
+/// everything it logged.
+///
+/// The events are filtered and formatted like `init_tracing_subscriber` would with the same
+/// options, except that `writer_mode`, `non_blocking` and `ring_buffer_capacity` are ignored in
+/// favor of capturing all output, and color is omitted unless `ansi` is set.
+///
+/// ```
+/// use forc_tracing::{testing::with_captured_logs, TracingSubscriberOptions};
+///
+/// let output = with_captured_logs(TracingSubscriberOptions::default(), || {
+///     tracing::info!("Compiling");
+/// });
+/// assert_eq!(output, "Compiling\n");
+/// ```
+///
+/// Panics if the options are invalid, e.g. a `TimeFormat::Custom` with an invalid pattern.
+pub fn with_captured_logs(options: TracingSubscriberOptions, f: impl FnOnce()) -> String {
+    let writer = CapturingWriter::new();
+    let layer = fmt_layer(
+        &options,
+        options.ansi.unwrap_or(false),
+        None,
+        writer.clone(),
+    )
+    .expect("invalid tracing subscriber options");
+    let (layer, dedupe_guard) = dedupe(layer, options.dedupe_window);
+    let subscriber =
+        tracing_subscriber::registry().with(layer.with_filter(resolve_env_filter(&options)));
+    tracing::subscriber::with_default(subscriber, || {
+        f();
+        drop(dedupe_guard);
+    });
+    writer.contents()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LogFormat, ENV_LOCK};
+
+    #[test]
+    fn test_with_captured_logs() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let output = with_captured_logs(TracingSubscriberOptions::default(), || {
+            tracing::info!(target: "forc", "Compiling");
+            tracing::error!(target: "forc", "Failed");
+            tracing::debug!(target: "forc", "hidden");
+        });
+        assert_eq!(output, "Compiling\nFailed\n");
+
+        let options = TracingSubscriberOptions::builder()
+            .verbosity(1)
+            .format(LogFormat::Json)
+            .build();
+        let output = with_captured_logs(options, || tracing::debug!(target: "forc", "resolved"));
+        let event: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(event["level"], "DEBUG");
+        assert_eq!(event["message"], "resolved");
+
+        // Repetitions are dropped only after the level filter.
+        let options = TracingSubscriberOptions::builder()
+            .dedupe_window(std::time::Duration::from_secs(3600))
+            .build();
+        let output = with_captured_logs(options, || {
+            for _ in 0..2 {
+                tracing::debug!(target: "forc", "hidden");
+                tracing::warn!(target: "forc", "Unused");
+            }
+        });
+        assert_eq!(output, "Unused\nUnused (repeated 1 time)\n");
+    }
+}
@@ -0,0 +1,383 @@
+//! The colors of the `println_*` helpers and status lines, see `set_theme`.
+
+use crate::{color, Color};
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// Colors of the theme to replace, e.g. `info=cyan,warn=bright-yellow,error=#e61e1e`.
+const LOG_COLORS: &str = "FORC_LOG_COLORS";
+
+/// The basic colors by name, as written in `FORC_LOG_COLORS`.
+const COLOR_NAMES: [(&str, Color, Color); 8] = [
+    ("black", Color::Black, Color::BrightBlack),
+    ("red", Color::Red, Color::BrightRed),
+    ("green", Color::Green, Color::BrightGreen),
+    ("yellow", Color::Yellow, Color::BrightYellow),
+    ("blue", Color::Blue, Color::BrightBlue),
+    ("magenta", Color::Magenta, Color::BrightMagenta),
+    ("cyan", Color::Cyan, Color::BrightCyan),
+    ("white", Color::White, Color::BrightWhite),
+];
+
+/// The colors that text is painted in according to what it reports.
+///
+/// Any `Color` can be used, including 256-color palette entries and RGB colors. Those are
+/// shown only if `COLORTERM` indicates that the terminal supports truecolor, and replaced by the
+/// closest of the 8 basic colors otherwise.
+///
+/// ```
+/// use forc_tracing::{set_theme, ColorTheme};
+///
+/// set_theme(ColorTheme {
+///     success: ColorTheme::rgb(0x14, 0xc8, 0x3c),
+///     ..ColorTheme::default()
+/// });
+/// forc_tracing::println_green("Finished");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorTheme {
+    /// Informational text, i.e. `println_blue`.
+    pub info: Color,
+    /// Success, i.e. `println_green`.
+    pub success: Color,
+    /// Warnings, i.e. `println_yellow_err` and the `Warning` status verb.
+    pub warn: Color,
+    /// Errors, i.e. `println_red`, `println_red_err` and the `Error` status verb.
+    pub error: Color,
+    /// The other status verbs of `print_action`.
+    pub action: Color,
+}
+
+impl ColorTheme {
+    /// The basic colors that forc has always used.
+    pub const DEFAULT: Self = Self {
+        info: Color::Blue,
+        success: Color::Green,
+        warn: Color::Yellow,
+        error: Color::Red,
+        action: Color::Green,
+    };
+
+    /// The bright variants of the default colors, that stand out more on dark backgrounds.
+    pub const HIGH_CONTRAST: Self = Self {
+        info: Color::BrightCyan,
+        success: Color::BrightGreen,
+        warn: Color::BrightYellow,
+        error: Color::BrightRed,
+        action: Color::BrightGreen,
+    };
+
+    /// The RGB color with the given components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color::Rgb(r, g, b)
+    }
+
+    /// The theme with every color given by its RGB components.
+    pub const fn from_rgb(
+        info: (u8, u8, u8),
+        success: (u8, u8, u8),
+        warn: (u8, u8, u8),
+        error: (u8, u8, u8),
+        action: (u8, u8, u8),
+    ) -> Self {
+        Self {
+            info: Self::rgb(info.0, info.1, info.2),
+            success: Self::rgb(success.0, success.1, success.2),
+            warn: Self::rgb(warn.0, warn.1, warn.2),
+            error: Self::rgb(error.0, error.1, error.2),
+            action: Self::rgb(action.0, action.1, action.2),
+        }
+    }
+
+    /// The theme with the colors given in `spec` replaced, a comma-separated list of `key=color`
+    /// pairs as in `FORC_LOG_COLORS`. Returns the warnings for the keys that aren't colors of the
+    /// theme, which are ignored, and for the colors that can't be parsed, which are left as they
+    /// are.
+    fn with_overrides(mut self, spec: &str) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        for entry in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (key, value) = entry.split_once('=').unwrap_or((entry, ""));
+            let (key, value) = (key.trim(), value.trim());
+            let color = match key.to_ascii_lowercase().as_str() {
+                "info" => &mut self.info,
+                "success" => &mut self.success,
+                "warn" => &mut self.warn,
+                "error" => &mut self.error,
+                "action" => &mut self.action,
+                _ => {
+                    warnings.push(format!(
+                        "ignoring unknown `{LOG_COLORS}` key `{key}`, expected one of `info`, \
+                         `success`, `warn`, `error` or `action`"
+                    ));
+                    continue;
+                }
+            };
+            match parse_color(value) {
+                Some(value) => *color = value,
+                None => warnings.push(format!(
+                    "ignoring invalid `{LOG_COLORS}` color `{value}` for `{key}`, expected a \
+                     color name like `green` or `bright-green`, a number up to 255 or `#rrggbb`"
+                )),
+            }
+        }
+        (self, warnings)
+    }
+
+    /// The theme with the colors the terminal can't show replaced by the closest basic colors.
+    fn downgrade(self, truecolor: bool) -> Self {
+        let color = |color| if truecolor { color } else { basic(color) };
+        Self {
+            info: color(self.info),
+            success: color(self.success),
+            warn: color(self.warn),
+            error: color(self.error),
+            action: color(self.action),
+        }
+    }
+}
+
+impl Default for ColorTheme {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static THEME: RwLock<ColorTheme> = RwLock::new(ColorTheme::DEFAULT);
+
+/// Install `theme` for the whole process, replacing the colors of the `println_*` helpers and of
+/// `print_action`.
+///
+/// The `init_tracing_subscriber` functions replace the colors named in the `FORC_LOG_COLORS`
+/// environment variable of the theme installed at the time, e.g. with
+/// `FORC_LOG_COLORS=info=cyan,error=bright-red`, so that users can adjust them without a rebuild.
+/// Call this afterwards to override their choice.
+///
+/// The RGB and 256-color palette colors of `theme` are replaced by the closest basic colors unless
+/// `COLORTERM` is `truecolor` or `24bit` at the time of the call.
+pub fn set_theme(theme: ColorTheme) {
+    *THEME.write().unwrap_or_else(|e| e.into_inner()) = theme.downgrade(color::truecolor());
+}
+
+/// Install the theme with the colors given in `FORC_LOG_COLORS` replaced, if it is set, on top of
+/// the theme installed so far.
+///
+/// The warnings about its invalid entries are only printed the first time, in case several
+/// subscribers are initialized in a row.
+pub(crate) fn set_theme_from_env() {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    let Some(spec) = env::var(LOG_COLORS).ok().filter(|spec| !spec.is_empty()) else {
+        return;
+    };
+    let (theme, warnings) = theme().with_overrides(&spec);
+    set_theme(theme);
+    if !warnings.is_empty() && !WARNED.swap(true, Ordering::Relaxed) {
+        for warning in warnings {
+            eprintln!("warning: {warning}");
+        }
+    }
+}
+
+/// The color written as `name` in `FORC_LOG_COLORS`: a basic color like `green`, optionally with a
+/// `bright-` prefix, an entry of the 256-color palette by number or an RGB color as `#rrggbb`.
+fn parse_color(name: &str) -> Option<Color> {
+    let name = name.to_ascii_lowercase();
+    if let Some(hex) = name.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)?;
+        let [_, r, g, b] = rgb.to_be_bytes();
+        return Some(ColorTheme::rgb(r, g, b));
+    }
+    if let Ok(index) = name.parse::<u8>() {
+        return Some(Color::Fixed(index));
+    }
+    let (base, bright) = match name
+        .strip_prefix("bright-")
+        .or_else(|| name.strip_prefix("bright_"))
+    {
+        Some(base) => (base, true),
+        None => (name.as_str(), false),
+    };
+    COLOR_NAMES
+        .iter()
+        .find(|(color_name, ..)| *color_name == base)
+        .map(|(_, color, bright_color)| if bright { *bright_color } else { *color })
+}
+
+/// The theme installed by `set_theme`, or the default one.
+pub(crate) fn theme() -> ColorTheme {
+    *THEME.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The RGB values of the basic colors, as xterm shows them.
+const BASIC: [(Color, (u8, u8, u8)); 8] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::White, (229, 229, 229)),
+];
+
+/// The basic color closest to `color`.
+fn basic(color: Color) -> Color {
+    let (r, g, b) = match color {
+        // The first 16 entries of the palette are the basic colors, and then their bright variants.
+        Color::Fixed(index @ 0..=7) => return COLOR_NAMES[usize::from(index)].1,
+        Color::Fixed(index @ 8..=15) => return COLOR_NAMES[usize::from(index - 8)].2,
+        Color::Fixed(index) => palette_rgb(index),
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => return color,
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        [(r, r2), (g, g2), (b, b2)]
+            .iter()
+            .map(|&(a, b)| (i32::from(a) - i32::from(b)).pow(2))
+            .sum::<i32>()
+    };
+    let (closest, _) = BASIC
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .expect("there are basic colors");
+    *closest
+}
+
+/// The RGB values of the 6x6x6 color cube and gray-scale entries of the 256-color palette.
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match index {
+        16..=231 => {
+            let index = usize::from(index - 16);
+            (LEVELS[index / 36], LEVELS[index / 6 % 6], LEVELS[index % 6])
+        }
+        index => {
+            let gray = 8 + 10 * index.saturating_sub(232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::COLORTERM;
+    use crate::{StatusPrinter, ENV_LOCK};
+
+    /// Run `f` with `theme` installed with `COLORTERM` set to `colorterm`, then restore the default.
+    fn with_theme(theme: ColorTheme, colorterm: &str, f: impl FnOnce()) {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        env::set_var(COLORTERM, colorterm);
+        set_theme(theme);
+        env::remove_var(COLORTERM);
+        f();
+        set_theme(ColorTheme::DEFAULT);
+    }
+
+    #[test]
+    fn test_log_colors() {
+        let (theme, warnings) = ColorTheme::DEFAULT
+            .with_overrides("info=cyan, WARN=bright-yellow,error=#e61e1e,action=208,success=green");
+        assert_eq!(
+            theme,
+            ColorTheme {
+                info: Color::Cyan,
+                success: Color::Green,
+                warn: Color::BrightYellow,
+                error: ColorTheme::rgb(0xe6, 0x1e, 0x1e),
+                action: Color::Fixed(208),
+            }
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_log_colors_invalid() {
+        // Invalid colors leave those of the theme overridden, unknown keys are ignored.
+        let (theme, warnings) = ColorTheme::HIGH_CONTRAST
+            .with_overrides("info=green,warn=chartreuse,error=#12345,debug=blue,action");
+        assert_eq!(
+            theme,
+            ColorTheme {
+                info: Color::Green,
+                ..ColorTheme::HIGH_CONTRAST
+            }
+        );
+        assert_eq!(warnings.len(), 4);
+        assert!(warnings[0].contains("`chartreuse` for `warn`"));
+        assert!(warnings[1].contains("`#12345` for `error`"));
+        assert!(warnings[2].contains("unknown `FORC_LOG_COLORS` key `debug`"));
+        assert!(warnings[3].contains("`` for `action`"));
+    }
+
+    #[test]
+    fn test_log_colors_unset() {
+        let (overridden, warnings) = ColorTheme::DEFAULT.with_overrides(" , ");
+        assert_eq!(
+            (overridden, warnings.is_empty()),
+            (ColorTheme::DEFAULT, true)
+        );
+
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        for value in [None, Some("")] {
+            match value {
+                Some(value) => env::set_var(LOG_COLORS, value),
+                None => env::remove_var(LOG_COLORS),
+            }
+            set_theme_from_env();
+            assert_eq!(theme(), ColorTheme::DEFAULT);
+        }
+        env::set_var(LOG_COLORS, "success=bright-green");
+        set_theme_from_env();
+        env::remove_var(LOG_COLORS);
+        assert_eq!(theme().success, Color::BrightGreen);
+        assert_eq!(theme().info, ColorTheme::DEFAULT.info);
+        set_theme(ColorTheme::DEFAULT);
+    }
+
+    #[test]
+    fn test_rgb_theme() {
+        let rgb = ColorTheme::from_rgb(
+            (40, 90, 255),
+            (20, 200, 60),
+            (255, 200, 10),
+            (230, 30, 30),
+            (120, 10, 200),
+        );
+        with_theme(rgb, "truecolor", || {
+            assert_eq!(
+                StatusPrinter::new().format("Finished", "release", true),
+                "\x1b[1m\x1b[38;2;120;10;200m   Finished\x1b[0m release"
+            );
+            assert_eq!(
+                color::paint("passed", theme().success.on_default()),
+                "\x1b[38;2;20;200;60mpassed\x1b[0m"
+            );
+        });
+    }
+
+    #[test]
+    fn test_rgb_theme_without_truecolor() {
+        let rgb = ColorTheme {
+            success: ColorTheme::rgb(20, 200, 60),
+            error: Color::Fixed(196),
+            ..ColorTheme::HIGH_CONTRAST
+        };
+        with_theme(rgb, "", || {
+            let theme = theme();
+            assert_eq!(theme.success, Color::Green);
+            assert_eq!(theme.error, Color::Red);
+            assert_eq!(theme.warn, Color::BrightYellow);
+            assert_eq!(
+                color::paint("failed", theme.error.on_default()),
+                "\x1b[31mfailed\x1b[0m"
+            );
+        });
+    }
+}
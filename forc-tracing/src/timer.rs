@@ -0,0 +1,199 @@
+//! The timestamps that events are prefixed with.
+//!
+//! Times are formatted with `chrono`, as the local time support of `tracing_subscriber`'s own
+//! timers requires building with `--cfg unsound_local_offset`.
+
+use crate::{LogFormat, TracingInitError};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat, Utc};
+use std::fmt;
+use std::sync::{Mutex, Once};
+use std::time::Instant;
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+
+/// The format of the timestamp that events are prefixed with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// The current time in UTC, e.g. `2023-07-25T14:03:54.123456Z`.
+    Rfc3339,
+    /// The current local time, e.g. `2023-07-25T16:03:54.123456+02:00`. The offset of the local
+    /// time zone is determined when the subscriber is initialized, which must happen before any
+    /// threads are spawned. Otherwise a warning is printed and the time is in UTC instead.
+    LocalRfc3339,
+    /// The seconds elapsed since the subscriber was initialized, e.g. `   1.234567890s`.
+    Uptime,
+    /// The seconds elapsed since the previous event was written, whichever stream it went to, e.g.
+    /// `+0.123s`, to spot where a build spends its time. The first event shows `+0.000s`.
+    Delta,
+    /// The current local time formatted with a strftime-style pattern, e.g. `%H:%M:%S`. See
+    /// <https://docs.rs/chrono/0.4/chrono/format/strftime> for the supported specifiers.
+    Custom(String),
+}
+
+/// Formats the timestamp of each event.
+pub(crate) enum Timer {
+    Rfc3339,
+    // The offset of the local time zone, or `None` for UTC if it couldn't be determined.
+    LocalRfc3339(Option<FixedOffset>),
+    // When the subscriber was initialized.
+    Uptime(Instant),
+    // When the previous event was written, if any.
+    Delta(Mutex<Option<Instant>>),
+    Custom(String),
+}
+
+/// The timer for the given options, or `None` if events aren't prefixed with a timestamp.
+///
+/// The timestamp is shown if `display_time` is set, and otherwise only for the JSON and logfmt
+/// formats or if a `time_format` is given.
+pub(crate) fn timer(
+    format: LogFormat,
+    display_time: Option<bool>,
+    time_format: Option<&TimeFormat>,
+) -> Result<Option<Timer>, TracingInitError> {
+    let structured = matches!(
+        format,
+        LogFormat::Json | LogFormat::JsonArray | LogFormat::Logfmt
+    );
+    if !display_time.unwrap_or(structured || time_format.is_some()) {
+        return Ok(None);
+    }
+    let timer = match time_format {
+        // The same format as the default timer of `tracing_subscriber`.
+        None => Timer::Rfc3339,
+        Some(TimeFormat::Rfc3339) => Timer::Rfc3339,
+        Some(TimeFormat::LocalRfc3339) => Timer::LocalRfc3339(local_offset()),
+        Some(TimeFormat::Uptime) => Timer::Uptime(Instant::now()),
+        Some(TimeFormat::Delta) => Timer::Delta(Mutex::new(None)),
+        Some(TimeFormat::Custom(pattern)) => {
+            // Reject an invalid pattern up front, as it would otherwise fail every event.
+            if StrftimeItems::new(pattern).any(|item| item == Item::Error) {
+                return Err(TracingInitError::InvalidTimeFormat(pattern.clone()));
+            }
+            Timer::Custom(pattern.clone())
+        }
+    };
+    Ok(Some(timer))
+}
+
+/// The offset of the local time zone, determined once here rather than for every event.
+///
+/// The `time` crate can only determine it soundly while the process is single-threaded, e.g. when
+/// the subscriber is initialized at the start of `main`. Otherwise a warning is printed, once, and
+/// `None` is returned for the timestamps to be in UTC instead.
+fn local_offset() -> Option<FixedOffset> {
+    let offset = time::UtcOffset::current_local_offset()
+        .ok()
+        .and_then(|offset| FixedOffset::east_opt(offset.whole_seconds()));
+    if offset.is_none() {
+        static WARNING: Once = Once::new();
+        WARNING.call_once(|| {
+            eprintln!("warning: could not determine the local time zone, logging times in UTC");
+        });
+    }
+    offset
+}
+
+/// `now` in RFC 3339 format, at `offset` or in UTC with the `Z` suffix.
+fn local_rfc3339(now: DateTime<Utc>, offset: Option<FixedOffset>) -> String {
+    match offset {
+        Some(offset) => now
+            .with_timezone(&offset)
+            .to_rfc3339_opts(SecondsFormat::Micros, false),
+        None => now.to_rfc3339_opts(SecondsFormat::Micros, true),
+    }
+}
+
+impl Timer {
+    /// Write the timestamp of an event logged now to `w`.
+    pub(crate) fn write(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        match self {
+            Timer::Rfc3339 => write!(
+                w,
+                "{}",
+                Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+            ),
+            Timer::LocalRfc3339(offset) => write!(w, "{}", local_rfc3339(Utc::now(), *offset)),
+            // Like the uptime timer of `tracing_subscriber`.
+            Timer::Uptime(start) => {
+                let elapsed = start.elapsed();
+                write!(w, "{:4}.{:09}s", elapsed.as_secs(), elapsed.subsec_nanos())
+            }
+            Timer::Delta(previous) => {
+                let now = Instant::now();
+                let previous = previous
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .replace(now);
+                let elapsed = previous.map_or_else(Default::default, |previous| now - previous);
+                write!(w, "+{:.3}s", elapsed.as_secs_f64())
+            }
+            Timer::Custom(pattern) => write!(w, "{}", Local::now().format(pattern)),
+        }
+    }
+}
+
+impl FormatTime for Timer {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        self.write(w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_shown() {
+        let shown = |format, display_time, time_format: Option<TimeFormat>| {
+            timer(format, display_time, time_format.as_ref())
+                .unwrap()
+                .is_some()
+        };
+        assert!(!shown(LogFormat::Pretty, None, None));
+        assert!(shown(LogFormat::Json, None, None));
+        assert!(shown(LogFormat::Logfmt, None, None));
+        assert!(shown(LogFormat::Pretty, None, Some(TimeFormat::Uptime)));
+        assert!(!shown(
+            LogFormat::Json,
+            Some(false),
+            Some(TimeFormat::Uptime)
+        ));
+    }
+
+    #[test]
+    fn test_local_rfc3339_offset() {
+        let now = DateTime::parse_from_rfc3339("2023-07-25T14:03:54.123456Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let offset = FixedOffset::east_opt(2 * 3600);
+        assert_eq!(
+            local_rfc3339(now, offset),
+            "2023-07-25T16:03:54.123456+02:00"
+        );
+        assert_eq!(local_rfc3339(now, None), "2023-07-25T14:03:54.123456Z");
+
+        // Whether or not the offset can be determined in the multi-threaded test harness, the
+        // timestamps show one.
+        let Some(Timer::LocalRfc3339(offset)) =
+            timer(LogFormat::Pretty, None, Some(&TimeFormat::LocalRfc3339)).unwrap()
+        else {
+            panic!("expected a local time timer");
+        };
+        let timestamp = local_rfc3339(Utc::now(), offset);
+        let (_, suffix) = timestamp.split_at(timestamp.len() - 6);
+        assert!(
+            timestamp.ends_with('Z') || (suffix.starts_with(['+', '-']) && &suffix[3..4] == ":"),
+            "{timestamp}"
+        );
+    }
+
+    #[test]
+    fn test_invalid_custom_time_format() {
+        let format = TimeFormat::Custom("%H:%Q".to_string());
+        assert!(matches!(
+            timer(LogFormat::Pretty, Some(true), Some(&format)),
+            Err(TracingInitError::InvalidTimeFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,224 @@
+//! A rollup of the time spent in each span, see `TracingSubscriberOptions::report_timings`.
+
+use crate::align::{pad_visible, pad_visible_start, visible_width};
+use crate::status::format_duration;
+use crate::style::bold;
+use crate::PRINT_TARGET;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The time spent in the spans of one name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct SpanTiming {
+    total: Duration,
+    calls: usize,
+}
+
+/// The time spent in the spans closed since recording started, by name.
+pub(crate) struct Timings {
+    spans: BTreeMap<&'static str, SpanTiming>,
+    start: Option<Instant>,
+}
+
+impl Timings {
+    pub(crate) const fn new() -> Self {
+        Self {
+            spans: BTreeMap::new(),
+            start: None,
+        }
+    }
+
+    /// The timings, longest total first, with the time since recording started.
+    fn sorted(&self) -> (Vec<(&'static str, SpanTiming)>, Duration) {
+        let mut spans: Vec<_> = self
+            .spans
+            .iter()
+            .map(|(name, timing)| (*name, *timing))
+            .collect();
+        spans.sort_by_key(|(_, timing)| std::cmp::Reverse(timing.total));
+        let elapsed = self.start.map(|start| start.elapsed()).unwrap_or_default();
+        (spans, elapsed)
+    }
+}
+
+/// The timings of the global subscriber.
+pub(crate) static TIMINGS: Mutex<Timings> = Mutex::new(Timings::new());
+
+fn lock(timings: &Mutex<Timings>) -> MutexGuard<'_, Timings> {
+    timings.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// When a span was opened, kept in its extensions.
+struct Opened(Instant);
+
+/// Adds the wall time from the opening to the closing of each span to `timings`, across threads.
+pub(crate) struct TimingLayer {
+    timings: &'static Mutex<Timings>,
+}
+
+impl TimingLayer {
+    /// A layer recording into `timings`, from now on and from nothing.
+    pub(crate) fn new(timings: &'static Mutex<Timings>) -> Self {
+        let mut recording = lock(timings);
+        recording.spans.clear();
+        recording.start = Some(Instant::now());
+        Self { timings }
+    }
+}
+
+impl<S> Layer<S> for TimingLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Opened(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(elapsed) = span
+            .extensions()
+            .get::<Opened>()
+            .map(|opened| opened.0.elapsed())
+        else {
+            return;
+        };
+        let mut timings = lock(self.timings);
+        let timing = timings.spans.entry(span.name()).or_default();
+        timing.total += elapsed;
+        timing.calls += 1;
+    }
+}
+
+/// Print how much time was spent in each span closed so far, as recorded with `report_timings`,
+/// as a table of the span names with their total time, the number of times they were opened and
+/// the share of the time since the subscriber was initialized, longest first:
+///
+/// ```text
+/// Span       Total  Calls  Share
+/// compile    3.42s      2  81.4%
+/// parse      120ms      2   2.9%
+/// ```
+///
+/// Nested spans are counted in the time of their parents too. Prints nothing if no span was
+/// recorded.
+pub fn print_timings_report() {
+    for line in report(&lock(&TIMINGS)) {
+        tracing::info!(target: PRINT_TARGET, "{line}");
+    }
+}
+
+/// The lines of the table of `print_timings_report`.
+fn report(timings: &Timings) -> Vec<String> {
+    let (spans, elapsed) = timings.sorted();
+    if spans.is_empty() {
+        return Vec::new();
+    }
+    let rows: Vec<_> = spans
+        .iter()
+        .map(|(name, timing)| {
+            let share = match elapsed.is_zero() {
+                true => 0.0,
+                false => timing.total.as_secs_f64() / elapsed.as_secs_f64() * 100.0,
+            };
+            [
+                name.to_string(),
+                format_duration(timing.total),
+                timing.calls.to_string(),
+                format!("{share:.1}%"),
+            ]
+        })
+        .collect();
+    let header = ["Span", "Total", "Calls", "Share"].map(String::from);
+    let widths: Vec<_> = (0..header.len())
+        .map(|column| {
+            let cells = rows.iter().chain([&header]);
+            cells
+                .map(|row| visible_width(&row[column]))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    // The names are aligned to the left and the numbers to the right.
+    let line = |row: &[String; 4]| {
+        let [name, total, calls, share] = row;
+        format!(
+            "{}  {}  {}  {}",
+            pad_visible(name, widths[0]),
+            pad_visible_start(total, widths[1]),
+            pad_visible_start(calls, widths[2]),
+            pad_visible_start(share, widths[3]),
+        )
+    };
+    let mut lines = vec![bold(&line(&header))];
+    lines.extend(rows.iter().map(line));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color, ENV_LOCK};
+    use std::thread;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    #[test]
+    fn test_timings() {
+        static TEST_TIMINGS: Mutex<Timings> = Mutex::new(Timings::new());
+        let subscriber = Registry::default().with(TimingLayer::new(&TEST_TIMINGS));
+        let dispatch = tracing::Dispatch::new(subscriber);
+        let compile = || {
+            let _span = tracing::info_span!("compile").entered();
+            thread::sleep(Duration::from_millis(20));
+        };
+        tracing::dispatcher::with_default(&dispatch, || {
+            compile();
+            let _parse = tracing::debug_span!("parse").entered();
+            thread::sleep(Duration::from_millis(5));
+        });
+        thread::scope(|scope| {
+            scope.spawn(|| tracing::dispatcher::with_default(&dispatch, compile));
+        });
+
+        let timings = lock(&TEST_TIMINGS);
+        let (spans, elapsed) = timings.sorted();
+        let names: Vec<_> = spans.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["compile", "parse"]);
+        assert_eq!(spans[0].1.calls, 2);
+        assert!(spans[0].1.total >= Duration::from_millis(40));
+        assert_eq!(spans[1].1.calls, 1);
+        assert!(spans[1].1.total >= Duration::from_millis(5));
+        assert!(elapsed >= spans[0].1.total);
+
+        // Without color, so that the header can be compared as it is.
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let global_color = color::global_color_enabled();
+        color::set_global_color(false);
+        let lines = report(&timings);
+        color::set_global_color(global_color);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("Span   "));
+        assert!(lines[0].ends_with("Calls  Share"));
+        let columns: Vec<_> = lines[1].split_whitespace().collect();
+        assert_eq!(columns[0], "compile");
+        assert_eq!(columns[2], "2");
+        assert!(columns[3].ends_with('%'));
+        let columns: Vec<_> = lines[2].split_whitespace().collect();
+        assert_eq!((columns[0], columns[2]), ("parse", "1"));
+        assert_eq!(lines[1].len(), lines[2].len());
+    }
+
+    #[test]
+    fn test_empty_timings_report() {
+        assert!(report(&Timings::new()).is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+//! A complete record of the log output in a file, see `TracingSubscriberOptions::transcript_file`.
+
+use crate::color::StripAnsi;
+use crate::writer::{lock, locked_file, open_log_file, LockedFile};
+use crate::{fmt_layer, TracingInitError, TracingSubscriberOptions};
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::Subscriber;
+use tracing_subscriber::{fmt::MakeWriter, registry::LookupSpan, Layer};
+
+/// The layer appending every event, at any level and of any target, to the file at `path` without
+/// ANSI color.
+///
+/// It has no filter of its own and isn't behind the level filter of the terminal, so that the
+/// transcript holds the TRACE events too while the terminal only shows INFO.
+pub(crate) fn transcript_layer<S>(
+    options: &TracingSubscriberOptions,
+    path: &Path,
+) -> Result<Box<dyn Layer<S> + Send + Sync>, TracingInitError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let line_ending = options.file_line_ending.unwrap_or_default();
+    let file =
+        open_log_file(path, None, line_ending).map_err(|source| TracingInitError::OpenLogFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    fmt_layer(options, false, None, TranscriptWriter(Mutex::new(file)))
+}
+
+struct TranscriptWriter(Mutex<Box<dyn io::Write + Send + Sync>>);
+
+impl<'a> MakeWriter<'a> for TranscriptWriter {
+    type Writer = StripAnsi<LockedFile<'a>>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        // The messages of the print helpers may be painted regardless of the formatter.
+        StripAnsi::new(locked_file(lock(&self.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        build_subscriber, color, print_action, with_writer_override, StatusPrinter,
+        TracingSubscriberOptions, TracingWriterMode, WriterOverride, ENV_LOCK,
+    };
+    use std::fs;
+    use tracing::Level;
+
+    #[test]
+    fn test_transcript_file() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var("RUST_LOG");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/transcript.log");
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Null)
+            .transcript_file(path.clone())
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        let ((), terminal) = tracing::subscriber::with_default(subscriber, || {
+            with_writer_override(WriterOverride::new().with_ansi(true), || {
+                // The terminal shows color, the transcript still doesn't.
+                color::set_level_color(&Level::INFO, true);
+                color::set_global_color(true);
+                print_action("Compiling", "my_contract");
+                tracing::debug!(target: "forc_pkg", "resolved 3 dependencies");
+                tracing::trace!(target: "hyper", "connection reused");
+                tracing::info!(target: "forc", "Finished");
+            })
+        });
+        let painted = StatusPrinter::new().format("Compiling", "my_contract", true);
+        assert_eq!(terminal, format!("{painted}\nFinished\n"));
+        let compiling = StatusPrinter::new().format("Compiling", "my_contract", false);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{compiling}\nresolved 3 dependencies\nconnection reused\nFinished\n")
+        );
+    }
+}
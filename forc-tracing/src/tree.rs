@@ -0,0 +1,187 @@
+//! Trees drawn with box-drawing connectors, e.g. of the dependency graph of a package.
+
+use crate::{color, symbols, PRINT_TARGET};
+use anstyle::Style;
+use tracing::Level;
+
+/// A node of the tree drawn by `print_tree`, with the nodes below it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeNode {
+    pub label: String,
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// A node without children.
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            children: Vec::new(),
+        }
+    }
+
+    /// The node with `child` added below its other children.
+    pub fn child(mut self, child: TreeNode) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+/// The connectors that the lines of a tree are drawn with.
+struct Connectors {
+    branch: &'static str,
+    last: &'static str,
+    pipe: &'static str,
+}
+
+const UNICODE: Connectors = Connectors {
+    branch: "├── ",
+    last: "└── ",
+    pipe: "│   ",
+};
+
+const ASCII: Connectors = Connectors {
+    branch: "|-- ",
+    last: "`-- ",
+    pipe: "|   ",
+};
+
+/// Print `root` and the nodes below it at INFO, one event per line, each child connected to its
+/// parent by dimmed box-drawing characters:
+///
+/// ```text
+/// my_contract
+/// ├── core
+/// └── std
+///     └── core
+/// ```
+///
+/// The connectors are drawn with `|--` and `` `-- `` instead where color is omitted or the terminal
+/// isn't expected to render them, e.g. outside of a UTF-8 locale.
+///
+/// ```no_run
+/// use forc_tracing::{print_tree, TreeNode};
+///
+/// let std = TreeNode::new("std").child(TreeNode::new("core"));
+/// print_tree(&TreeNode::new("my_contract").child(TreeNode::new("core")).child(std));
+/// ```
+pub fn print_tree(root: &TreeNode) {
+    let ansi = color::level_color(&Level::INFO);
+    let unicode = ansi && symbols::supports_unicode(|var| std::env::var(var).ok());
+    for line in tree_lines(root, unicode, ansi) {
+        tracing::info!(target: PRINT_TARGET, "{line}");
+    }
+}
+
+fn tree_lines(root: &TreeNode, unicode: bool, ansi: bool) -> Vec<String> {
+    let connectors = match unicode {
+        true => &UNICODE,
+        false => &ASCII,
+    };
+    let mut lines = vec![root.label.clone()];
+    push_children(&mut lines, root, "", connectors, ansi);
+    lines
+}
+
+/// Append the lines of the children of `node` to `lines`, each after `prefix`, which connects the
+/// ancestors of `node` that have siblings left below.
+fn push_children(
+    lines: &mut Vec<String>,
+    node: &TreeNode,
+    prefix: &str,
+    connectors: &Connectors,
+    ansi: bool,
+) {
+    let dim = |text: &str| match ansi {
+        true => color::paint(text, Style::new().dimmed()),
+        false => text.to_string(),
+    };
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == node.children.len();
+        let connector = match last {
+            true => connectors.last,
+            false => connectors.branch,
+        };
+        lines.push(format!(
+            "{}{}",
+            dim(&format!("{prefix}{connector}")),
+            child.label
+        ));
+        let continuation = match last {
+            true => "    ",
+            false => connectors.pipe,
+        };
+        push_children(
+            lines,
+            child,
+            &format!("{prefix}{continuation}"),
+            connectors,
+            ansi,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> TreeNode {
+        let std = TreeNode::new("std v0.46.0").child(TreeNode::new("core v0.46.0"));
+        let my_lib = TreeNode::new("my_lib")
+            .child(std.clone())
+            .child(TreeNode::new("sha3"));
+        TreeNode::new("my_contract").child(my_lib).child(std)
+    }
+
+    #[test]
+    fn test_tree_lines() {
+        assert_eq!(
+            tree_lines(&tree(), true, false),
+            [
+                "my_contract",
+                "├── my_lib",
+                "│   ├── std v0.46.0",
+                "│   │   └── core v0.46.0",
+                "│   └── sha3",
+                "└── std v0.46.0",
+                "    └── core v0.46.0",
+            ]
+        );
+        assert_eq!(
+            tree_lines(&TreeNode::new("my_contract"), true, false),
+            ["my_contract"]
+        );
+    }
+
+    #[test]
+    fn test_tree_lines_ascii() {
+        assert_eq!(
+            tree_lines(&tree(), false, false),
+            [
+                "my_contract",
+                "|-- my_lib",
+                "|   |-- std v0.46.0",
+                "|   |   `-- core v0.46.0",
+                "|   `-- sha3",
+                "`-- std v0.46.0",
+                "    `-- core v0.46.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tree_lines_dimmed() {
+        let dim = |text| color::paint(text, Style::new().dimmed());
+        let root = TreeNode::new("my_contract")
+            .child(TreeNode::new("core"))
+            .child(TreeNode::new("std"));
+        assert_eq!(
+            tree_lines(&root, true, true),
+            [
+                "my_contract".to_string(),
+                format!("{}core", dim("├── ")),
+                format!("{}std", dim("└── ")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Truncation of very long messages, see `TracingSubscriberOptions::max_message_len`.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use std::io::Write;
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::writer::EitherWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// The key the JSON format writes the message of an event under.
+const JSON_MESSAGE: &[u8] = b"\"message\":";
+
+/// Which messages are truncated, and to how many bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Truncation {
+    /// The length in bytes that longer messages are truncated to, if any.
+    pub(crate) max_len: Option<usize>,
+    /// Whether the messages of ERROR events are left whole.
+    pub(crate) keep_errors: bool,
+}
+
+impl Truncation {
+    /// The length the messages of events at `level` are truncated to, if any.
+    pub(crate) fn max_len_for(self, level: &Level) -> Option<usize> {
+        match self.keep_errors && *level == Level::ERROR {
+            true => None,
+            false => self.max_len,
+        }
+    }
+}
+
+/// `text` cut to at most `max_len` bytes followed by a marker with its full length, e.g.
+/// `deadbe… (truncated, 4096 bytes total)`, or nothing if it isn't longer than that.
+///
+/// The text is only cut between characters and outside of escape codes, and the colors of the
+/// part kept are reset before the marker.
+pub(crate) fn truncate(text: &str, max_len: usize) -> Option<String> {
+    if text.len() <= max_len {
+        return None;
+    }
+    let mut end = max_len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    if let Some(escape) = text[..end].rfind('\x1b') {
+        // An escape code is `ESC [`, any parameters and a final byte from `@` to `~`.
+        let code = &text.as_bytes()[escape + 1..end];
+        let complete = code.len() >= 2 && code[1..].iter().any(|b| (b'@'..=b'~').contains(b));
+        if !complete {
+            end = escape;
+        }
+    }
+    let kept = &text[..end];
+    let reset = match kept.contains('\x1b') {
+        true => "\x1b[0m",
+        false => "",
+    };
+    Some(format!(
+        "{kept}{reset}… (truncated, {} bytes total)",
+        text.len()
+    ))
+}
+
+/// Truncates the `message` of the JSON objects written through the wrapped writer as `truncation`
+/// says.
+pub(crate) struct JsonTruncateWriter<W> {
+    inner: W,
+    truncation: Truncation,
+}
+
+impl<W> JsonTruncateWriter<W> {
+    pub(crate) fn new(inner: W, truncation: Truncation) -> Self {
+        Self { inner, truncation }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for JsonTruncateWriter<W> {
+    type Writer = EitherWriter<BoxedEvent<'a, W::Writer>, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        EitherWriter::B(self.inner.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        let inner = self.inner.make_writer_for(meta);
+        match self.truncation.max_len_for(meta.level()) {
+            Some(max_len) => EitherWriter::A(BufferedEvent::boxed(inner, move |object, inner| {
+                inner.write_all(truncated(object, max_len).as_deref().unwrap_or(object))
+            })),
+            None => EitherWriter::B(inner),
+        }
+    }
+}
+
+/// The JSON `object` of an event with its message truncated to `max_len`, if it is longer.
+fn truncated(object: &[u8], max_len: usize) -> Option<Vec<u8>> {
+    // Quotes inside string values are escaped, so the first match is the key of the event's own
+    // message.
+    let key = object
+        .windows(JSON_MESSAGE.len())
+        .position(|window| window == JSON_MESSAGE)?;
+    let start = key + JSON_MESSAGE.len();
+    let mut values = serde_json::Deserializer::from_slice(&object[start..]).into_iter::<String>();
+    let message = values.next()?.ok()?;
+    let end = start + values.byte_offset();
+    let message = truncate(&message, max_len)?;
+    let mut truncated = object[..start].to_vec();
+    serde_json::to_writer(&mut truncated, &message).expect("strings serialize to JSON");
+    truncated.extend_from_slice(&object[end..]);
+    Some(truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, LogFormat, TracingSubscriberOptions};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn capture(options: &TracingSubscriberOptions, f: impl FnOnce()) -> String {
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(options, false, None, capture.clone()).unwrap();
+        tracing::subscriber::with_default(Registry::default().with(layer), f);
+        capture.contents()
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 5), None);
+        // `é` takes up bytes 3 and 4, so cutting after 4 bytes would split it.
+        assert_eq!(
+            truncate("café au lait", 4).unwrap(),
+            "caf… (truncated, 13 bytes total)"
+        );
+        assert_eq!(
+            truncate("café au lait", 5).unwrap(),
+            "café… (truncated, 13 bytes total)"
+        );
+        // Escape codes aren't cut in two, and the colors are reset.
+        assert_eq!(
+            truncate("\x1b[31mred\x1b[0m", 7).unwrap(),
+            "\x1b[31mre\x1b[0m… (truncated, 12 bytes total)"
+        );
+        assert_eq!(
+            truncate("\x1b[31mred", 3).unwrap(),
+            "… (truncated, 8 bytes total)"
+        );
+    }
+
+    #[test]
+    fn test_max_message_len() {
+        let options = TracingSubscriberOptions::builder()
+            .max_message_len(11)
+            .display_level(true)
+            .build();
+        let output = capture(&options, || {
+            tracing::info!("bytes: ✓✓✓✓");
+            tracing::info!("short");
+            tracing::error!("failed: ✗✗✗✗");
+        });
+        assert_eq!(
+            output,
+            " INFO bytes: ✓… (truncated, 19 bytes total)\n INFO short\n\
+             ERROR failed: ✗… (truncated, 20 bytes total)\n"
+        );
+
+        let options = TracingSubscriberOptions::builder()
+            .max_message_len(11)
+            .never_truncate_errors(true)
+            .build();
+        let output = capture(&options, || tracing::error!("failed: ✗✗✗✗"));
+        assert_eq!(output, "failed: ✗✗✗✗\n");
+    }
+
+    #[test]
+    fn test_max_message_len_json() {
+        let options = TracingSubscriberOptions::builder()
+            .format(LogFormat::Json)
+            .display_time(false)
+            .max_message_len(11)
+            .build();
+        let output = capture(
+            &options,
+            || tracing::info!(target: "forc", bytes = "0xdeadbeef", "message: \"ééé\""),
+        );
+        let event: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            event,
+            serde_json::json!({
+                "level": "INFO",
+                "target": "forc",
+                "message": "message: \"… (truncated, 17 bytes total)",
+                "bytes": "0xdeadbeef",
+            })
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! Soft-wrapping of long messages at word boundaries, and indentation of the lines of multi-line
+//! messages, see `TracingSubscriberOptions::wrap_width` and `indent_continuation`.
+//!
+//! The formatter of `tracing_subscriber` writes each event into a buffer of its own, so the start
+//! of the message is marked by the field formatter and the whole event is laid out by the writer,
+//! which aligns the continuation lines under the marked column.
+
+use crate::align::visible_width;
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use crate::truncate::{truncate, Truncation};
+use std::cell::Cell;
+use std::fmt;
+use std::io;
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::{DefaultFields, FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Marks where the message of an event starts, and is removed again before it is written.
+const MESSAGE_START: char = '\u{1e}';
+
+/// The width wrapped to if that of the terminal can't be detected.
+const DEFAULT_WIDTH: usize = 80;
+
+thread_local! {
+    // Whether the fields being formatted on this thread are those of an event, rather than a span.
+    static FORMATTING_EVENT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// How the messages of events are laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Layout {
+    /// The width to wrap messages to, if any.
+    pub(crate) width: Option<usize>,
+    /// Whether the lines after a newline in a message are indented under its start too.
+    pub(crate) indent_lines: bool,
+    /// Which messages are truncated before they are laid out.
+    pub(crate) truncation: Truncation,
+}
+
+impl Layout {
+    /// Whether messages are left as they are.
+    pub(crate) fn is_plain(self) -> bool {
+        self.width.is_none() && !self.indent_lines && self.truncation.max_len.is_none()
+    }
+}
+
+/// The width of the terminal on stdout, or 80 columns if it isn't one.
+pub(crate) fn terminal_width() -> usize {
+    detected_terminal_width().unwrap_or(DEFAULT_WIDTH)
+}
+
+/// The width of the terminal on stdout, if it is one.
+pub(crate) fn detected_terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(width, _)| usize::from(width.0))
+}
+
+/// Wraps the event formatter, so that `WrapFields` can tell the fields of events from spans.
+pub(crate) struct WrapFormat<E>(pub(crate) E);
+
+impl<S, N, E> FormatEvent<S, N> for WrapFormat<E>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+    E: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        FORMATTING_EVENT.with(|formatting| formatting.set(true));
+        let result = self.0.format_event(ctx, writer, event);
+        FORMATTING_EVENT.with(|formatting| formatting.set(false));
+        result
+    }
+}
+
+/// Formats fields like `DefaultFields`, marking where the fields of an event start.
+#[derive(Default)]
+pub(crate) struct WrapFields(DefaultFields);
+
+impl<'writer> FormatFields<'writer> for WrapFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        if FORMATTING_EVENT.with(Cell::get) {
+            fmt::Write::write_char(&mut writer, MESSAGE_START)?;
+        }
+        self.0.format_fields(writer, fields)
+    }
+}
+
+/// Lays out the events formatted with `WrapFormat` and `WrapFields` before passing them on to
+/// `inner`.
+pub(crate) struct WrapWriter<W> {
+    inner: W,
+    layout: Layout,
+}
+
+impl<W> WrapWriter<W> {
+    pub(crate) fn new(inner: W, layout: Layout) -> Self {
+        Self { inner, layout }
+    }
+}
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for WrapWriter<W> {
+    type Writer = BoxedEvent<'a, W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        wrap_writer(self.inner.make_writer(), self.layout)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        let max_len = self.layout.truncation.max_len_for(meta.level());
+        let layout = Layout {
+            truncation: Truncation {
+                max_len,
+                ..self.layout.truncation
+            },
+            ..self.layout
+        };
+        wrap_writer(self.inner.make_writer_for(meta), layout)
+    }
+}
+
+/// A writer for one event to `inner`, that writes it out laid out as `layout` says.
+fn wrap_writer<'a, W: io::Write>(inner: W, layout: Layout) -> BoxedEvent<'a, W> {
+    BufferedEvent::boxed(inner, move |buffer, inner| {
+        let text = String::from_utf8_lossy(buffer);
+        inner.write_all(wrap_event(&text, layout).as_bytes())
+    })
+}
+
+/// Lay out the message of the formatted `event`, truncating it if it is too long and indenting the
+/// continuation lines to the column the message starts at.
+fn wrap_event(event: &str, layout: Layout) -> String {
+    let Some((prefix, message)) = event.rsplit_once(MESSAGE_START) else {
+        return event.to_string();
+    };
+    let prefix = prefix.replace(MESSAGE_START, "");
+    let indent = visible_width(prefix.rsplit('\n').next().unwrap_or_default());
+    let (message, newline) = match message.strip_suffix('\n') {
+        Some(message) => (message, "\n"),
+        None => (message, ""),
+    };
+    let truncated = layout
+        .truncation
+        .max_len
+        .and_then(|max_len| truncate(message, max_len));
+    let message = truncated.as_deref().unwrap_or(message);
+    let width = layout.width.unwrap_or(usize::MAX);
+    let wrapped = wrap(message, width, indent, layout.indent_lines);
+    format!("{prefix}{wrapped}{newline}")
+}
+
+/// Wrap each line of `text` to `width` columns at spaces, where the first line starts at column
+/// `indent` and the lines it is wrapped onto are indented to it. The lines after a newline in the
+/// text itself are indented to it too if `indent_lines` is set, and otherwise left to start at the
+/// first column, as they would be without wrapping.
+///
+/// Words wider than the space available are left whole rather than broken up.
+pub(crate) fn wrap(text: &str, width: usize, indent: usize, indent_lines: bool) -> String {
+    let mut wrapped = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        let indent = if i == 0 || indent_lines { indent } else { 0 };
+        if i > 0 {
+            wrapped.push('\n');
+            wrapped.push_str(&" ".repeat(indent));
+        }
+        let available = width.saturating_sub(indent).max(1);
+        let mut column = 0;
+        for (j, word) in line.split(' ').enumerate() {
+            let word_width = visible_width(word);
+            if j > 0 {
+                if column > 0 && column + 1 + word_width > available {
+                    wrapped.push('\n');
+                    wrapped.push_str(&" ".repeat(indent));
+                    column = 0;
+                } else {
+                    wrapped.push(' ');
+                    column += 1;
+                }
+            }
+            wrapped.push_str(word);
+            column += word_width;
+        }
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CapturingWriter;
+    use crate::{fmt_layer, LogFormat, TracingSubscriberOptions};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn capture_wrapped(
+        options: TracingSubscriberOptions,
+        width: impl Into<Option<usize>>,
+        f: impl FnOnce(),
+    ) -> String {
+        let capture = CapturingWriter::new();
+        let layer = fmt_layer(&options, false, width.into(), capture.clone()).unwrap();
+        tracing::subscriber::with_default(Registry::default().with(layer), f);
+        capture.contents()
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap("the quick brown fox jumps over", 10, 0, false),
+            "the quick\nbrown fox\njumps over"
+        );
+        assert_eq!(
+            wrap("the quick brown fox", 16, 6, false),
+            "the quick\n      brown fox"
+        );
+        // Words too wide to fit are left whole, and existing lines are wrapped separately.
+        assert_eq!(
+            wrap("see https://fuel.network/docs\nthen retry", 8, 0, false),
+            "see\nhttps://fuel.network/docs\nthen\nretry"
+        );
+        assert_eq!(wrap("  |  x", 80, 0, false), "  |  x");
+        let painted = "\x1b[31mred\x1b[0m \x1b[32mgreen\x1b[0m";
+        assert_eq!(wrap(painted, 9, 0, false), painted);
+    }
+
+    #[test]
+    fn test_wrapped_events_are_aligned_under_the_message() {
+        let options = TracingSubscriberOptions::builder()
+            .display_level(true)
+            .display_target(true)
+            .build();
+        let output = capture_wrapped(options, 30, || {
+            tracing::warn!(target: "forc_pkg", "unused import of the std library in a script");
+            tracing::info!(target: "forc_pkg", "short");
+        });
+        assert_eq!(
+            output,
+            concat!(
+                " WARN forc_pkg: unused import\n",
+                "                of the std\n",
+                "                library in a\n",
+                "                script\n",
+                " INFO forc_pkg: short\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_span_fields_are_not_marked() {
+        let options = TracingSubscriberOptions::builder()
+            .display_span_context(true)
+            .build();
+        let output = capture_wrapped(options, 80, || {
+            let _span = tracing::info_span!("build", package = "std").entered();
+            tracing::info!("Compiling");
+        });
+        assert_eq!(output, "build{package=\"std\"}: Compiling\n");
+    }
+
+    #[test]
+    fn test_indent_continuation() {
+        let options = TracingSubscriberOptions::builder()
+            .display_level(true)
+            .indent_continuation(true);
+        let log = || tracing::error!("mismatched types\n  --> main.sw:3:5\n   |");
+        assert_eq!(
+            capture_wrapped(options.clone().build(), None, log),
+            "ERROR mismatched types\n        --> main.sw:3:5\n         |\n"
+        );
+        // Wrapping takes the indentation into account.
+        let log = || tracing::error!("mismatched types\nexpected u64 found bool");
+        assert_eq!(
+            capture_wrapped(options.clone().build(), 22, log),
+            "ERROR mismatched types\n      expected u64\n      found bool\n"
+        );
+        // The JSON format is left alone.
+        let json = options.format(LogFormat::Json).build();
+        let output = capture_wrapped(json, None, log);
+        let event: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            event["message"],
+            "mismatched types\nexpected u64 found bool"
+        );
+    }
+}
@@ -0,0 +1,1404 @@
+//! The destinations that formatted log events are written to.
+
+use crate::buffered::{BoxedEvent, BufferedEvent};
+use crate::capture::override_writer;
+use crate::color::{self, Stream, StripAnsi};
+use crate::dedupe::DedupeGuard;
+use crate::flush::{self, FlushBuffer, FlushPolicy, Flusher};
+use crate::json_array::JsonArrayCloser;
+use crate::panic::PANIC_TARGET;
+use crate::rotation::{Rolling, RollingFile};
+#[cfg(feature = "net")]
+use crate::socket::{SocketTarget, SocketWriter};
+use crate::spinner::above_spinner;
+use crate::TracingInitError;
+use anstream::{AutoStream, ColorChoice, RawStream};
+use std::cell::Cell;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, Weak};
+use tracing::{Level, Metadata};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::fmt::MakeWriter;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TracingWriterMode {
+    /// Write ERROR and WARN to stderr and everything else to stdout.
+    Stdio,
+    /// Write everything to stdout.
+    Stdout,
+    /// Write everything to stderr.
+    Stderr,
+    /// Append everything to the file at the given path, without ANSI color. Missing parent
+    /// directories are created.
+    File(PathBuf),
+    /// Write to stdout and stderr like `Stdio`, and also append everything to `file` without ANSI
+    /// color. If the file can't be opened, a warning is printed and only the terminal is written to.
+    Tee { file: PathBuf },
+    /// Discard everything. Unlike the `silent` option, events are still formatted and pass through
+    /// the other layers, e.g. to be counted for `log_counts` or retained in the ring buffer, which
+    /// makes this useful for benchmarks and for embedding forc without its output.
+    Null,
+    /// Write everything, without ANSI color, to the writers returned by the given function, e.g. to
+    /// show the output in a GUI or send it over a socket. The function is called once per event,
+    /// which is written whole. `non_blocking` doesn't apply to these writers.
+    Custom(CustomWriter),
+    /// Send everything, without ANSI color, to a log collector listening on the given socket, e.g.
+    /// a sidecar of a container. The connection is made with the first event and shared by all
+    /// events. If the collector can't be reached, the output is kept and the connection is made
+    /// again with a later event, so that the build carries on without it.
+    #[cfg(feature = "net")]
+    Socket(SocketTarget),
+}
+
+/// The function that `TracingWriterMode::Custom` gets a writer for each event from.
+///
+/// Two are equal only if they are clones of the same function.
+#[derive(Clone)]
+pub struct CustomWriter(Arc<dyn Fn() -> Box<dyn io::Write + Send> + Send + Sync>);
+
+impl CustomWriter {
+    pub fn new(
+        make_writer: impl Fn() -> Box<dyn io::Write + Send> + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(make_writer))
+    }
+}
+
+impl fmt::Debug for CustomWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CustomWriter").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for CustomWriter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CustomWriter {}
+
+/// A `TracingWriterMode::Custom` that sends each line of the log output, without the newline, to
+/// the returned receiver, e.g. for a build server to forward to its clients.
+///
+/// Lines are sent once complete, so a receiver never sees part of one. Events are no longer sent
+/// once the receiver is dropped.
+pub fn channel_writer() -> (TracingWriterMode, Receiver<String>) {
+    let (sender, receiver) = mpsc::channel();
+    let custom = CustomWriter::new(move || Box::new(ChannelWriter::new(sender.clone())));
+    (TracingWriterMode::Custom(custom), receiver)
+}
+
+/// Buffers the output of one event, sending it on line by line.
+struct ChannelWriter {
+    sender: Sender<String>,
+    buffer: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn new(sender: Sender<String>) -> Self {
+        Self {
+            sender,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn send(&self, line: &[u8]) {
+        // The receiver is gone, and with it anyone interested in the output.
+        let _ = self.sender.send(String::from_utf8_lossy(line).into_owned());
+    }
+}
+
+impl io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while let Some(newline) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=newline).collect();
+            self.send(&line[..newline]);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ChannelWriter {
+    fn drop(&mut self) {
+        // An event that doesn't end with a newline still ends its line.
+        if !self.buffer.is_empty() {
+            self.send(&self.buffer);
+        }
+    }
+}
+
+impl TracingWriterMode {
+    /// The stream that events at `level` are written to, if any.
+    fn stream_for(&self, level: &Level) -> Option<Stream> {
+        match self {
+            TracingWriterMode::Stdio | TracingWriterMode::Tee { .. } if *level <= Level::WARN => {
+                Some(Stream::Stderr)
+            }
+            TracingWriterMode::Stdio
+            | TracingWriterMode::Stdout
+            | TracingWriterMode::Tee { .. } => Some(Stream::Stdout),
+            TracingWriterMode::Stderr => Some(Stream::Stderr),
+            TracingWriterMode::File(_) | TracingWriterMode::Null | TracingWriterMode::Custom(_) => {
+                None
+            }
+            #[cfg(feature = "net")]
+            TracingWriterMode::Socket(_) => None,
+        }
+    }
+}
+
+// This allows us to write ERROR and WARN level logs to stderr and everything else to stdout.
+// https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/trait.MakeWriter.html
+pub(crate) struct StdioTracingWriter {
+    destination: Arc<RwLock<Arc<Destination>>>,
+    stdout_ansi: bool,
+    stderr_ansi: bool,
+    stdout: Sink,
+    stderr: Sink,
+    files: Vec<LogFile>,
+    rolling: Option<Rolling>,
+    line_ending: LineEnding,
+}
+
+/// Where the events go as the writer mode says, which `set_writer_mode` can change.
+struct Destination {
+    writer_mode: TracingWriterMode,
+    // The file of `TracingWriterMode::File` or `Tee`, if it could be opened, or the connection of
+    // `Socket`.
+    file: Option<Sink>,
+    rolling: Option<Rolling>,
+    line_ending: LineEnding,
+}
+
+impl Destination {
+    /// Open the file of `writer_mode`, if any.
+    fn open(
+        writer_mode: TracingWriterMode,
+        rolling: Option<Rolling>,
+        line_ending: LineEnding,
+    ) -> Result<Self, TracingInitError> {
+        let file = match &writer_mode {
+            TracingWriterMode::File(path) => {
+                let file = open_log_file(path, rolling, line_ending).map_err(|source| {
+                    TracingInitError::OpenLogFile {
+                        path: path.clone(),
+                        source,
+                    }
+                })?;
+                Some(Sink::File(Mutex::new(file)))
+            }
+            // The terminal output is still useful on its own, so don't fail over the copy.
+            TracingWriterMode::Tee { file } => match open_log_file(file, rolling, line_ending) {
+                Ok(file) => Some(Sink::File(Mutex::new(file))),
+                Err(err) => {
+                    eprintln!(
+                        "warning: failed to open log file `{}`, logging to the terminal only: {err}",
+                        file.display()
+                    );
+                    None
+                }
+            },
+            #[cfg(feature = "net")]
+            TracingWriterMode::Socket(target) => Some(Sink::File(Mutex::new(Box::new(
+                SocketWriter::new(target.clone()),
+            )))),
+            _ => None,
+        };
+        Ok(Self {
+            writer_mode,
+            file,
+            rolling,
+            line_ending,
+        })
+    }
+}
+
+/// The destination of the writer of a subscriber, for `set_writer_mode` to switch.
+#[derive(Clone)]
+pub(crate) struct DestinationHandle(Weak<RwLock<Arc<Destination>>>);
+
+impl DestinationHandle {
+    /// Switch the output of the subscriber to `writer_mode`, see `set_writer_mode`.
+    pub(crate) fn set_writer_mode(
+        &self,
+        writer_mode: TracingWriterMode,
+    ) -> Result<(), TracingInitError> {
+        let destination = self.0.upgrade().ok_or(TracingInitError::NotInstalled)?;
+        let current = read(&destination);
+        let new = Destination::open(writer_mode, current.rolling, current.line_ending)?;
+        *destination.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(new);
+        Ok(())
+    }
+}
+
+// The destination of the writer of the global subscriber, once forc's is installed.
+static INSTALLED: Mutex<Option<DestinationHandle>> = Mutex::new(None);
+
+/// Remember `handle` as that of the global subscriber, for `set_writer_mode`.
+pub(crate) fn set_installed(handle: DestinationHandle) {
+    *lock(&INSTALLED) = Some(handle);
+}
+
+/// Switch the output of the subscriber installed by `init_tracing_subscriber` to `writer_mode`,
+/// e.g. to a `channel_writer` once a GUI attaches.
+///
+/// Events logged during the switch go either to the old destination or to the new one, whole. The
+/// color decision made for the terminal when the subscriber was built stays as it is, and a new log
+/// file is written to on the thread of the event even with `non_blocking`. Subscribers built by
+/// `build_subscriber` aren't affected.
+///
+/// Returns an error, leaving the output as it was, if forc's subscriber hasn't been installed, or if
+/// the file of `TracingWriterMode::File` can't be opened.
+pub fn set_writer_mode(writer_mode: TracingWriterMode) -> Result<(), TracingInitError> {
+    let installed = lock(&INSTALLED).clone();
+    installed
+        .ok_or(TracingInitError::NotInstalled)?
+        .set_writer_mode(writer_mode)
+}
+
+fn read(destination: &RwLock<Arc<Destination>>) -> Arc<Destination> {
+    destination
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// A file that events are appended to besides the terminal, without ANSI color.
+struct LogFile {
+    sink: Sink,
+    // Whether only WARN and ERROR events are appended.
+    errors_only: bool,
+}
+
+impl LogFile {
+    fn new(file: Box<dyn io::Write + Send + Sync>, errors_only: bool) -> Self {
+        Self {
+            sink: Sink::File(Mutex::new(file)),
+            errors_only,
+        }
+    }
+}
+
+/// The line ending that events end with in log files, see
+/// `TracingSubscriberOptions::file_line_ending`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`.
+    Lf,
+    /// `\r\n`, e.g. for Notepad.
+    CrLf,
+    /// `\r\n` on Windows, and `\n` elsewhere.
+    #[default]
+    Native,
+}
+
+impl LineEnding {
+    fn is_crlf(self) -> bool {
+        match self {
+            LineEnding::Lf => false,
+            LineEnding::CrLf => true,
+            LineEnding::Native => cfg!(windows),
+        }
+    }
+}
+
+/// Files for `TracingSubscriberOptions::split_files`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitFiles {
+    /// The file every event that is logged is appended to.
+    pub all: PathBuf,
+    /// The file only WARN and ERROR events are appended to.
+    pub errors: PathBuf,
+}
+
+impl StdioTracingWriter {
+    pub(crate) fn new(
+        writer_mode: TracingWriterMode,
+        ansi: Option<bool>,
+        rolling: Option<Rolling>,
+        line_ending: LineEnding,
+    ) -> Result<Self, TracingInitError> {
+        let destination = Arc::new(RwLock::new(Arc::new(Destination::open(
+            writer_mode,
+            rolling,
+            line_ending,
+        )?)));
+        Ok(Self {
+            destination,
+            stdout_ansi: color::resolve_ansi(ansi, Stream::Stdout),
+            stderr_ansi: color::resolve_ansi(ansi, Stream::Stderr),
+            stdout: Sink::Stdout,
+            stderr: Sink::Stderr,
+            files: Vec::new(),
+            rolling,
+            line_ending,
+        })
+    }
+
+    /// The handle for switching the destination of the events, see `set_writer_mode`.
+    pub(crate) fn destination_handle(&self) -> DestinationHandle {
+        DestinationHandle(Arc::downgrade(&self.destination))
+    }
+
+    /// The current destination of the events.
+    fn destination(&self) -> Arc<Destination> {
+        read(&self.destination)
+    }
+
+    /// Also append every event to `split.all`, and WARN and ERROR events to `split.errors`.
+    pub(crate) fn split_files(&mut self, split: &SplitFiles) -> Result<(), TracingInitError> {
+        for (path, errors_only) in [(&split.all, false), (&split.errors, true)] {
+            let file = open_log_file(path, self.rolling, self.line_ending).map_err(|source| {
+                TracingInitError::OpenLogFile {
+                    path: path.clone(),
+                    source,
+                }
+            })?;
+            self.files.push(LogFile::new(file, errors_only));
+        }
+        Ok(())
+    }
+
+    /// Move writing to each destination in use onto a dedicated background thread, returning the
+    /// guards that flush and stop those threads when dropped.
+    pub(crate) fn make_non_blocking(&mut self) -> Vec<WorkerGuard> {
+        let mut guards = Vec::new();
+        for sink in self.streams_in_use() {
+            guards.extend(sink.make_non_blocking());
+        }
+        let mut destination = self.destination.write().unwrap_or_else(|e| e.into_inner());
+        // Nothing has been written with the destination yet, so it isn't shared.
+        if let Some(file) = Arc::get_mut(&mut destination).and_then(|d| d.file.as_mut()) {
+            guards.extend(file.make_non_blocking());
+        }
+        drop(destination);
+        for file in &mut self.files {
+            guards.extend(file.sink.make_non_blocking());
+        }
+        guards
+    }
+
+    /// Buffer the output for stdout and stderr as `policy` says, returning the thread that writes
+    /// it out for `FlushPolicy::Interval`. The streams moved onto a background thread by
+    /// `make_non_blocking` are left as they are.
+    pub(crate) fn buffer_output(&mut self, policy: FlushPolicy) -> Option<Flusher> {
+        let line_buffered = match policy {
+            FlushPolicy::Immediate => return None,
+            FlushPolicy::LineBuffered => true,
+            FlushPolicy::Interval(_) => false,
+        };
+        let buffers: Vec<_> = self
+            .streams_in_use()
+            .into_iter()
+            .filter_map(|sink| sink.buffer(line_buffered))
+            .collect();
+        match policy {
+            FlushPolicy::Interval(interval) => Some(Flusher::spawn(buffers, interval)),
+            _ => None,
+        }
+    }
+
+    /// The sinks of the streams that events at some level are written to.
+    fn streams_in_use(&mut self) -> Vec<&mut Sink> {
+        let destination = self.destination();
+        let uses = |stream| {
+            LEVELS
+                .iter()
+                .any(|level| destination.writer_mode.stream_for(level) == Some(stream))
+        };
+        let (stdout, stderr) = (uses(Stream::Stdout), uses(Stream::Stderr));
+        [(stdout, &mut self.stdout), (stderr, &mut self.stderr)]
+            .into_iter()
+            .filter_map(|(used, sink)| used.then_some(sink))
+            .collect()
+    }
+
+    pub(crate) fn disable_ansi(&mut self) {
+        self.stdout_ansi = false;
+        self.stderr_ansi = false;
+    }
+
+    /// Enable escape code processing in the Windows consoles that color is written to, disabling
+    /// color for any console that doesn't support it.
+    #[cfg(windows)]
+    pub(crate) fn enable_virtual_terminal(&mut self) {
+        if self.stdout_ansi && color::enable_virtual_terminal(&io::stdout()).is_err() {
+            self.stdout_ansi = false;
+        }
+        if self.stderr_ansi && color::enable_virtual_terminal(&io::stderr()).is_err() {
+            self.stderr_ansi = false;
+        }
+    }
+
+    /// Whether ANSI color is shown for events at `level`.
+    pub(crate) fn ansi_for(&self, level: &Level) -> bool {
+        match self.destination().writer_mode.stream_for(level) {
+            Some(Stream::Stdout) => self.stdout_ansi,
+            Some(Stream::Stderr) => self.stderr_ansi,
+            None => false,
+        }
+    }
+
+    /// Whether all of the output goes to terminals, rather than to a file or a redirected stream.
+    pub(crate) fn is_terminal(&self) -> bool {
+        let destination = self.destination();
+        self.files.is_empty()
+            && destination.file.is_none()
+            && LEVELS.iter().all(|level| {
+                destination
+                    .writer_mode
+                    .stream_for(level)
+                    .is_some_and(Stream::is_terminal)
+            })
+    }
+
+    /// Whether ANSI color is shown for events at any level.
+    pub(crate) fn any_ansi(&self) -> bool {
+        LEVELS.iter().any(|level| self.ansi_for(level))
+    }
+
+    /// A writer for one event at `level`, to its stream and to the files it is appended to.
+    ///
+    /// The stream is replaced by the override of the current thread, if any, see
+    /// `with_writer_override`.
+    fn writer(&self, level: &Level) -> Box<dyn io::Write + '_> {
+        let destination = self.destination();
+        let stream: Option<Box<dyn io::Write>> = match (override_writer(), &destination.writer_mode)
+        {
+            (Some(writer), _) => Some(writer),
+            // Stripped like the streams that don't show color, as the formatter still emits it if
+            // any stream does, e.g. after `set_writer_mode` from a terminal, and so can messages.
+            (None, TracingWriterMode::Custom(CustomWriter(make_writer))) => {
+                Some(Box::new(StripAnsi::new(make_writer())))
+            }
+            (None, writer_mode) => writer_mode
+                .stream_for(level)
+                .map(|stream| self.stream_writer(stream)),
+        };
+        let mode_file = destination
+            .file
+            .is_some()
+            .then(|| -> Box<dyn io::Write + '_> { Box::new(mode_file(destination.clone())) });
+        let files = self
+            .files
+            .iter()
+            .filter(|file| !file.errors_only || *level <= Level::WARN)
+            .map(|file| file.sink.writer(false));
+        mode_file
+            .into_iter()
+            .chain(files)
+            .fold(stream, |writer, file| match writer {
+                Some(writer) => Some(Box::new(Tee(writer, file))),
+                None => Some(file),
+            })
+            .unwrap_or_else(|| Box::new(io::sink()))
+    }
+
+    fn stream_writer(&self, stream: Stream) -> Box<dyn io::Write + '_> {
+        match stream {
+            Stream::Stdout => self
+                .stdout
+                .writer(self.stdout_ansi && color::global_color_enabled()),
+            Stream::Stderr => self
+                .stderr
+                .writer(self.stderr_ansi && color::global_color_enabled()),
+        }
+    }
+}
+
+thread_local! {
+    // Whether a panic has been logged to stderr on this thread since `take_panic_on_stderr`.
+    static PANIC_ON_STDERR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the panic hook has logged a panic to stderr on this thread since it was last asked.
+pub(crate) fn take_panic_on_stderr() -> bool {
+    PANIC_ON_STDERR.take()
+}
+
+pub(crate) const LEVELS: [Level; 5] = [
+    Level::ERROR,
+    Level::WARN,
+    Level::INFO,
+    Level::DEBUG,
+    Level::TRACE,
+];
+
+/// Where the output for one destination of the writer ends up.
+enum Sink {
+    Stdout,
+    Stderr,
+    // Shared by all threads so that concurrent events don't interleave partial lines.
+    File(Mutex<Box<dyn io::Write + Send + Sync>>),
+    // Written to by a dedicated background thread, which receives each event as one message.
+    NonBlocking(NonBlocking),
+    // Collected in a buffer that is written out to a standard stream as `FlushPolicy` says.
+    Buffered(Arc<FlushBuffer>),
+}
+
+impl Sink {
+    /// A writer for one event, that passes color through only if `ansi` is set.
+    ///
+    /// The formatter emits color if any stream shows it, so it's stripped again for a destination
+    /// that doesn't, e.g. stdout redirected to a file while stderr is still a terminal.
+    fn writer(&self, ansi: bool) -> Box<dyn io::Write + '_> {
+        // Each destination is locked for as long as the writer lives, i.e. for the whole of one
+        // event, so that the lines of concurrent events are never torn apart.
+        match self {
+            Sink::Stdout => terminal(|| io::stdout().lock(), &STDOUT_CLOSED, ansi),
+            Sink::Stderr => terminal(|| io::stderr().lock(), &STDERR_CLOSED, ansi),
+            Sink::File(file) if ansi => Box::new(locked_file(lock(file))),
+            Sink::File(file) => Box::new(StripAnsi::new(locked_file(lock(file)))),
+            Sink::NonBlocking(writer) if ansi => Box::new(writer.clone()),
+            Sink::NonBlocking(writer) => Box::new(StripAnsi::new(writer.clone())),
+            Sink::Buffered(buffer) => buffer.writer(ansi),
+        }
+    }
+
+    /// Collect the output for the standard stream in a buffer, returning it for it to be drained.
+    fn buffer(&mut self, line_buffered: bool) -> Option<Arc<FlushBuffer>> {
+        // The color is stripped as the output is collected, so it's passed through as it's written.
+        let buffer = match self {
+            Sink::Stdout => FlushBuffer::new(line_buffered, || {
+                terminal(|| io::stdout().lock(), &STDOUT_CLOSED, true)
+            }),
+            Sink::Stderr => FlushBuffer::new(line_buffered, || {
+                terminal(|| io::stderr().lock(), &STDERR_CLOSED, true)
+            }),
+            Sink::File(_) | Sink::NonBlocking(_) | Sink::Buffered(_) => return None,
+        };
+        *self = Sink::Buffered(buffer.clone());
+        Some(buffer)
+    }
+
+    fn make_non_blocking(&mut self) -> Option<WorkerGuard> {
+        let (writer, guard) = match std::mem::replace(self, Sink::Stdout) {
+            Sink::Stdout => tracing_appender::non_blocking(AboveTerminal::new(|| {
+                Box::new(DiscardClosedPipe::new(io::stdout(), &STDOUT_CLOSED))
+            })),
+            Sink::Stderr => tracing_appender::non_blocking(AboveTerminal::new(|| {
+                Box::new(DiscardClosedPipe::new(io::stderr(), &STDERR_CLOSED))
+            })),
+            Sink::File(file) => {
+                tracing_appender::non_blocking(file.into_inner().unwrap_or_else(|e| e.into_inner()))
+            }
+            sink @ (Sink::NonBlocking(_) | Sink::Buffered(_)) => {
+                *self = sink;
+                return None;
+            }
+        };
+        *self = Sink::NonBlocking(writer);
+        Some(guard)
+    }
+}
+
+impl<'a> MakeWriter<'a> for StdioTracingWriter {
+    type Writer = Box<dyn io::Write + 'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        // We must have an implementation of `make_writer` that makes
+        // a "default" writer without any configuring metadata. Let's
+        // just use the destination of INFO events in that case.
+        self.writer(&Level::INFO)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        if meta.target() == PANIC_TARGET
+            && self.destination().writer_mode.stream_for(meta.level()) == Some(Stream::Stderr)
+            && override_writer().is_none()
+        {
+            PANIC_ON_STDERR.set(true);
+        }
+        // Here's where we can implement our special behavior. We'll
+        // check if the metadata's verbosity level is WARN or ERROR,
+        // and return stderr in that case.
+        self.writer(meta.level())
+    }
+}
+
+/// Flushes the log output when dropped, see `init_tracing_subscriber_with_guard`.
+///
+/// With `TracingSubscriberOptions::non_blocking`, dropping the guard also waits for the background
+/// writer threads to write out all pending events, and stops them. Likewise for the thread of
+/// `FlushPolicy::Interval`.
+#[must_use = "dropping the guard flushes the log output, keep it alive until the program exits"]
+pub struct TracingGuard {
+    dedupe: Option<DedupeGuard>,
+    flusher: Option<Flusher>,
+    workers: Vec<WorkerGuard>,
+    json_array: Option<JsonArrayCloser>,
+    #[cfg(feature = "opentelemetry")]
+    tracer_provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+impl fmt::Debug for TracingGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("TracingGuard");
+        debug
+            .field("dedupe", &self.dedupe.is_some())
+            .field("flusher", &self.flusher)
+            .field("workers", &self.workers)
+            .field("json_array", &self.json_array.is_some());
+        #[cfg(feature = "opentelemetry")]
+        debug.field("tracer_provider", &self.tracer_provider.is_some());
+        debug.finish()
+    }
+}
+
+impl TracingGuard {
+    pub(crate) fn new(
+        workers: Vec<WorkerGuard>,
+        dedupe: Option<DedupeGuard>,
+        flusher: Option<Flusher>,
+    ) -> Self {
+        Self {
+            dedupe,
+            flusher,
+            workers,
+            json_array: None,
+            #[cfg(feature = "opentelemetry")]
+            tracer_provider: None,
+        }
+    }
+
+    /// Give up on dropping the guard, keeping the background writers of `non_blocking` running for
+    /// the rest of the program, and leaving the pending repetitions of `dedupe_window` to `flush`.
+    pub(crate) fn forget(mut self) {
+        if let Some(dedupe) = self.dedupe.take() {
+            dedupe.forget();
+        }
+        std::mem::forget(self);
+    }
+
+    /// Close the array of `LogFormat::JsonArray` with `closer` when dropped.
+    pub(crate) fn close_json_array(&mut self, closer: JsonArrayCloser) {
+        self.json_array = Some(closer);
+    }
+
+    /// Keep the provider of the tracer spans are exported with alive, as the tracer only refers to
+    /// it weakly, and shut it down when dropped.
+    #[cfg(feature = "opentelemetry")]
+    pub(crate) fn keep_tracer_provider(
+        &mut self,
+        tracer_provider: opentelemetry_sdk::trace::TracerProvider,
+    ) {
+        self.tracer_provider = Some(tracer_provider);
+    }
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        // The pending repetitions are logged first, so that the writers still write them out.
+        self.dedupe = None;
+        // Closed before the buffered and background writers are drained, so that the `]` is
+        // written out with the rest.
+        if let Some(close) = self.json_array.take() {
+            close();
+        }
+        self.flusher = None;
+        self.workers.clear();
+        flush();
+    }
+}
+
+/// Flush everything logged so far to its destination, e.g. before calling `std::process::exit`
+/// or at the end of a build phase.
+///
+/// Log files are written to without buffering, so this only needs to write out the output buffered
+/// by the `TracingSubscriberOptions::flush_policy` and flush stdout and stderr, after logging the
+/// repetitions held back by `TracingSubscriberOptions::dedupe_window` of a subscriber installed
+/// without keeping its guard, e.g. by `init_tracing_subscriber`. It doesn't wait for
+/// the background writer threads of `TracingSubscriberOptions::non_blocking`, drop the
+/// `TracingGuard` for that.
+pub fn flush() {
+    crate::dedupe::log_forgotten();
+    flush::drain_all();
+    // A failure to flush the streams couldn't be reported on them, so `flush` returns nothing.
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+}
+
+#[cfg(feature = "indicatif")]
+use crate::progress::above_progress;
+
+#[cfg(not(feature = "indicatif"))]
+fn above_progress<'a>(writer: impl io::Write + 'a) -> Box<dyn io::Write + 'a> {
+    Box::new(writer)
+}
+
+/// A writer for one event to the standard stream locked by `lock`, above the spinner or the
+/// progress bars if any are active. Once the stream is found `closed`, the output is discarded.
+fn terminal<S: RawStream + 'static>(
+    lock: fn() -> S,
+    closed: &'static AtomicBool,
+    ansi: bool,
+) -> Box<dyn io::Write> {
+    let writer = move || DiscardClosedPipe::new(terminal_writer(lock(), ansi), closed);
+    above_spinner(move || Box::new(writer())).unwrap_or_else(|| above_progress(writer()))
+}
+
+/// Writes each message of the background thread of `non_blocking`, i.e. each event, with a writer
+/// made by `make_writer`, above the spinner or the progress bars if any are active, like `terminal`
+/// does on the thread of the event.
+pub(crate) struct AboveTerminal<F>(F);
+
+impl<F: Fn() -> Box<dyn io::Write> + Clone + 'static> AboveTerminal<F> {
+    pub(crate) fn new(make_writer: F) -> Self {
+        Self(make_writer)
+    }
+}
+
+impl<F: Fn() -> Box<dyn io::Write> + Clone + 'static> io::Write for AboveTerminal<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let make_writer = self.0.clone();
+        let mut writer =
+            above_spinner(make_writer.clone()).unwrap_or_else(|| above_progress(make_writer()));
+        writer.write_all(buf)?;
+        writer.flush()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Each message is flushed as it's written.
+        Ok(())
+    }
+}
+
+// Whether stdout and stderr have been closed by the reader of their pipe, e.g. `head` exiting
+// early, which the standard streams remain for the rest of the program.
+static STDOUT_CLOSED: AtomicBool = AtomicBool::new(false);
+static STDERR_CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// Discards everything written once a write failed with `BrokenPipe`, setting `closed`, so that
+/// piping the output into e.g. `head` neither fails every event that follows nor panics.
+struct DiscardClosedPipe<'a, W> {
+    inner: W,
+    closed: &'a AtomicBool,
+}
+
+impl<'a, W> DiscardClosedPipe<'a, W> {
+    fn new(inner: W, closed: &'a AtomicBool) -> Self {
+        Self { inner, closed }
+    }
+
+    fn discard<T>(&self, result: io::Result<T>, discarded: T) -> io::Result<T> {
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                self.closed.store(true, Ordering::Relaxed);
+                Ok(discarded)
+            }
+            result => result,
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for DiscardClosedPipe<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed.load(Ordering::Relaxed) {
+            return Ok(buf.len());
+        }
+        let result = self.inner.write(buf);
+        self.discard(result, buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.closed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let result = self.inner.flush();
+        self.discard(result, ())
+    }
+}
+
+/// Adapt the escape codes written to the standard stream `raw`, stripping them unless `ansi` is set.
+///
+/// On Windows, this enables escape code processing in the console, falling back to the console API
+/// for consoles that don't support it.
+fn terminal_writer<S: RawStream>(raw: S, ansi: bool) -> AutoStream<S> {
+    let choice = match ansi {
+        true => ColorChoice::Always,
+        false => ColorChoice::Never,
+    };
+    AutoStream::new(raw, choice)
+}
+
+/// Open `path` for appending, creating it and its parent directories if missing, rotating it as
+/// configured by `rolling` and ending the lines written to it with `line_ending`.
+///
+/// Nothing is written but the events, e.g. no byte order mark, and those are valid UTF-8 as they
+/// are written through `StripAnsi`, for strict parsers of the logs.
+pub(crate) fn open_log_file(
+    path: &Path,
+    rolling: Option<Rolling>,
+    line_ending: LineEnding,
+) -> io::Result<Box<dyn io::Write + Send + Sync>> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let file: Box<dyn io::Write + Send + Sync> = match rolling {
+        Some(rolling) => Box::new(RollingFile::open(path, rolling)?),
+        None => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+    };
+    Ok(match line_ending.is_crlf() {
+        true => Box::new(CrLf::new(file)),
+        false => file,
+    })
+}
+
+/// Writes the `\n` line endings as `\r\n`, leaving those that already are as they are.
+struct CrLf<W> {
+    inner: W,
+    // Whether the last byte written was `\r`.
+    after_cr: bool,
+}
+
+impl<W> CrLf<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            after_cr: false,
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for CrLf<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut converted = Vec::with_capacity(buf.len() + buf.len() / 32);
+        for &byte in buf {
+            if byte == b'\n' && !self.after_cr {
+                converted.push(b'\r');
+            }
+            converted.push(byte);
+            self.after_cr = byte == b'\r';
+        }
+        // Whole, so that a rotated file still only ever receives whole events.
+        self.inner.write_all(&converted)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panic while writing a log line leaves nothing worth protecting, so keep logging.
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A writer for one event that holds the file lock for its whole lifetime.
+///
+/// The event is written to the file in one go, so that a rotated file only ever receives whole
+/// events.
+pub(crate) type LockedFile<'a> = BoxedEvent<'a, io::Sink>;
+
+pub(crate) fn locked_file(
+    mut file: MutexGuard<'_, Box<dyn io::Write + Send + Sync>>,
+) -> LockedFile<'_> {
+    BufferedEvent::boxed(io::sink(), move |buffer, _| {
+        file.write_all(buffer)?;
+        file.flush()
+    })
+}
+
+/// A writer for one event for the file of `destination`, which is only looked up once the event
+/// is complete, so that the destination can be switched while the event is written.
+fn mode_file<'a>(destination: Arc<Destination>) -> BoxedEvent<'a, io::Sink> {
+    BufferedEvent::boxed(io::sink(), move |buffer, _| {
+        let Some(file) = &destination.file else {
+            return Ok(());
+        };
+        let mut writer = file.writer(false);
+        writer.write_all(buffer)?;
+        writer.flush()
+    })
+}
+
+/// Forwards every write to both writers.
+struct Tee<A, B>(A, B);
+
+impl<A: io::Write, B: io::Write> io::Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(buf)?;
+        self.1.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        self.1.flush()
+    }
+}
+
+#[cfg(test)]
+impl StdioTracingWriter {
+    /// Write what goes to stderr to `capture` instead.
+    pub(crate) fn capture_stderr(&mut self, capture: crate::testing::CapturingWriter) {
+        self.stderr = Sink::File(Mutex::new(Box::new(capture)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::paint;
+    use crate::testing::CapturingWriter;
+    use crate::{build_subscriber, fmt_layer, TracingSubscriberOptions, ENV_LOCK};
+    use anstyle::AnsiColor;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    #[test]
+    fn test_file_writer_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("forc.log");
+        let writer = StdioTracingWriter::new(
+            TracingWriterMode::File(path.clone()),
+            Some(true),
+            None,
+            LineEnding::Lf,
+        )
+        .unwrap();
+        assert!(!writer.any_ansi());
+
+        // Even if the formatter were to emit color, it must not end up in the file.
+        let subscriber =
+            Registry::default().with(fmt_layer(&Default::default(), true, None, writer).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("{}", paint("Compiling", AnsiColor::Green.on_default()));
+            tracing::error!("{}", paint("Failed", AnsiColor::Red.on_default()));
+        });
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Compiling\nFailed\n");
+    }
+
+    #[test]
+    fn test_channel_writer() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let (writer_mode, receiver) = channel_writer();
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(writer_mode)
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "forc", "Compiling");
+            tracing::warn!(target: "forc", "Unused");
+        });
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            ["Compiling", "Unused"]
+        );
+
+        // Partial writes are held back until the line is complete.
+        let (sender, receiver) = mpsc::channel();
+        let mut writer = ChannelWriter::new(sender);
+        writer.write_all(b"Comp").unwrap();
+        assert!(receiver.try_recv().is_err());
+        writer.write_all(b"iling\nFin").unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), "Compiling");
+        drop(writer);
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), ["Fin"]);
+    }
+
+    #[test]
+    fn test_channel_writer_without_color() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let (writer_mode, receiver) = channel_writer();
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(writer_mode)
+            .ansi(true)
+            .display_level(true)
+            .build();
+        let subscriber = build_subscriber(options).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "forc", "\x1b[1mUnused\x1b[0m");
+        });
+        let lines: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(lines, [" WARN Unused"]);
+        assert!(!lines[0].contains("\x1b["));
+    }
+
+    #[test]
+    fn test_split_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let split = SplitFiles {
+            all: dir.path().join("build.log"),
+            errors: dir.path().join("errors.log"),
+        };
+        let mut writer =
+            StdioTracingWriter::new(TracingWriterMode::Null, Some(true), None, LineEnding::Lf)
+                .unwrap();
+        writer.split_files(&split).unwrap();
+        let subscriber =
+            Registry::default().with(fmt_layer(&Default::default(), true, None, writer).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("Compiling");
+            tracing::warn!("{}", paint("Unused", AnsiColor::Yellow.on_default()));
+            tracing::error!("Failed");
+            tracing::info!("Finished");
+        });
+        assert_eq!(
+            fs::read_to_string(&split.all).unwrap(),
+            "Compiling\nUnused\nFailed\nFinished\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&split.errors).unwrap(),
+            "Unused\nFailed\n"
+        );
+    }
+
+    #[test]
+    fn test_file_line_ending() {
+        let dir = tempfile::tempdir().unwrap();
+        let native = match cfg!(windows) {
+            true => "Compiling\r\nFailed\r\n",
+            false => "Compiling\nFailed\n",
+        };
+        for (line_ending, expected) in [
+            (LineEnding::Lf, "Compiling\nFailed\n"),
+            (LineEnding::CrLf, "Compiling\r\nFailed\r\n"),
+            (LineEnding::Native, native),
+        ] {
+            let path = dir.path().join(format!("{line_ending:?}.log"));
+            let terminal = CapturingWriter::new();
+            let custom = {
+                let terminal = terminal.clone();
+                CustomWriter::new(move || Box::new(terminal.clone()))
+            };
+            let mode = TracingWriterMode::Custom(custom);
+            let mut writer = StdioTracingWriter::new(mode, None, None, line_ending).unwrap();
+            let split = SplitFiles {
+                all: path.clone(),
+                errors: dir.path().join(format!("{line_ending:?}.errors.log")),
+            };
+            writer.split_files(&split).unwrap();
+            let subscriber = Registry::default()
+                .with(fmt_layer(&Default::default(), false, None, writer).unwrap());
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info!("Compiling");
+                tracing::error!("Failed");
+            });
+            assert_eq!(fs::read(&path).unwrap(), expected.as_bytes());
+            // The terminal always gets `\n`.
+            assert_eq!(terminal.contents(), "Compiling\nFailed\n");
+        }
+
+        // Line endings that already are `\r\n` are left as they are, also across writes.
+        let mut converted = CrLf::new(Vec::new());
+        converted.write_all(b"Compiling\r").unwrap();
+        converted.write_all(b"\nFailed\n").unwrap();
+        assert_eq!(converted.inner, b"Compiling\r\nFailed\r\n");
+    }
+
+    #[test]
+    fn test_log_file_is_valid_utf8() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        let writer = StdioTracingWriter::new(
+            TracingWriterMode::File(path.clone()),
+            None,
+            None,
+            LineEnding::Lf,
+        )
+        .unwrap();
+        // E.g. the output of an external tool, passed on as it is.
+        let mut output = b"Compiling \xff\xfe my_contract \xe2\x9c".to_vec();
+        output.extend_from_slice("\u{2713}\n".as_bytes());
+        let mut event = writer.make_writer();
+        // A sequence split across writes is kept whole.
+        event.write_all(&output[..output.len() - 2]).unwrap();
+        event.write_all(&output[output.len() - 2..]).unwrap();
+        drop(event);
+        let subscriber =
+            Registry::default().with(fmt_layer(&Default::default(), false, None, writer).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            let bytes = b"Finished \xc0 in 1.2s";
+            tracing::info!("{}", String::from_utf8_lossy(bytes));
+        });
+
+        let contents = fs::read(&path).unwrap();
+        assert!(!contents.starts_with("\u{feff}".as_bytes()));
+        assert_eq!(
+            String::from_utf8(contents).unwrap(),
+            "Compiling \u{fffd}\u{fffd} my_contract \u{fffd}\u{2713}\nFinished \u{fffd} in 1.2s\n"
+        );
+    }
+
+    #[test]
+    fn test_concurrent_events_are_rotated_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        let rolling = Rolling {
+            rotation: crate::Rotation::Size(1024),
+            max_files: None,
+            compress: false,
+        };
+        let writer = StdioTracingWriter::new(
+            TracingWriterMode::File(path.clone()),
+            None,
+            Some(rolling),
+            LineEnding::Lf,
+        )
+        .unwrap();
+        let subscriber =
+            Registry::default().with(fmt_layer(&Default::default(), false, None, writer).unwrap());
+        let dispatch = tracing::Dispatch::new(subscriber);
+        let threads: Vec<_> = (0..4)
+            .map(|thread| {
+                let dispatch = dispatch.clone();
+                std::thread::spawn(move || {
+                    tracing::dispatcher::with_default(&dispatch, || {
+                        for i in 0..100 {
+                            tracing::info!("thread {thread} event {i:02}");
+                        }
+                    })
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut lines = Vec::new();
+        for entry in fs::read_dir(dir.path()).unwrap() {
+            let contents = fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(contents.len() <= 1024);
+            lines.extend(contents.lines().map(str::to_string));
+        }
+        lines.sort();
+        let mut expected: Vec<_> = (0..4)
+            .flat_map(|thread| (0..100).map(move |i| format!("thread {thread} event {i:02}")))
+            .collect();
+        expected.sort();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_concurrent_events_are_not_torn() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        let writer = StdioTracingWriter::new(
+            TracingWriterMode::File(path.clone()),
+            None,
+            None,
+            LineEnding::Lf,
+        )
+        .unwrap();
+        let subscriber =
+            Registry::default().with(fmt_layer(&Default::default(), false, None, writer).unwrap());
+        let dispatch = tracing::Dispatch::new(subscriber);
+
+        let body = "x".repeat(16 * 1024);
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let dispatch = dispatch.clone();
+                let body = body.clone();
+                std::thread::spawn(move || {
+                    tracing::dispatcher::with_default(&dispatch, || {
+                        for _ in 0..50 {
+                            tracing::info!("<{thread}{body}{thread}>");
+                        }
+                    })
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 8 * 50);
+        for line in lines {
+            let thread = &line[1..2];
+            assert_eq!(line, format!("<{thread}{body}{thread}>"));
+        }
+    }
+
+    #[test]
+    fn test_drop_guard() {
+        drop(TracingGuard::new(Vec::new(), None, None));
+        flush();
+    }
+
+    #[test]
+    fn test_non_blocking_writes_everything_before_guard_drops() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        let mut writer = StdioTracingWriter::new(
+            TracingWriterMode::File(path.clone()),
+            None,
+            None,
+            LineEnding::Lf,
+        )
+        .unwrap();
+        let guard = TracingGuard::new(writer.make_non_blocking(), None, None);
+
+        let subscriber =
+            Registry::default().with(fmt_layer(&Default::default(), false, None, writer).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..10_000 {
+                tracing::trace!("event {i}");
+            }
+        });
+        drop(guard);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 10_000);
+        assert_eq!(lines[9_999], "event 9999");
+    }
+
+    #[test]
+    fn test_closed_pipe_is_discarded() {
+        /// A pipe whose reader has gone away, counting the writes attempted.
+        struct ClosedPipe(usize);
+
+        impl io::Write for ClosedPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                self.0 += 1;
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let closed = AtomicBool::new(false);
+        let mut pipe = ClosedPipe(0);
+        let mut writer = DiscardClosedPipe::new(&mut pipe, &closed);
+        writer.write_all(b"Compiling\n").unwrap();
+        writer.write_all(b"Finished\n").unwrap();
+        writer.flush().unwrap();
+        assert!(closed.load(Ordering::Relaxed));
+        assert_eq!(pipe.0, 1);
+
+        // Other errors are still reported.
+        let closed = AtomicBool::new(false);
+        let mut buffer = [0u8; 4];
+        let mut full = DiscardClosedPipe::new(&mut buffer[..], &closed);
+        assert!(full.write_all(b"Compiling").is_err());
+        assert!(!closed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_tee_strips_ansi_from_file_copy() {
+        let mut terminal = Vec::new();
+        let mut file = Vec::new();
+        let painted = paint("Compiling", AnsiColor::Green.on_default());
+        Tee(&mut terminal, StripAnsi::new(&mut file))
+            .write_all(painted.as_bytes())
+            .unwrap();
+        assert_eq!(terminal, painted.as_bytes());
+        assert_eq!(file, b"Compiling");
+    }
+
+    #[test]
+    fn test_terminal_writer_strips_ansi_unless_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stdout");
+        let painted = paint("Compiling", AnsiColor::Green.on_default());
+        for ansi in [false, true] {
+            let mut writer = terminal_writer(fs::File::create(&path).unwrap(), ansi);
+            writer.write_all(painted.as_bytes()).unwrap();
+            drop(writer);
+            let expected = if ansi { painted.as_str() } else { "Compiling" };
+            assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_set_writer_mode() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let custom = |capture: &CapturingWriter| {
+            let capture = capture.clone();
+            TracingWriterMode::Custom(CustomWriter::new(move || Box::new(capture.clone())))
+        };
+        let (before, after) = (CapturingWriter::new(), CapturingWriter::new());
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(custom(&before))
+            .build();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forc.log");
+        // The handle `set_writer_mode` switches the installed subscriber with.
+        let (subscriber, _guard, globals) = crate::build(&options, Vec::new()).unwrap();
+        let destination = globals.destination;
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("Compiling");
+            destination.set_writer_mode(custom(&after)).unwrap();
+            tracing::info!("Finished");
+            destination
+                .set_writer_mode(TracingWriterMode::File(path.clone()))
+                .unwrap();
+            tracing::error!("Failed");
+
+            // A file that can't be opened leaves the output as it was.
+            let not_a_dir = path.join("forc.log");
+            assert!(matches!(
+                destination.set_writer_mode(TracingWriterMode::File(not_a_dir)),
+                Err(TracingInitError::OpenLogFile { .. })
+            ));
+            tracing::warn!("Retrying");
+        });
+        assert_eq!(before.contents(), "Compiling\n");
+        assert_eq!(after.contents(), "Finished\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Failed\nRetrying\n");
+
+        // Switching the output of a subscriber that is gone is an error.
+        assert!(matches!(
+            destination.set_writer_mode(custom(&after)),
+            Err(TracingInitError::NotInstalled)
+        ));
+    }
+
+    #[test]
+    fn test_custom_writer_strips_color() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let capture = CapturingWriter::new();
+        let custom = {
+            let capture = capture.clone();
+            TracingWriterMode::Custom(CustomWriter::new(move || Box::new(capture.clone())))
+        };
+        // The formatter colors the level for stdout, which the custom writer is switched to.
+        let options = TracingSubscriberOptions::builder()
+            .writer_mode(TracingWriterMode::Stdout)
+            .ansi(true)
+            .display_level(true)
+            .build();
+        let (subscriber, _guard, globals) = crate::build(&options, Vec::new()).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            globals.destination.set_writer_mode(custom).unwrap();
+            tracing::warn!("\x1b[1mUnused\x1b[0m");
+        });
+        assert_eq!(capture.contents(), " WARN Unused\n");
+    }
+
+    #[test]
+    fn test_tee_falls_back_to_terminal_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let not_a_dir = dir.path().join("file");
+        fs::write(&not_a_dir, "").unwrap();
+        let mode = TracingWriterMode::Tee {
+            file: not_a_dir.join("forc.log"),
+        };
+        let writer = StdioTracingWriter::new(mode, None, None, LineEnding::Lf).unwrap();
+        assert!(writer.destination().file.is_none());
+        assert_eq!(
+            writer.destination().writer_mode.stream_for(&Level::ERROR),
+            Some(Stream::Stderr)
+        );
+    }
+}